@@ -0,0 +1,184 @@
+use crate::{
+    ray::{Interval, Ray, EMPTY_INTERVAL},
+    vec3::{Point3, Vec3},
+};
+
+// Axis-aligned bounding box stored as one interval per axis.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub x: Interval,
+    pub y: Interval,
+    pub z: Interval,
+}
+
+impl Default for Aabb {
+    // The empty box, which any other box absorbs when combined.
+    fn default() -> Self {
+        Self::new(EMPTY_INTERVAL, EMPTY_INTERVAL, EMPTY_INTERVAL)
+    }
+}
+
+impl Aabb {
+    pub fn new(x: Interval, y: Interval, z: Interval) -> Self {
+        let mut bbox = Self { x, y, z };
+        bbox.pad_to_minimums();
+        bbox
+    }
+
+    // Treat the two points a and b as extrema for the bounding box, so we don't require a
+    // particular minimum/maximum coordinate order.
+    pub fn from_points(a: Point3, b: Point3) -> Self {
+        Self::new(
+            Interval::new(a.x.min(b.x), a.x.max(b.x)),
+            Interval::new(a.y.min(b.y), a.y.max(b.y)),
+            Interval::new(a.z.min(b.z), a.z.max(b.z)),
+        )
+    }
+
+    pub fn surrounding(box0: &Aabb, box1: &Aabb) -> Self {
+        Self {
+            x: Interval::enclosing(&box0.x, &box1.x),
+            y: Interval::enclosing(&box0.y, &box1.y),
+            z: Interval::enclosing(&box0.z, &box1.z),
+        }
+    }
+
+    pub fn axis_interval(&self, n: usize) -> &Interval {
+        match n {
+            1 => &self.y,
+            2 => &self.z,
+            _ => &self.x,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.x.min > self.x.max || self.y.min > self.y.max || self.z.min > self.z.max
+    }
+
+    pub fn centroid(&self) -> Point3 {
+        Point3::new(
+            (self.x.min + self.x.max) * 0.5,
+            (self.y.min + self.y.max) * 0.5,
+            (self.z.min + self.z.max) * 0.5,
+        )
+    }
+
+    pub fn extent(&self) -> Vec3 {
+        Vec3::new(self.x.size(), self.y.size(), self.z.size())
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let d = self.extent();
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    // Returns the index of the longest axis of the bounding box.
+    pub fn longest_axis(&self) -> usize {
+        if self.x.size() > self.y.size() {
+            if self.x.size() > self.z.size() {
+                0
+            } else {
+                2
+            }
+        } else if self.y.size() > self.z.size() {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn hit(&self, r: &Ray, ray_t: Interval) -> bool {
+        self.ray_interval(r, ray_t).is_some()
+    }
+
+    // The part of `ray_t` during which the ray is inside the box, if any.
+    pub fn ray_interval(&self, r: &Ray, mut ray_t: Interval) -> Option<Interval> {
+        for axis in 0..3 {
+            let ax = self.axis_interval(axis);
+            let adinv = 1.0 / r.direction[axis];
+
+            let t0 = (ax.min - r.origin[axis]) * adinv;
+            let t1 = (ax.max - r.origin[axis]) * adinv;
+
+            let (t_near, t_far) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+            if t_near > ray_t.min {
+                ray_t.min = t_near;
+            }
+            if t_far < ray_t.max {
+                ray_t.max = t_far;
+            }
+
+            if ray_t.max <= ray_t.min {
+                return None;
+            }
+        }
+        Some(ray_t)
+    }
+
+    // Adjust the AABB so that no side is narrower than some delta, padding if necessary.
+    fn pad_to_minimums(&mut self) {
+        let delta = 0.0001;
+        if self.x.size() >= 0.0 && self.x.size() < delta {
+            self.x = self.x.expand(delta);
+        }
+        if self.y.size() >= 0.0 && self.y.size() < delta {
+            self.y = self.y.expand(delta);
+        }
+        if self.z.size() >= 0.0 && self.z.size() < delta {
+            self.z = self.z.expand(delta);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Aabb;
+    use crate::ray::{Interval, Ray};
+    use crate::vec3::{Point3, Vec3};
+
+    fn unit_box() -> Aabb {
+        Aabb::from_points(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0))
+    }
+
+    #[test]
+    fn test_hit() {
+        let bbox = unit_box();
+        let through = Ray::new(Vec3::new(0.5, 0.5, -1.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(bbox.hit(&through, Interval::new(0.0, f32::INFINITY)));
+        // The box is beyond the allowed range.
+        assert!(!bbox.hit(&through, Interval::new(0.0, 0.5)));
+
+        let beside = Ray::new(Vec3::new(1.5, 0.5, -1.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(!bbox.hit(&beside, Interval::new(0.0, f32::INFINITY)));
+
+        let away = Ray::new(Vec3::new(0.5, 0.5, -1.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(!bbox.hit(&away, Interval::new(0.0, f32::INFINITY)));
+
+        let inside = bbox.ray_interval(&through, Interval::new(0.0, f32::INFINITY));
+        assert_eq!(inside, Some(Interval::new(1.0, 2.0)));
+        let clipped = bbox.ray_interval(&through, Interval::new(1.5, 10.0));
+        assert_eq!(clipped, Some(Interval::new(1.5, 2.0)));
+    }
+
+    #[test]
+    fn test_surrounding_and_empty() {
+        let other = Aabb::from_points(Point3::new(-1.0, 2.0, 0.5), Point3::new(0.0, 3.0, 0.5));
+        let both = Aabb::surrounding(&unit_box(), &other);
+        assert_eq!(both.x, Interval::new(-1.0, 1.0));
+        assert_eq!(both.y, Interval::new(0.0, 3.0));
+        assert_eq!(Aabb::surrounding(&Aabb::default(), &unit_box()), unit_box());
+        assert!(Aabb::default().is_empty());
+        assert_eq!(Aabb::default().surface_area(), 0.0);
+    }
+
+    #[test]
+    fn test_flat_box_is_padded() {
+        let flat = Aabb::from_points(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 0.0));
+        assert!(flat.z.size() > 0.0);
+        assert_eq!(unit_box().surface_area(), 6.0);
+        assert_eq!(flat.longest_axis(), 1);
+    }
+}
@@ -0,0 +1,241 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    ray::{HitRecord, Hittable, HittableList, Interval, Ray},
+};
+
+// Number of centroid buckets evaluated per axis by the surface area heuristic.
+const SAH_BUCKETS: usize = 12;
+
+// Bounding volume hierarchy node; leaves are the scene objects themselves.
+pub struct BvhNode {
+    left: Arc<dyn Hittable>,
+    right: Arc<dyn Hittable>,
+    bbox: Aabb,
+}
+
+impl BvhNode {
+    pub fn from_list(list: HittableList) -> Self {
+        Self::new(list.objects)
+    }
+
+    pub fn new(mut objects: Vec<Arc<dyn Hittable>>) -> Self {
+        match objects.len() {
+            0 => {
+                let empty: Arc<dyn Hittable> = Arc::new(HittableList::new());
+                Self::from_children(empty.clone(), empty)
+            }
+            1 => Self::from_children(objects[0].clone(), objects[0].clone()),
+            2 => Self::from_children(objects[0].clone(), objects[1].clone()),
+            _ => {
+                let right = Self::partition(&mut objects);
+                Self::from_children(Self::subtree(objects), Self::subtree(right))
+            }
+        }
+    }
+
+    fn from_children(left: Arc<dyn Hittable>, right: Arc<dyn Hittable>) -> Self {
+        let bbox = Aabb::surrounding(&left.bounding_box(), &right.bounding_box());
+        Self { left, right, bbox }
+    }
+
+    fn subtree(mut objects: Vec<Arc<dyn Hittable>>) -> Arc<dyn Hittable> {
+        if objects.len() == 1 {
+            objects.pop().unwrap()
+        } else {
+            Arc::new(Self::new(objects))
+        }
+    }
+
+    // Index of the SAH bucket holding the object's centroid along `axis`.
+    fn bucket(object: &Arc<dyn Hittable>, axis: usize, range: &Interval) -> usize {
+        let c = object.bounding_box().centroid()[axis];
+        let b = ((c - range.min) / range.size() * SAH_BUCKETS as f32) as usize;
+        b.min(SAH_BUCKETS - 1)
+    }
+
+    // Splits `objects` in place using the surface area heuristic and returns the right half.
+    // Falls back to a median split along the longest axis when no bucket boundary separates
+    // the centroids.
+    fn partition(objects: &mut Vec<Arc<dyn Hittable>>) -> Vec<Arc<dyn Hittable>> {
+        let centroids = objects
+            .iter()
+            .map(|o| o.bounding_box().centroid())
+            .fold(Aabb::default(), |acc, c| {
+                Aabb::surrounding(&acc, &Aabb::from_points(c, c))
+            });
+
+        let mut best: Option<(f32, usize, usize)> = None;
+        for axis in 0..3 {
+            let range = centroids.axis_interval(axis);
+            if range.size() <= 0.0 {
+                continue;
+            }
+            let mut counts = [0usize; SAH_BUCKETS];
+            let mut bounds = [Aabb::default(); SAH_BUCKETS];
+            for o in objects.iter() {
+                let b = Self::bucket(o, axis, range);
+                counts[b] += 1;
+                bounds[b] = Aabb::surrounding(&bounds[b], &o.bounding_box());
+            }
+
+            // Cost of splitting after bucket i, up to the constant traversal term and the
+            // parent's surface area, which are the same for every candidate.
+            for split in 0..SAH_BUCKETS - 1 {
+                let (mut left_box, mut right_box) = (Aabb::default(), Aabb::default());
+                let (mut left_count, mut right_count) = (0, 0);
+                for b in 0..=split {
+                    left_box = Aabb::surrounding(&left_box, &bounds[b]);
+                    left_count += counts[b];
+                }
+                for b in split + 1..SAH_BUCKETS {
+                    right_box = Aabb::surrounding(&right_box, &bounds[b]);
+                    right_count += counts[b];
+                }
+                if left_count == 0 || right_count == 0 {
+                    continue;
+                }
+                let cost = left_box.surface_area() * left_count as f32
+                    + right_box.surface_area() * right_count as f32;
+                if best.map_or(true, |(best_cost, _, _)| cost < best_cost) {
+                    best = Some((cost, axis, split));
+                }
+            }
+        }
+
+        match best {
+            Some((_, axis, split)) => {
+                let range = *centroids.axis_interval(axis);
+                let (left, right): (Vec<_>, Vec<_>) = objects
+                    .drain(..)
+                    .partition(|o| Self::bucket(o, axis, &range) <= split);
+                *objects = left;
+                right
+            }
+            None => {
+                let key = |o: &Arc<dyn Hittable>, axis: usize| o.bounding_box().centroid()[axis];
+                let axis = centroids.longest_axis();
+                objects.sort_by(|a, b| key(a, axis).total_cmp(&key(b, axis)));
+                objects.split_off(objects.len() / 2)
+            }
+        }
+    }
+}
+
+impl Hittable for BvhNode {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        if !self.bbox.hit(r, ray_t) {
+            return false;
+        }
+
+        let hit_left = self.left.hit(r, ray_t, rec);
+        let right_t = Interval::new(ray_t.min, if hit_left { rec.t } else { ray_t.max });
+        let hit_right = self.right.hit(r, right_t, rec);
+
+        hit_left || hit_right
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: Interval) -> f32 {
+        if !self.bbox.hit(r, ray_t) {
+            return 1.0;
+        }
+        let left = self.left.transmittance(r, ray_t);
+        if left <= 0.0 {
+            return 0.0;
+        }
+        left * self.right.transmittance(r, ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::BvhNode;
+    use crate::material::Lambertian;
+    use crate::ray::{HitRecord, Hittable, HittableList, Interval, Ray, Sphere};
+    use crate::vec3::{Color, Point3, Vec3};
+
+    fn random_vec(rng: &mut StdRng, min: f32, max: f32) -> Vec3 {
+        Vec3::new(
+            rng.gen_range(min..max),
+            rng.gen_range(min..max),
+            rng.gen_range(min..max),
+        )
+    }
+
+    fn random_spheres(rng: &mut StdRng, count: usize) -> HittableList {
+        let mat = Arc::new(Lambertian::new(Color::ones()));
+        let mut list = HittableList::new();
+        for _ in 0..count {
+            let center = random_vec(rng, -10.0, 10.0);
+            list.add(Arc::new(Sphere::new(
+                center,
+                rng.gen_range(0.05..0.8),
+                mat.clone(),
+            )));
+        }
+        list
+    }
+
+    #[test]
+    fn test_bvh_matches_linear_list() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let list = random_spheres(&mut rng, 500);
+        let bvh = BvhNode::new(list.objects.clone());
+        assert_eq!(bvh.bounding_box(), list.bounding_box());
+
+        let mut hits = 0;
+        for _ in 0..2000 {
+            let origin = random_vec(&mut rng, -15.0, 15.0);
+            let direction = random_vec(&mut rng, -1.0, 1.0);
+            let r = Ray::new(origin, direction);
+
+            let mut list_rec = HitRecord::default();
+            let mut bvh_rec = HitRecord::default();
+            let ray_t = Interval::new(0.001, f32::INFINITY);
+            let list_hit = list.hit(&r, ray_t, &mut list_rec);
+            let bvh_hit = bvh.hit(&r, ray_t, &mut bvh_rec);
+
+            assert_eq!(list_hit, bvh_hit);
+            if list_hit {
+                hits += 1;
+                assert_eq!(list_rec.t, bvh_rec.t);
+                assert_eq!(list_rec.point, bvh_rec.point);
+                assert_eq!(list_rec.normal, bvh_rec.normal);
+            }
+        }
+        assert!(hits > 0);
+    }
+
+    #[test]
+    fn test_bvh_coincident_centroids() {
+        // Concentric spheres can't be separated by SAH buckets and need the median split.
+        let mat = Arc::new(Lambertian::new(Color::ones()));
+        let mut list = HittableList::new();
+        for i in 1..=5 {
+            list.add(Arc::new(Sphere::new(Point3::zero(), i as f32, mat.clone())));
+        }
+        let bvh = BvhNode::from_list(list);
+
+        let r = Ray::new(Vec3::new(0.0, 0.0, 10.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut rec = HitRecord::default();
+        assert!(bvh.hit(&r, Interval::new(0.001, f32::INFINITY), &mut rec));
+        assert_eq!(rec.t, 5.0);
+    }
+
+    #[test]
+    fn test_empty_bvh() {
+        let bvh = BvhNode::from_list(HittableList::new());
+        let r = Ray::new(Vec3::zero(), Vec3::new(0.0, 0.0, -1.0));
+        let mut rec = HitRecord::default();
+        assert!(!bvh.hit(&r, Interval::new(0.001, f32::INFINITY), &mut rec));
+    }
+}
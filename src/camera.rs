@@ -1,25 +1,130 @@
-use std::io;
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
 
 use log::info;
 
 use crate::{
-    color::write_color,
-    ray::{HitRecord, Hittable, Interval, Ray},
-    rtweekend::{random_double, INFINITY},
-    vec3::{Point3, Vec3},
+    film::Film,
+    integrator::{Integrator, PathTracer, SceneView},
+    ray::{Hittable, HittableList, Ray},
+    rtweekend::{degrees_to_radians, mix_seed, pixel_seed, seed_rng},
+    sampler::{concentric_disk, Sampler, SamplerKind},
+    tonemap::{luminance, DisplayTransform},
+    vec3::{Color, Point3, Vec3},
 };
 
+// Radiance returned for rays that escape the scene.
+#[derive(Clone)]
+pub enum Background {
+    Solid(Color),
+    // Vertical blend from `bottom` to `top` along the ray direction.
+    Gradient { bottom: Color, top: Color },
+}
+
+impl Background {
+    pub fn sky() -> Self {
+        Background::Gradient {
+            bottom: Color::new(1.0, 1.0, 1.0),
+            top: Color::new(0.5, 0.7, 1.0),
+        }
+    }
+
+    pub fn value(&self, r: &Ray) -> Color {
+        match self {
+            Background::Solid(color) => *color,
+            Background::Gradient { bottom, top } => {
+                let unit_dir = r.direction.unit();
+                let a = 0.5 * (unit_dir.y + 1.0);
+                *bottom * (1.0 - a) + *top * a
+            }
+        }
+    }
+}
+
+// Running mean and variance of the luminance of a pixel's samples (Welford's algorithm).
+#[derive(Clone, Copy, Debug, Default)]
+struct PixelStats {
+    count: u32,
+    mean: f32,
+    m2: f32,
+}
+
+impl PixelStats {
+    fn add(&mut self, radiance: Color) {
+        let x = luminance(radiance);
+        self.count += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.count as f32;
+        self.m2 += delta * (x - self.mean);
+    }
+
+    fn variance(&self) -> f32 {
+        if self.count < 2 {
+            0.0
+        } else {
+            self.m2 / (self.count - 1) as f32
+        }
+    }
+
+    // Standard error of the mean, relative to the mean. Means below 0.01 count as 0.01, so
+    // that nearly black pixels don't sample forever to pin down noise nobody can see.
+    fn relative_error(&self) -> f32 {
+        (self.variance() / self.count as f32).sqrt() / self.mean.abs().max(0.01)
+    }
+}
+
+// A rectangular block of pixels, rendered as one unit of work: [x0, x1) x [y0, y1).
+#[derive(Clone, Copy, Debug)]
+struct Tile {
+    x0: i32,
+    y0: i32,
+    x1: i32,
+    y1: i32,
+}
+
 pub struct Camera {
     pub aspect_ratio: f32,
     pub img_width: i32,
     pub samples_per_pixel: i32,
+    pub sampler: SamplerKind, // Generator of the sample values behind each pixel sample
+    pub adaptive_threshold: f32, // Relative error at which a pixel stops sampling; 0 disables
+    pub min_samples: i32,     // Samples every pixel takes before it may stop early
     pub max_depth: i32,
-    pixel_samples_scale: f32,
+    pub roulette_depth: i32, // Bounces before Russian roulette may end a path early
+    pub background: Background,
+    pub integrator: Arc<dyn Integrator>, // Light transport algorithm estimating each sample
+    pub display: DisplayTransform,       // Exposure, tone mapping and encoding for 8-bit output
+
+    pub vfov: f32,          // Vertical view angle (field of view) in degrees
+    pub lookfrom: Point3,   // Point camera is looking from
+    pub lookat: Point3,     // Point camera is looking at
+    pub vup: Vec3,          // Camera-relative "up" direction
+    pub defocus_angle: f32, // Variation angle of rays through each pixel, in degrees
+    pub focus_dist: f32,    // Distance from camera lookfrom point to plane of perfect focus
+
+    pub shutter_open: f32, // Time the shutter opens; rays are spread over the open interval
+    pub shutter_close: f32, // Time the shutter closes; equal to `shutter_open` for no blur
+
+    pub threads: usize, // Worker threads; 0 uses every available core
+    pub tile_size: i32, // Edge length of the square tiles handed to workers
+    pub seed: u64,      // Base seed; each pixel sample derives its own stream from it
+
     img_height: i32,
     center: Point3,
     pixel00_loc: Point3,
     pixel_delta_u: Vec3,
     pixel_delta_v: Vec3,
+    // Camera frame basis vectors
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    defocus_disk_u: Vec3,
+    defocus_disk_v: Vec3,
 }
 
 impl Default for Camera {
@@ -33,95 +138,519 @@ impl Default for Camera {
             pixel_delta_u: Vec3::default(),
             pixel_delta_v: Vec3::default(),
             samples_per_pixel: 10,
-            pixel_samples_scale: 0.0,
+            sampler: SamplerKind::default(),
+            adaptive_threshold: 0.0,
+            min_samples: 16,
             max_depth: 10,
+            roulette_depth: 3,
+            background: Background::sky(),
+            integrator: Arc::new(PathTracer::default()),
+            display: DisplayTransform::default(),
+            vfov: 90.0,
+            lookfrom: Point3::new(0.0, 0.0, 0.0),
+            lookat: Point3::new(0.0, 0.0, -1.0),
+            vup: Vec3::new(0.0, 1.0, 0.0),
+            defocus_angle: 0.0,
+            focus_dist: 10.0,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+            threads: 0,
+            tile_size: 16,
+            seed: 0,
+            u: Vec3::default(),
+            v: Vec3::default(),
+            w: Vec3::default(),
+            defocus_disk_u: Vec3::default(),
+            defocus_disk_v: Vec3::default(),
         }
     }
 }
 
 impl Camera {
-    pub fn render(&mut self, world: &dyn Hittable) {
+    // Renders every tile in parallel and returns the accumulated image. `lights` are the
+    // emitters in `world` that integrators may sample directly.
+    pub fn render_film(&mut self, world: &dyn Hittable, lights: &HittableList) -> Film {
         self.initialize();
 
-        println!("P3\n{} {}\n255", self.img_width, self.img_height);
-        let stdout = io::stdout();
-        let mut handle = stdout.lock();
-
-        for j in 0..self.img_height {
-            info!("Scanlines remaining: {}", self.img_width - j);
-            for i in 0..self.img_width {
-                let mut pixel_color = Vec3::zero();
-                for _sample in 0..self.samples_per_pixel {
-                    let r = self.get_ray(i, j);
-                    pixel_color += Self::ray_color(&r, self.max_depth, world);
+        let tiles = self.tiles();
+        let film = Mutex::new(Film::new(self.img_width as usize, self.img_height as usize));
+        let next_tile = AtomicUsize::new(0);
+        let tiles_done = AtomicUsize::new(0);
+        let workers = self.worker_count().min(tiles.len()).max(1);
+
+        let camera = &*self;
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| loop {
+                    let index = next_tile.fetch_add(1, Ordering::Relaxed);
+                    let Some(tile) = tiles.get(index) else {
+                        break;
+                    };
+
+                    let block = camera.render_tile(tile, world, lights);
+                    film.lock()
+                        .unwrap()
+                        .add_tile(tile.x0 as usize, tile.y0 as usize, &block);
+
+                    let done = tiles_done.fetch_add(1, Ordering::Relaxed) + 1;
+                    info!("Tiles remaining: {}", tiles.len() - done);
+                });
+            }
+        });
+
+        let mut film = film.into_inner().unwrap();
+        film.set_display(self.display);
+        film
+    }
+
+    fn render_tile(&self, tile: &Tile, world: &dyn Hittable, lights: &HittableList) -> Film {
+        let scene = SceneView {
+            world,
+            lights,
+            background: &self.background,
+            max_depth: self.max_depth,
+            roulette_depth: self.roulette_depth,
+        };
+        let mut sampler = self
+            .sampler
+            .create(self.samples_per_pixel as u32, self.seed);
+        let sampler = sampler.as_mut();
+        let mut block = Film::new((tile.x1 - tile.x0) as usize, (tile.y1 - tile.y0) as usize);
+        for j in tile.y0..tile.y1 {
+            for i in tile.x0..tile.x1 {
+                let mut stats = PixelStats::default();
+                for sample in 0..self.samples_per_pixel {
+                    // Seeding every pixel sample makes it reproducible on its own, whatever
+                    // the thread count, tile size or rendering order.
+                    seed_rng(mix_seed(pixel_seed(self.seed, (i, j)), sample as u64));
+                    sampler.start_pixel_sample((i, j), sample as u32);
+                    let r = self.get_ray(i, j, sampler);
+                    let radiance = self.integrator.radiance(&r, &scene, sampler);
+                    block.add_sample((i - tile.x0) as usize, (j - tile.y0) as usize, radiance);
+                    stats.add(radiance);
+                    if self.converged(&stats) {
+                        break;
+                    }
                 }
-                pixel_color = pixel_color * self.pixel_samples_scale;
+            }
+        }
+        block
+    }
+
+    // Whether adaptive sampling can stop any pixel early: it needs a threshold, and pixels
+    // must be allowed to stop before they have taken every sample anyway.
+    pub fn adaptive_sampling(&self) -> bool {
+        self.adaptive_threshold > 0.0 && self.min_samples.max(2) < self.samples_per_pixel
+    }
+
+    // Whether `vup` leaves the camera's roll undefined: it is zero or (nearly) parallel to the
+    // direction from `lookfrom` to `lookat`.
+    pub fn vup_is_degenerate(&self) -> bool {
+        let w = (self.lookfrom - self.lookat).unit();
+        self.vup.cross(&w).length() <= 1e-4 * self.vup.length()
+    }
 
-                write_color(&mut handle, pixel_color.rgba()).unwrap();
+    // Whether adaptive sampling may stop taking samples in a pixel. One that has only seen
+    // black can't tell a rarely hit light from none at all, so it keeps sampling.
+    fn converged(&self, stats: &PixelStats) -> bool {
+        self.adaptive_sampling()
+            && stats.count >= self.min_samples.max(2) as u32
+            && stats.mean > 0.0
+            && stats.relative_error() <= self.adaptive_threshold
+    }
+
+    fn tiles(&self) -> Vec<Tile> {
+        let size = self.tile_size.max(1);
+        let mut tiles = Vec::new();
+        for y0 in (0..self.img_height).step_by(size as usize) {
+            for x0 in (0..self.img_width).step_by(size as usize) {
+                tiles.push(Tile {
+                    x0,
+                    y0,
+                    x1: (x0 + size).min(self.img_width),
+                    y1: (y0 + size).min(self.img_height),
+                });
             }
         }
+        tiles
+    }
+
+    fn worker_count(&self) -> usize {
+        if self.threads > 0 {
+            self.threads
+        } else {
+            thread::available_parallelism().map_or(1, |n| n.get())
+        }
     }
 
     fn initialize(&mut self) {
         // Image size
-        self.img_width = 400;
         self.img_height = {
-            let img_height = (self.img_width as f32 / self.aspect_ratio) as i32;
+            let img_height = (self.img_width as f32 / self.aspect_ratio).round() as i32;
             img_height.max(1)
         };
 
-        // pixel sample
-        self.pixel_samples_scale = 1.0 / self.samples_per_pixel as f32;
+        self.center = self.lookfrom;
 
-        // Camera
-        let focal_length = 1f32;
-        let viewport_height = 2f32;
+        // Determine viewport dimensions.
+        let theta = degrees_to_radians(self.vfov);
+        let h = (theta / 2.0).tan();
+        let viewport_height = 2.0 * h * self.focus_dist;
         let viewport_width = viewport_height * (self.img_width as f32 / self.img_height as f32);
-        self.center = Point3::new(0.0, 0.0, 0.0);
 
-        // viewport space
-        let viewport_u = Vec3::new(viewport_width, 0.0, 0.0);
-        let viewport_v = Vec3::new(0.0, -viewport_height, 0.0);
+        // Calculate the u,v,w unit basis vectors for the camera coordinate frame.
+        self.w = (self.lookfrom - self.lookat).unit();
+        let vup = if self.vup_is_degenerate() {
+            // Looking along `vup` gives no horizon; take world up, or -z when looking along y.
+            if self.w.y.abs() > 0.9 {
+                Vec3::new(0.0, 0.0, -1.0)
+            } else {
+                Vec3::new(0.0, 1.0, 0.0)
+            }
+        } else {
+            self.vup
+        };
+        self.u = vup.cross(&self.w).unit();
+        self.v = self.w.cross(&self.u);
+
+        // Calculate the vectors across the horizontal and down the vertical viewport edges.
+        let viewport_u = self.u * viewport_width; // Vector across viewport horizontal edge
+        let viewport_v = -self.v * viewport_height; // Vector down viewport vertical edge
 
+        // Calculate the horizontal and vertical delta vectors from pixel to pixel.
         self.pixel_delta_u = viewport_u / self.img_width as f32;
         self.pixel_delta_v = viewport_v / self.img_height as f32;
 
+        // Calculate the location of the upper left pixel.
         let viewport_upper_left =
-            self.center - Vec3::new(0.0, 0.0, focal_length) - viewport_u / 2.0 - viewport_v / 2.0;
+            self.center - self.w * self.focus_dist - viewport_u / 2.0 - viewport_v / 2.0;
         self.pixel00_loc = viewport_upper_left + (self.pixel_delta_u + self.pixel_delta_v) * 0.5;
+
+        // Calculate the camera defocus disk basis vectors.
+        let defocus_radius = self.focus_dist * degrees_to_radians(self.defocus_angle / 2.0).tan();
+        self.defocus_disk_u = self.u * defocus_radius;
+        self.defocus_disk_v = self.v * defocus_radius;
     }
 
-    fn get_ray(&self, i: i32, j: i32) -> Ray {
-        let offset = Self::sample_square();
+    // Construct a camera ray originating from the defocus disk and directed at a randomly
+    // sampled point around the pixel location i, j.
+    fn get_ray(&self, i: i32, j: i32, sampler: &mut dyn Sampler) -> Ray {
+        let (offset_x, offset_y) = sampler.get_2d();
         let pixel_sample = self.pixel00_loc
-            + (self.pixel_delta_u * (i as f32 + offset.x))
-            + (self.pixel_delta_v * (j as f32 + offset.y));
-        let ray_origin = self.center;
+            + (self.pixel_delta_u * (i as f32 + offset_x - 0.5))
+            + (self.pixel_delta_v * (j as f32 + offset_y - 0.5));
+        let ray_origin = if self.defocus_angle <= 0.0 {
+            self.center
+        } else {
+            self.defocus_disk_sample(sampler.get_2d())
+        };
         let ray_direction = pixel_sample - ray_origin;
+        let shutter = sampler.get_1d();
+        let ray_time = self.shutter_open + (self.shutter_close - self.shutter_open) * shutter;
 
-        return Ray::new(ray_origin, ray_direction);
+        Ray::with_time(ray_origin, ray_direction, ray_time)
     }
 
-    fn sample_square() -> Vec3 {
-        Vec3::new(random_double() - 0.5, random_double() - 0.5, 0.0)
+    // Returns the point of the camera defocus disk that `u` maps to.
+    fn defocus_disk_sample(&self, u: (f32, f32)) -> Point3 {
+        let (x, y) = concentric_disk(u);
+        self.center + (self.defocus_disk_u * x) + (self.defocus_disk_v * y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{Background, Camera, PixelStats};
+    use crate::aabb::Aabb;
+    use crate::material::{Lambertian, Metal, VolumeMaterial};
+    use crate::ray::{HittableList, Ray, Sphere};
+    use crate::sampler::{IndependentSampler, SamplerKind};
+    use crate::texture::SolidColor;
+    use crate::tonemap::{DisplayTransform, ToneMap};
+    use crate::vec3::{Color, Point3, Vec3};
+    use crate::volume::{HeterogeneousVolume, VoxelGrid};
+
+    fn small_scene() -> HittableList {
+        let mut world = HittableList::new();
+        world.add(Arc::new(Sphere::new(
+            Point3::new(0.0, -100.5, -1.0),
+            100.0,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        )));
+        world.add(Arc::new(Sphere::new(
+            Point3::new(0.0, 0.0, -1.0),
+            0.5,
+            Arc::new(Metal::new(Color::new(0.8, 0.6, 0.2), 0.3)),
+        )));
+        world
     }
 
-    fn ray_color(r: &Ray, depth: i32, world: &dyn Hittable) -> Vec3 {
-        if depth <= 0 {
-            return Vec3::zero();
+    fn small_camera(threads: usize) -> Camera {
+        Camera {
+            aspect_ratio: 8.0,
+            img_width: 400,
+            samples_per_pixel: 2,
+            max_depth: 4,
+            threads,
+            tile_size: 7,
+            seed: 42,
+            ..Default::default()
         }
+    }
 
-        let mut rec = HitRecord::default();
-        let color_vec = if world.hit(r, Interval::new(0.001, INFINITY), &mut rec) {
-            let direction = rec.normal + Vec3::random_unit_vec();
-            Self::ray_color(&Ray::new(rec.point, direction), depth - 1, world) * 0.5
-        } else {
-            let unit_dir = r.direction.unit();
-            let a = 0.5 * (unit_dir.y + 1.0);
+    #[test]
+    fn test_render_is_independent_of_scheduling() {
+        let world = small_scene();
+        let single = small_camera(1).render_film(&world, &HittableList::new());
+        let parallel = small_camera(4).render_film(&world, &HittableList::new());
+        assert_eq!((single.width(), single.height()), (400, 50));
+        assert_eq!(single.sample_count(399, 49), 2);
+        assert_eq!(single, parallel);
+
+        let mut retiled = small_camera(3);
+        retiled.tile_size = 32;
+        assert_eq!(single, retiled.render_film(&world, &HittableList::new()));
 
-            Vec3::new(1.0, 1.0, 1.0) * (1.0 - a) + Vec3::new(0.5, 0.7, 1.0) * a
+        let mut reseeded = small_camera(4);
+        reseeded.seed = 43;
+        assert_ne!(single, reseeded.render_film(&world, &HittableList::new()));
+    }
+
+    #[test]
+    fn test_render_applies_display() {
+        let world = small_scene();
+        let plain = small_camera(0).render_film(&world, &HittableList::new());
+        let mut camera = Camera {
+            display: DisplayTransform {
+                exposure: 3.0,
+                tone_map: ToneMap::Aces,
+                ..Default::default()
+            },
+            ..small_camera(0)
         };
+        let graded = camera.render_film(&world, &HittableList::new());
+        assert_eq!(graded.pixel(0, 0), plain.pixel(0, 0));
+        assert_ne!(graded.to_rgb8(), plain.to_rgb8());
+    }
+
+    #[test]
+    fn test_samplers_agree() {
+        // Every sampler estimates the same image, and seeded ones reproduce it exactly.
+        let world = small_scene();
+        let mean = |kind: SamplerKind| {
+            let mut camera = Camera {
+                img_width: 200,
+                samples_per_pixel: 16,
+                sampler: kind,
+                ..small_camera(0)
+            };
+            let film = camera.render_film(&world, &HittableList::new());
+            let mut total = Color::zero();
+            for y in 0..film.height() {
+                for x in 0..film.width() {
+                    total += film.pixel(x, y);
+                }
+            }
+            (total / (film.width() * film.height()) as f32, film)
+        };
+        let (expected, _) = mean(SamplerKind::Independent);
+        for kind in [
+            SamplerKind::Stratified,
+            SamplerKind::Halton,
+            SamplerKind::Sobol,
+        ] {
+            let (value, film) = mean(kind);
+            assert!(
+                (value - expected).length() < 0.01 * expected.length(),
+                "{:?}: {:?} vs {:?}",
+                kind,
+                value,
+                expected
+            );
+            assert_eq!(film, mean(kind).1, "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn test_pixel_stats() {
+        let mut stats = PixelStats::default();
+        for x in [1.0, 2.0, 3.0, 6.0] {
+            stats.add(Color::ones() * x);
+        }
+        assert!((stats.mean - 3.0).abs() < 1e-5);
+        assert!((stats.variance() - 14.0 / 3.0).abs() < 1e-4);
+        assert!((stats.relative_error() - (14.0f32 / 12.0).sqrt() / 3.0).abs() < 1e-4);
+
+        // Black pixels have a floor on the mean, and flat ones no error at all.
+        let mut dark = PixelStats::default();
+        dark.add(Color::zero());
+        dark.add(Color::ones() * 0.002);
+        assert!((dark.relative_error() - 0.1).abs() < 1e-4);
+        let mut flat = PixelStats::default();
+        flat.add(Color::ones());
+        flat.add(Color::ones());
+        assert_eq!(flat.relative_error(), 0.0);
+    }
+
+    #[test]
+    fn test_adaptive_sampling() {
+        // Pixels that only see the smooth sky stop as soon as they may, while ones on the
+        // diffuse ground, lit from every part of the sky, keep sampling.
+        let world = small_scene();
+        let mut camera = Camera {
+            samples_per_pixel: 64,
+            adaptive_threshold: 0.005,
+            min_samples: 8,
+            ..small_camera(0)
+        };
+        let film = camera.render_film(&world, &HittableList::new());
+        let (width, height) = (film.width(), film.height());
+        assert_eq!(film.sample_count(0, 0), 8);
+        assert!(film.sample_count(width / 2, height - 1) > 8);
+        assert!((0..height).all(|y| (0..width).all(|x| film.sample_count(x, y) <= 64)));
+        assert!(film.mean_sample_count() < 64.0);
+
+        // Without a threshold every pixel takes every sample.
+        camera.adaptive_threshold = 0.0;
+        assert!(!camera.adaptive_sampling());
+        let film = camera.render_film(&world, &HittableList::new());
+        assert_eq!(film.mean_sample_count(), 64.0);
+
+        // Nor can pixels stop early when they must take every sample first.
+        let camera = Camera {
+            samples_per_pixel: 10,
+            adaptive_threshold: 0.005,
+            ..Default::default()
+        };
+        assert!(camera.min_samples >= camera.samples_per_pixel);
+        assert!(!camera.adaptive_sampling());
+    }
+
+    #[test]
+    fn test_adaptive_sampling_waits_for_light() {
+        // A pixel whose first samples all miss a small, bright light must not stop at black.
+        let camera = Camera {
+            samples_per_pixel: 256,
+            adaptive_threshold: 0.05,
+            min_samples: 8,
+            ..Default::default()
+        };
+        let mut stats = PixelStats::default();
+        while stats.count < 256 && !camera.converged(&stats) {
+            let hit = stats.count % 40 == 39;
+            stats.add(if hit {
+                Color::ones() * 40.0
+            } else {
+                Color::zero()
+            });
+        }
+        assert!(stats.count >= 40);
+        assert!(stats.mean > 0.0);
+
+        // A pixel that stays black takes every sample.
+        let mut black = PixelStats::default();
+        while black.count < 256 && !camera.converged(&black) {
+            black.add(Color::zero());
+        }
+        assert_eq!(black.count, 256);
+    }
+
+    #[test]
+    fn test_ray_times_span_shutter() {
+        let mut camera = Camera {
+            shutter_open: 0.25,
+            shutter_close: 0.75,
+            ..small_camera(1)
+        };
+        camera.initialize();
+        let times: Vec<f32> = (0..200)
+            .map(|_| camera.get_ray(3, 4, &mut IndependentSampler).time)
+            .collect();
+        assert!(times.iter().all(|t| (0.25..0.75).contains(t)));
+        assert!(times.iter().any(|t| *t < 0.4) && times.iter().any(|t| *t > 0.6));
+
+        // A closed shutter freezes every ray at the open time.
+        let mut still = Camera {
+            shutter_open: 0.5,
+            shutter_close: 0.5,
+            ..small_camera(1)
+        };
+        still.initialize();
+        assert_eq!(still.get_ray(0, 0, &mut IndependentSampler).time, 0.5);
+    }
+
+    #[test]
+    fn test_vup_along_view_direction() {
+        // Looking straight down the default up vector still gives a full camera frame.
+        let mut camera = Camera {
+            lookfrom: Point3::new(0.0, 5.0, 0.0),
+            lookat: Point3::new(0.0, 0.0, 0.0),
+            ..small_camera(1)
+        };
+        assert!(camera.vup_is_degenerate());
+        camera.initialize();
+        assert!((camera.u.length() - 1.0).abs() < 1e-6);
+        assert!((camera.v.length() - 1.0).abs() < 1e-6);
+        assert!(camera.u.dot(&camera.w).abs() < 1e-6);
+        let r = camera.get_ray(200, 25, &mut IndependentSampler);
+        assert!(r.direction.unit().dot(&Vec3::new(0.0, -1.0, 0.0)) > 0.99);
+    }
+
+    #[test]
+    fn test_volume_emission_and_scattering() {
+        // A dense, purely absorbing volume shows its emission; a purely scattering one lit
+        // only by a white background passes the background through. The scattering one is
+        // kept thin so that paths leave it well within `max_depth` bounces.
+        let bounds = Aabb::from_points(Point3::new(-1.0, -1.0, -3.0), Point3::new(1.0, 1.0, -1.0));
+        let volume = |albedo: Color, emission: Color, extinction: f32| {
+            let grid = Arc::new(VoxelGrid::new([1, 1, 1], 1, vec![1.0], bounds));
+            let mat = Arc::new(VolumeMaterial::new(
+                Arc::new(SolidColor::new(albedo)),
+                Arc::new(SolidColor::new(emission)),
+                0.0,
+            ));
+            HeterogeneousVolume::from_grid(grid, extinction, mat)
+        };
+        let mut camera = Camera {
+            img_width: 4,
+            samples_per_pixel: 8,
+            max_depth: 50,
+            vfov: 20.0,
+            background: Background::Solid(Color::zero()),
+            ..Default::default()
+        };
+        let film = camera.render_film(
+            &volume(Color::zero(), Color::new(0.5, 1.0, 2.0), 1e4),
+            &HittableList::new(),
+        );
+        assert!((film.pixel(1, 1) - Color::new(0.5, 1.0, 2.0)).length() < 1e-4);
+
+        let mut camera = Camera {
+            background: Background::Solid(Color::ones()),
+            ..camera
+        };
+        let film = camera.render_film(
+            &volume(Color::ones(), Color::zero(), 2.0),
+            &HittableList::new(),
+        );
+        assert!((film.pixel(1, 1) - Color::ones()).length() < 1e-4);
+    }
+
+    #[test]
+    fn test_solid_background() {
+        let bg = Background::Solid(Color::new(0.1, 0.2, 0.3));
+        let r = Ray::new(Vec3::zero(), Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(bg.value(&r), Color::new(0.1, 0.2, 0.3));
+    }
 
-        color_vec
+    #[test]
+    fn test_gradient_background() {
+        let bg = Background::sky();
+        let up = Ray::new(Vec3::zero(), Vec3::new(0.0, 1.0, 0.0));
+        let down = Ray::new(Vec3::zero(), Vec3::new(0.0, -1.0, 0.0));
+        assert_eq!(bg.value(&up), Color::new(0.5, 0.7, 1.0));
+        assert_eq!(bg.value(&down), Color::new(1.0, 1.0, 1.0));
     }
 }
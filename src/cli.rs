@@ -0,0 +1,367 @@
+// Command-line interface of the `ray1` binary.
+use std::path::{Path, PathBuf};
+
+use clap::Parser;
+
+use crate::{
+    camera::Camera,
+    integrator::{self, INTEGRATORS},
+    sampler::SamplerKind,
+    tonemap::{ToneMap, Transfer},
+};
+
+pub const OUTPUT_FORMATS: &[&str] = &["png", "jpg", "jpeg", "bmp", "ppm", "pfm", "hdr", "exr"];
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "ray1",
+    version,
+    about = "Renders a scene with a Monte Carlo path tracer"
+)]
+pub struct Args {
+    /// Scene file (.toml) or the name of a built-in scene
+    #[arg(default_value = "three-spheres")]
+    pub scene: String,
+
+    /// Image width in pixels
+    #[arg(short = 'W', long, value_parser = clap::value_parser!(i32).range(1..))]
+    pub width: Option<i32>,
+
+    /// Image height in pixels [default: derived from width and aspect ratio]
+    #[arg(short = 'H', long, value_parser = clap::value_parser!(i32).range(1..))]
+    pub height: Option<i32>,
+
+    /// Image width over height, as a number or a ratio such as 16:9
+    #[arg(short, long, value_parser = parse_aspect_ratio)]
+    pub aspect_ratio: Option<f32>,
+
+    /// Samples per pixel
+    #[arg(short, long, value_parser = clap::value_parser!(i32).range(1..))]
+    pub samples: Option<i32>,
+
+    /// Sample generator: independent, stratified, halton or sobol
+    #[arg(long, value_parser = clap::value_parser!(SamplerKind))]
+    pub sampler: Option<SamplerKind>,
+
+    /// Stop sampling a pixel once the standard error of its mean falls below this fraction
+    /// of the mean; 0 takes every sample
+    #[arg(long, value_parser = parse_threshold)]
+    pub adaptive_threshold: Option<f32>,
+
+    /// Samples every pixel takes before adaptive sampling may stop it
+    #[arg(long, value_parser = clap::value_parser!(i32).range(1..))]
+    pub min_samples: Option<i32>,
+
+    /// Maximum number of ray bounces
+    #[arg(short = 'd', long, value_parser = clap::value_parser!(i32).range(1..))]
+    pub max_depth: Option<i32>,
+
+    /// Bounces before Russian roulette may end a path early
+    #[arg(long, value_parser = clap::value_parser!(i32).range(0..))]
+    pub roulette_depth: Option<i32>,
+
+    /// Light transport algorithm
+    #[arg(short, long, value_parser = clap::builder::PossibleValuesParser::new(INTEGRATORS))]
+    pub integrator: Option<String>,
+
+    /// Worker threads, 0 for one per available core
+    #[arg(short = 'j', long)]
+    pub threads: Option<usize>,
+
+    /// Seed for the random number generator
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Output image path; the format follows the extension
+    #[arg(short, long, default_value = "image.png")]
+    pub output: PathBuf,
+
+    /// Also write the samples taken in each pixel as a heatmap image
+    #[arg(long)]
+    pub sample_map: Option<PathBuf>,
+
+    /// Output format, overriding the output path's extension
+    #[arg(short, long, value_parser = clap::builder::PossibleValuesParser::new(OUTPUT_FORMATS))]
+    pub format: Option<String>,
+
+    /// Exposure adjustment in stops for 8-bit output
+    #[arg(long, allow_negative_numbers = true)]
+    pub exposure: Option<f32>,
+
+    /// Tone mapping curve: clamp, reinhard, reinhard-extended[:white], hable or aces
+    #[arg(long, value_parser = clap::value_parser!(ToneMap))]
+    pub tone_map: Option<ToneMap>,
+
+    /// Transfer function for 8-bit output: linear, gamma2 or srgb
+    #[arg(long, value_parser = clap::value_parser!(Transfer))]
+    pub transfer: Option<Transfer>,
+
+    /// Minimum level of log messages written to stderr
+    #[arg(long, default_value = "info",
+          value_parser = ["off", "error", "warn", "info", "debug", "trace"])]
+    pub log_level: String,
+}
+
+fn parse_aspect_ratio(s: &str) -> Result<f32, String> {
+    let ratio = match s.split_once(':') {
+        Some((w, h)) => {
+            let w: f32 = w
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid ratio `{}`", s))?;
+            let h: f32 = h
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid ratio `{}`", s))?;
+            w / h
+        }
+        None => s.parse().map_err(|_| format!("invalid number `{}`", s))?,
+    };
+    if ratio.is_finite() && ratio > 0.0 {
+        Ok(ratio)
+    } else {
+        Err(format!("aspect ratio must be positive, got `{}`", s))
+    }
+}
+
+fn parse_threshold(s: &str) -> Result<f32, String> {
+    let threshold: f32 = s.parse().map_err(|_| format!("invalid number `{}`", s))?;
+    if threshold.is_finite() && threshold >= 0.0 {
+        Ok(threshold)
+    } else {
+        Err(format!("threshold must not be negative, got `{}`", s))
+    }
+}
+
+impl Args {
+    // The output path with `--format` applied as its extension.
+    pub fn output_path(&self) -> Result<PathBuf, String> {
+        let Some(format) = &self.format else {
+            return Ok(self.output.clone());
+        };
+        match self.output.extension().and_then(|e| e.to_str()) {
+            None => Ok(self.output.with_extension(format)),
+            Some(ext) if ext.eq_ignore_ascii_case(format) => Ok(self.output.clone()),
+            Some(ext) => Err(format!(
+                "output `{}` has extension `{}`, which conflicts with --format {}",
+                self.output.display(),
+                ext,
+                format
+            )),
+        }
+    }
+
+    // Whether `scene` names a file rather than a built-in scene.
+    pub fn scene_is_file(&self) -> bool {
+        let path = Path::new(&self.scene);
+        path.exists() || path.extension().is_some()
+    }
+
+    // Overrides the scene's camera settings with the ones given on the command line.
+    pub fn apply(&self, camera: &mut Camera) -> Result<(), String> {
+        match (self.width, self.height, self.aspect_ratio) {
+            (Some(_), Some(_), Some(_)) => {
+                return Err("--width, --height and --aspect-ratio can't all be given".to_string())
+            }
+            (Some(width), Some(height), None) => {
+                camera.img_width = width;
+                camera.aspect_ratio = width as f32 / height as f32;
+            }
+            (width, height, aspect_ratio) => {
+                if let Some(aspect_ratio) = aspect_ratio {
+                    camera.aspect_ratio = aspect_ratio;
+                }
+                if let Some(width) = width {
+                    camera.img_width = width;
+                }
+                if let Some(height) = height {
+                    camera.img_width =
+                        ((height as f32 * camera.aspect_ratio).round() as i32).max(1);
+                }
+            }
+        }
+
+        if let Some(samples) = self.samples {
+            camera.samples_per_pixel = samples;
+        }
+        if let Some(sampler) = self.sampler {
+            camera.sampler = sampler;
+        }
+        if let Some(threshold) = self.adaptive_threshold {
+            camera.adaptive_threshold = threshold;
+        }
+        if let Some(min_samples) = self.min_samples {
+            camera.min_samples = min_samples;
+        }
+        if let Some(max_depth) = self.max_depth {
+            camera.max_depth = max_depth;
+        }
+        if let Some(roulette_depth) = self.roulette_depth {
+            camera.roulette_depth = roulette_depth;
+        }
+        if let Some(name) = &self.integrator {
+            camera.integrator = integrator::by_name(name)?;
+        }
+        if let Some(threads) = self.threads {
+            camera.threads = threads;
+        }
+        if let Some(seed) = self.seed {
+            camera.seed = seed;
+        }
+        if let Some(exposure) = self.exposure {
+            camera.display.exposure = exposure;
+        }
+        if let Some(tone_map) = self.tone_map {
+            camera.display.tone_map = tone_map;
+        }
+        if let Some(transfer) = self.transfer {
+            camera.display.transfer = transfer;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use clap::{CommandFactory, Parser};
+
+    use super::Args;
+    use crate::camera::Camera;
+    use crate::sampler::SamplerKind;
+    use crate::scenes;
+    use crate::tonemap::ToneMap;
+
+    fn parse(args: &[&str]) -> Result<Args, clap::Error> {
+        Args::try_parse_from(std::iter::once("ray1").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn test_command_is_valid() {
+        Args::command().debug_assert();
+    }
+
+    #[test]
+    fn test_defaults() {
+        let args = parse(&[]).unwrap();
+        assert_eq!(args.scene, "three-spheres");
+        assert!(!args.scene_is_file());
+        assert_eq!(args.output_path().unwrap(), PathBuf::from("image.png"));
+        assert_eq!(args.log_level, "info");
+    }
+
+    #[test]
+    fn test_overrides_camera() {
+        let args = parse(&[
+            "scenes/three_spheres.toml",
+            "-W",
+            "320",
+            "-a",
+            "4:3",
+            "-s",
+            "8",
+            "--sampler",
+            "sobol",
+            "--adaptive-threshold",
+            "0.05",
+            "--min-samples",
+            "4",
+            "-d",
+            "5",
+            "--roulette-depth",
+            "2",
+            "-i",
+            "normals",
+            "-j",
+            "2",
+            "--seed",
+            "9",
+            "--exposure",
+            "-1.5",
+            "--tone-map",
+            "aces",
+        ])
+        .unwrap();
+        assert!(args.scene_is_file());
+
+        let mut camera = Camera::default();
+        args.apply(&mut camera).unwrap();
+        assert_eq!(camera.img_width, 320);
+        assert_eq!(camera.aspect_ratio, 4.0 / 3.0);
+        assert_eq!(camera.samples_per_pixel, 8);
+        assert_eq!(camera.sampler, SamplerKind::Sobol);
+        assert_eq!(camera.adaptive_threshold, 0.05);
+        assert_eq!(camera.min_samples, 4);
+        assert_eq!(camera.max_depth, 5);
+        assert_eq!(camera.roulette_depth, 2);
+        assert_eq!(camera.threads, 2);
+        assert_eq!(camera.seed, 9);
+        assert_eq!(camera.display.exposure, -1.5);
+        assert_eq!(camera.display.tone_map, ToneMap::Aces);
+    }
+
+    #[test]
+    fn test_display_flags_change_output() {
+        let render = |flags: &[&str]| {
+            let mut scene = scenes::builtin("three-spheres", 0).unwrap();
+            let args = parse(&[&["-W", "16", "-s", "2", "-j", "1"], flags].concat()).unwrap();
+            args.apply(&mut scene.camera).unwrap();
+            scene
+                .camera
+                .render_film(scene.world.as_ref(), &scene.lights)
+                .to_rgb8()
+        };
+        let plain = render(&["--exposure", "0"]);
+        assert_ne!(plain, render(&["--exposure", "3", "--tone-map", "aces"]));
+        assert_ne!(plain, render(&["--transfer", "linear"]));
+        assert_eq!(plain, render(&[]));
+    }
+
+    #[test]
+    fn test_resolution() {
+        let mut camera = Camera::default();
+        parse(&["-W", "400", "-H", "225"])
+            .unwrap()
+            .apply(&mut camera)
+            .unwrap();
+        assert_eq!(camera.img_width, 400);
+        assert_eq!(camera.aspect_ratio, 400.0 / 225.0);
+
+        let mut camera = Camera::default();
+        parse(&["-H", "100", "-a", "2"])
+            .unwrap()
+            .apply(&mut camera)
+            .unwrap();
+        assert_eq!(camera.img_width, 200);
+
+        let all = parse(&["-W", "4", "-H", "4", "-a", "1"]).unwrap();
+        assert!(all.apply(&mut Camera::default()).is_err());
+    }
+
+    #[test]
+    fn test_validation() {
+        assert!(parse(&["--samples", "0"]).is_err());
+        assert!(parse(&["--width", "-3"]).is_err());
+        assert!(parse(&["--aspect-ratio", "0"]).is_err());
+        assert!(parse(&["--aspect-ratio", "16:x"]).is_err());
+        assert!(parse(&["--format", "gif"]).is_err());
+        assert!(parse(&["--tone-map", "magic"]).is_err());
+        assert!(parse(&["--sampler", "random"]).is_err());
+        assert!(parse(&["--adaptive-threshold", "-0.1"]).is_err());
+        assert!(parse(&["--min-samples", "0"]).is_err());
+        assert!(parse(&["--log-level", "loud"]).is_err());
+        assert!(parse(&["--integrator", "photon"]).is_err());
+        assert!(parse(&["--roulette-depth", "-1"]).is_err());
+    }
+
+    #[test]
+    fn test_output_format() {
+        let args = parse(&["-o", "out/render", "-f", "exr"]).unwrap();
+        assert_eq!(args.output_path().unwrap(), PathBuf::from("out/render.exr"));
+        let args = parse(&["-o", "render.EXR", "-f", "exr"]).unwrap();
+        assert_eq!(args.output_path().unwrap(), PathBuf::from("render.EXR"));
+        let args = parse(&["-o", "render.png", "-f", "exr"]).unwrap();
+        assert!(args.output_path().is_err());
+    }
+}
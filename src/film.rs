@@ -0,0 +1,232 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use crate::{
+    hdr,
+    tonemap::{DisplayTransform, ToneMap, Transfer},
+    vec3::Color,
+};
+
+// Framebuffer accumulating linear radiance samples per pixel, stored row-major from the top.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Film {
+    width: usize,
+    height: usize,
+    sums: Vec<Color>,
+    counts: Vec<u32>,
+    display: DisplayTransform,
+}
+
+impl Film {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            sums: vec![Color::zero(); width * height],
+            counts: vec![0; width * height],
+            display: DisplayTransform::default(),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // Sets the transform used for 8-bit output; float formats always keep linear radiance.
+    pub fn set_display(&mut self, display: DisplayTransform) {
+        self.display = display;
+    }
+
+    pub fn add_sample(&mut self, x: usize, y: usize, radiance: Color) {
+        let index = y * self.width + x;
+        self.sums[index] += radiance;
+        self.counts[index] += 1;
+    }
+
+    // Accumulates every sample of `tile` into this film, with the tile's origin at (x0, y0).
+    pub fn add_tile(&mut self, x0: usize, y0: usize, tile: &Film) {
+        for y in 0..tile.height {
+            for x in 0..tile.width {
+                let src = y * tile.width + x;
+                let dst = (y0 + y) * self.width + x0 + x;
+                self.sums[dst] += tile.sums[src];
+                self.counts[dst] += tile.counts[src];
+            }
+        }
+    }
+
+    #[cfg(test)]
+    pub fn sample_count(&self, x: usize, y: usize) -> u32 {
+        self.counts[y * self.width + x]
+    }
+
+    // Average number of samples per pixel.
+    pub fn mean_sample_count(&self) -> f32 {
+        let total: u64 = self.counts.iter().map(|&n| n as u64).sum();
+        total as f32 / self.counts.len().max(1) as f32
+    }
+
+    // A heatmap of the samples taken in each pixel, running from black for none through red
+    // and yellow to white for `max_samples` or more.
+    pub fn sample_map(&self, max_samples: u32) -> Film {
+        let mut map = Film::new(self.width, self.height);
+        map.display = DisplayTransform {
+            exposure: 0.0,
+            tone_map: ToneMap::Clamp,
+            transfer: Transfer::Linear,
+        };
+        for (index, &count) in self.counts.iter().enumerate() {
+            let t = 3.0 * count as f32 / max_samples.max(1) as f32;
+            let channel = |offset: f32| (t - offset).clamp(0.0, 1.0);
+            map.sums[index] = Color::new(channel(0.0), channel(1.0), channel(2.0));
+            map.counts[index] = 1;
+        }
+        map
+    }
+
+    // Mean linear radiance of the pixel, or black if it has no samples yet.
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        let index = y * self.width + x;
+        match self.counts[index] {
+            0 => Color::zero(),
+            n => self.sums[index] / n as f32,
+        }
+    }
+
+    pub fn pixels(&self) -> impl Iterator<Item = Color> + '_ {
+        (0..self.height).flat_map(move |y| (0..self.width).map(move |x| self.pixel(x, y)))
+    }
+
+    // Display-encoded 8-bit RGB bytes, row-major.
+    pub fn to_rgb8(&self) -> Vec<u8> {
+        self.pixels()
+            .flat_map(|c| {
+                let rgba = self.display.rgba(c);
+                [rgba.data[0], rgba.data[1], rgba.data[2]]
+            })
+            .collect()
+    }
+
+    // Saves the image in the format implied by the file extension: png, jpg/jpeg, bmp or
+    // binary (P6) ppm for 8-bit output, pfm, hdr or exr to keep the linear float radiance.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let ext = path
+            .extension()
+            .and_then(|s| s.to_str())
+            .map(|s| s.to_ascii_lowercase())
+            .unwrap_or_default();
+
+        match ext.as_str() {
+            "png" | "jpg" | "jpeg" | "bmp" | "ppm" => image::save_buffer(
+                path,
+                &self.to_rgb8(),
+                self.width as u32,
+                self.height as u32,
+                image::ColorType::RGB(8),
+            ),
+            "pfm" | "hdr" | "exr" => {
+                let mut out = BufWriter::new(File::create(path)?);
+                match ext.as_str() {
+                    "pfm" => hdr::write_pfm(self, &mut out)?,
+                    "hdr" => hdr::write_rgbe(self, &mut out)?,
+                    _ => hdr::write_exr(self, &mut out)?,
+                }
+                out.flush()
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unsupported output format `{}`", path.display()),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::Film;
+    use crate::tonemap::{DisplayTransform, ToneMap, Transfer};
+    use crate::vec3::Color;
+
+    #[test]
+    fn test_accumulate_samples() {
+        let mut film = Film::new(2, 1);
+        film.add_sample(1, 0, Color::new(1.0, 0.0, 0.0));
+        film.add_sample(1, 0, Color::new(0.0, 0.0, 1.0));
+        assert_eq!(film.pixel(0, 0), Color::zero());
+        assert_eq!(film.pixel(1, 0), Color::new(0.5, 0.0, 0.5));
+        assert_eq!(film.sample_count(1, 0), 2);
+    }
+
+    #[test]
+    fn test_add_tile() {
+        let mut tile = Film::new(1, 2);
+        tile.add_sample(0, 1, Color::ones());
+        let mut film = Film::new(3, 3);
+        film.add_tile(2, 1, &tile);
+        assert_eq!(film.pixel(2, 2), Color::ones());
+        assert_eq!(film.sample_count(2, 1), 0);
+    }
+
+    #[test]
+    fn test_sample_map() {
+        let mut film = Film::new(3, 1);
+        film.add_sample(1, 0, Color::ones());
+        for _ in 0..4 {
+            film.add_sample(2, 0, Color::ones());
+        }
+        assert_eq!(film.mean_sample_count(), 5.0 / 3.0);
+
+        let map = film.sample_map(3);
+        assert_eq!(map.pixel(0, 0), Color::zero());
+        assert_eq!(map.pixel(1, 0), Color::new(1.0, 0.0, 0.0));
+        assert_eq!(map.pixel(2, 0), Color::ones());
+        assert_eq!(map.to_rgb8()[3..6], [255, 0, 0]);
+    }
+
+    #[test]
+    fn test_display_transform() {
+        let mut film = Film::new(1, 1);
+        film.add_sample(0, 0, Color::new(4.0, 1.0, 0.0));
+        assert_eq!(film.to_rgb8(), vec![255, 255, 0]);
+
+        film.set_display(DisplayTransform {
+            exposure: -2.0,
+            tone_map: ToneMap::Clamp,
+            transfer: Transfer::Linear,
+        });
+        assert_eq!(film.to_rgb8(), vec![255, 63, 0]);
+        // Float radiance is untouched by the display transform.
+        assert_eq!(film.pixel(0, 0), Color::new(4.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_save_by_extension() {
+        let dir = std::env::temp_dir().join(format!("ray1-film-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut film = Film::new(4, 2);
+        film.add_sample(3, 1, Color::new(1.0, 0.25, 0.0));
+
+        for ext in ["png", "jpg", "bmp", "ppm", "pfm", "hdr", "exr"] {
+            let path = dir.join(format!("out.{}", ext));
+            film.save(&path).unwrap();
+            assert!(fs::metadata(&path).unwrap().len() > 0);
+        }
+        let ppm = fs::read(dir.join("out.ppm")).unwrap();
+        assert!(ppm.starts_with(b"P6"));
+        assert_eq!(&ppm[ppm.len() - 3..], &[255, 127, 0]);
+
+        assert!(film.save(dir.join("out.xyz")).is_err());
+        assert!(!dir.join("out.xyz").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
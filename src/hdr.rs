@@ -0,0 +1,172 @@
+// Writers for floating point image formats, fed from the film's linear radiance.
+use std::io::{self, Write};
+
+use crate::{film::Film, vec3::Color};
+
+// Portable float map: little-endian RGB floats, stored bottom row first.
+pub fn write_pfm(film: &Film, out: &mut dyn Write) -> io::Result<()> {
+    write!(out, "PF\n{} {}\n-1.0\n", film.width(), film.height())?;
+    for y in (0..film.height()).rev() {
+        for x in 0..film.width() {
+            let c = film.pixel(x, y);
+            for v in [c.x, c.y, c.z] {
+                out.write_all(&v.to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// Radiance RGBE (.hdr) image with run-length encoded scanlines.
+pub fn write_rgbe(film: &Film, out: &mut dyn Write) -> io::Result<()> {
+    let data: Vec<image::Rgb<f32>> = film
+        .pixels()
+        .map(|c| image::Rgb {
+            data: [c.x.max(0.0), c.y.max(0.0), c.z.max(0.0)],
+        })
+        .collect();
+    image::hdr::HDREncoder::new(out).encode(&data, film.width(), film.height())
+}
+
+// OpenEXR pixel type for 32-bit float channels.
+const EXR_FLOAT: i32 = 2;
+
+// Single-part, uncompressed, scanline OpenEXR image with FLOAT R, G and B channels.
+pub fn write_exr(film: &Film, out: &mut dyn Write) -> io::Result<()> {
+    let (width, height) = (film.width(), film.height());
+    let max_x = width as i32 - 1;
+    let max_y = height as i32 - 1;
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&[0x76, 0x2f, 0x31, 0x01]); // magic number
+    header.extend_from_slice(&2u32.to_le_bytes()); // version 2, single-part scanline
+
+    // Channels must be listed in alphabetical order.
+    let mut channels = Vec::new();
+    for name in ["B", "G", "R"] {
+        channels.extend_from_slice(name.as_bytes());
+        channels.push(0);
+        channels.extend_from_slice(&EXR_FLOAT.to_le_bytes());
+        channels.extend_from_slice(&[0, 0, 0, 0]); // pLinear + reserved
+        channels.extend_from_slice(&1i32.to_le_bytes()); // x sampling
+        channels.extend_from_slice(&1i32.to_le_bytes()); // y sampling
+    }
+    channels.push(0);
+    exr_attribute(&mut header, "channels", "chlist", &channels);
+
+    exr_attribute(&mut header, "compression", "compression", &[0]);
+    let window: Vec<u8> = [0, 0, max_x, max_y]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+    exr_attribute(&mut header, "dataWindow", "box2i", &window);
+    exr_attribute(&mut header, "displayWindow", "box2i", &window);
+    exr_attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    exr_attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    exr_attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    exr_attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    header.push(0);
+    out.write_all(&header)?;
+
+    // Offset table: one chunk per scanline, each prefixed by its y coordinate and size.
+    let line_size = width * 3 * 4;
+    let first_chunk = header.len() + height * 8;
+    for y in 0..height {
+        let offset = (first_chunk + y * (8 + line_size)) as u64;
+        out.write_all(&offset.to_le_bytes())?;
+    }
+
+    for y in 0..height {
+        out.write_all(&(y as i32).to_le_bytes())?;
+        out.write_all(&(line_size as i32).to_le_bytes())?;
+        let row: Vec<Color> = (0..width).map(|x| film.pixel(x, y)).collect();
+        for channel in [2, 1, 0] {
+            for c in &row {
+                out.write_all(&c[channel].to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn exr_attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::{write_exr, write_pfm, write_rgbe};
+    use crate::{film::Film, vec3::Color};
+
+    fn test_film() -> Film {
+        let mut film = Film::new(2, 2);
+        film.add_sample(0, 0, Color::new(8.0, 0.5, 0.25));
+        film.add_sample(1, 1, Color::new(0.0, 2.0, 16.0));
+        film
+    }
+
+    fn f32_at(bytes: &[u8], offset: usize) -> f32 {
+        f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_write_pfm() {
+        let mut out = Vec::new();
+        write_pfm(&test_film(), &mut out).unwrap();
+        let header = b"PF\n2 2\n-1.0\n";
+        assert!(out.starts_with(header));
+        assert_eq!(out.len(), header.len() + 2 * 2 * 3 * 4);
+        // Bottom row comes first, so the top-left pixel starts the second row.
+        let top_left = header.len() + 2 * 3 * 4;
+        assert_eq!(f32_at(&out, top_left), 8.0);
+        assert_eq!(f32_at(&out, top_left + 4), 0.5);
+    }
+
+    #[test]
+    fn test_write_rgbe_round_trip() {
+        let mut out = Vec::new();
+        write_rgbe(&test_film(), &mut out).unwrap();
+        let decoder = image::hdr::HDRDecoder::new(Cursor::new(out)).unwrap();
+        let pixels = decoder.read_image_hdr().unwrap();
+        assert_eq!(pixels[0].data, [8.0, 0.5, 0.25]);
+        assert_eq!(pixels[3].data, [0.0, 2.0, 16.0]);
+    }
+
+    #[test]
+    fn test_write_exr() {
+        let mut out = Vec::new();
+        write_exr(&test_film(), &mut out).unwrap();
+        assert_eq!(&out[..8], &[0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0]);
+
+        let table_start = out.len() - 2 * (8 + 2 * 3 * 4) - 2 * 8;
+        let second_line =
+            u64::from_le_bytes(out[table_start + 8..table_start + 16].try_into().unwrap());
+
+        // Second scanline: y = 1, then B, G and R planes for both pixels.
+        let line = second_line as usize;
+        assert_eq!(
+            i32::from_le_bytes(out[line..line + 4].try_into().unwrap()),
+            1
+        );
+        assert_eq!(f32_at(&out, line + 8 + 4), 16.0); // B of pixel (1, 1)
+        assert_eq!(f32_at(&out, line + 8 + 8 + 4), 2.0); // G of pixel (1, 1)
+        assert_eq!(out.len(), line + 8 + 2 * 3 * 4);
+    }
+}
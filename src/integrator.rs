@@ -0,0 +1,473 @@
+// Light transport algorithms: each estimates the radiance arriving along a camera ray.
+use std::sync::Arc;
+
+use crate::{
+    camera::Background,
+    material::Material,
+    ray::{HitRecord, Hittable, HittableList, Interval, Ray},
+    rtweekend::INFINITY,
+    sampler::Sampler,
+    vec3::{Color, Vec3},
+};
+
+// The parts of a scene an integrator sees.
+pub struct SceneView<'a> {
+    pub world: &'a dyn Hittable,
+    // Emitters to sample directly; each must also be part of `world`.
+    pub lights: &'a HittableList,
+    pub background: &'a Background,
+    // Longest path, in bounces, that integrators may trace.
+    pub max_depth: i32,
+    // Bounces after which paths may be ended at random, in proportion to how little they carry.
+    pub roulette_depth: i32,
+}
+
+pub trait Integrator: Send + Sync {
+    // Estimates the radiance arriving at the ray's origin from along its direction.
+    fn radiance(&self, r: &Ray, scene: &SceneView, sampler: &mut dyn Sampler) -> Color;
+}
+
+pub const INTEGRATORS: &[&str] = &["path", "mis", "mis-balance", "normals"];
+
+// The integrator called `name`, one of `INTEGRATORS`.
+pub fn by_name(name: &str) -> Result<Arc<dyn Integrator>, String> {
+    match name.to_ascii_lowercase().as_str() {
+        "path" => Ok(Arc::new(PathTracer::default())),
+        "mis" => Ok(Arc::new(PathTracer::new(LightWeighting::Power))),
+        "mis-balance" => Ok(Arc::new(PathTracer::new(LightWeighting::Balance))),
+        "normals" => Ok(Arc::new(NormalsIntegrator)),
+        _ => Err(format!(
+            "unknown integrator `{}`; expected one of {}",
+            name,
+            INTEGRATORS.join(", ")
+        )),
+    }
+}
+
+// How a path tracer splits a light's contribution between its two ways of finding it: shadow
+// rays toward points sampled on the light, and scattered rays that happen to hit it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LightWeighting {
+    // Next event estimation: only shadow rays count the sampled lights.
+    #[default]
+    LightSamples,
+    // Multiple importance sampling with the balance heuristic, weighting each sample by its
+    // density over the sum of both.
+    Balance,
+    // Multiple importance sampling with the power heuristic (exponent 2), which favors the
+    // likelier sample more strongly and is usually less noisy.
+    Power,
+}
+
+impl LightWeighting {
+    // Weight of a shadow ray sampled with density `light_pdf`, which the material would have
+    // scattered into with density `bsdf_pdf`.
+    pub fn light_weight(self, light_pdf: f32, bsdf_pdf: f32) -> f32 {
+        match self {
+            LightWeighting::LightSamples => 1.0,
+            _ => self.heuristic(light_pdf, bsdf_pdf),
+        }
+    }
+
+    // Weight of a scattered ray reaching a light, for the same densities the other way round.
+    pub fn bsdf_weight(self, bsdf_pdf: f32, light_pdf: f32) -> f32 {
+        match self {
+            LightWeighting::LightSamples => 0.0,
+            _ => self.heuristic(bsdf_pdf, light_pdf),
+        }
+    }
+
+    fn heuristic(self, pdf: f32, other: f32) -> f32 {
+        let (pdf, other) = match self {
+            LightWeighting::Power => (pdf * pdf, other * other),
+            _ => (pdf, other),
+        };
+        if pdf + other > 0.0 {
+            pdf / (pdf + other)
+        } else {
+            0.0
+        }
+    }
+}
+
+// Path tracing with direct light sampling: each bounce off a material that can weigh arbitrary
+// directions also casts a shadow ray toward a point sampled on the scene's lights. Scattered
+// rays that then reach one of those lights count its emission only as far as `weighting`
+// leaves them. Past the scene's `roulette_depth`, Russian roulette ends dim paths early.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PathTracer {
+    pub weighting: LightWeighting,
+}
+
+impl PathTracer {
+    pub fn new(weighting: LightWeighting) -> Self {
+        Self { weighting }
+    }
+
+    // Light arriving at `rec` straight from a point sampled on the lights, weighted by the
+    // material; None if the material or the scene leaves nothing to sample.
+    fn sample_lights(
+        &self,
+        r: &Ray,
+        rec: &HitRecord,
+        mat: &dyn Material,
+        scene: &SceneView,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Color> {
+        if scene.lights.is_empty() {
+            return None;
+        }
+        let direction = scene.lights.random(&rec.point, r.time, sampler.get_2d());
+        let f = mat.eval(r, rec, &direction)?;
+        let pdf = scene.lights.pdf_value(&rec.point, &direction, r.time);
+        if pdf <= 0.0 || f.near_zero() {
+            return Some(Color::zero());
+        }
+
+        let shadow = Ray::with_time(rec.point, direction, r.time);
+        let mut light_rec = HitRecord::default();
+        if !scene
+            .lights
+            .hit(&shadow, Interval::new(0.001, INFINITY), &mut light_rec)
+        {
+            return Some(Color::zero());
+        }
+        // Stop just short of the light so it doesn't occlude itself.
+        let unoccluded = Interval::new(0.001, light_rec.t * (1.0 - 1e-4));
+        let transmittance = scene.world.transmittance(&shadow, unoccluded);
+        if transmittance <= 0.0 {
+            return Some(Color::zero());
+        }
+
+        let emitted = match &light_rec.mat {
+            Some(light) => light.emitted(&light_rec),
+            None => Color::zero(),
+        };
+        let weight = self
+            .weighting
+            .light_weight(pdf, mat.scattering_pdf(r, rec, &direction));
+        Some(Vec3::elemul(f, emitted) * (weight * transmittance / pdf))
+    }
+
+    // Whether the hit `rec` along `r` lies on one of the sampled lights.
+    fn reaches_light(r: &Ray, rec: &HitRecord, scene: &SceneView) -> bool {
+        let mut light_rec = HitRecord::default();
+        scene
+            .lights
+            .hit(r, Interval::new(0.001, INFINITY), &mut light_rec)
+            && (light_rec.t - rec.t).abs() <= 1e-4 * rec.t.max(1.0)
+    }
+}
+
+impl Integrator for PathTracer {
+    fn radiance(&self, r: &Ray, scene: &SceneView, sampler: &mut dyn Sampler) -> Color {
+        let mut color = Color::zero();
+        // Fraction of the radiance found at the current vertex that reaches the camera.
+        let mut throughput = Color::ones();
+        let mut ray = *r;
+        // Density with which the previous material scattered into `ray`, or None for the camera
+        // ray and after specular bounces, which light sampling can't reproduce.
+        let mut scatter_pdf: Option<f32> = None;
+
+        for bounce in 0..scene.max_depth {
+            let mut rec = HitRecord::default();
+            if !scene
+                .world
+                .hit(&ray, Interval::new(0.001, INFINITY), &mut rec)
+            {
+                color += Vec3::elemul(throughput, scene.background.value(&ray));
+                break;
+            }
+
+            let Some(mat) = rec.mat.clone() else {
+                break;
+            };
+
+            let emission_weight = match scatter_pdf {
+                Some(bsdf_pdf) if Self::reaches_light(&ray, &rec, scene) => {
+                    let light_pdf = scene
+                        .lights
+                        .pdf_value(&ray.origin, &ray.direction, ray.time);
+                    self.weighting.bsdf_weight(bsdf_pdf, light_pdf)
+                }
+                _ => 1.0,
+            };
+            color += Vec3::elemul(throughput, mat.emitted(&rec) * emission_weight);
+
+            // Light the vertex before scattering, which may absorb the path: the light samples
+            // weigh every direction the material reflects into, not only the one it picks.
+            let direct = self.sample_lights(&ray, &rec, mat.as_ref(), scene, sampler);
+            if let Some(direct) = direct {
+                color += Vec3::elemul(throughput, direct);
+            }
+            let Some((attenuation, scattered)) = mat.scatter(&ray, &rec, sampler) else {
+                break;
+            };
+            scatter_pdf = direct.map(|_| mat.scattering_pdf(&ray, &rec, &scattered.direction));
+            throughput = Vec3::elemul(throughput, attenuation);
+
+            // Survive with a probability that shrinks with the throughput, scaling survivors up
+            // to keep the estimate unbiased. Paths carrying at least as much as they started
+            // with always continue.
+            if bounce + 1 >= scene.roulette_depth {
+                let survival = throughput.max_component().min(1.0);
+                if survival < 1.0 {
+                    let survival = survival.min(0.95);
+                    if sampler.get_1d() >= survival {
+                        break;
+                    }
+                    throughput = throughput / survival;
+                }
+            }
+            ray = scattered;
+        }
+        color
+    }
+}
+
+// Debug view of the shading normal at the first hit, mapped from [-1, 1] to [0, 1] per axis.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NormalsIntegrator;
+
+impl Integrator for NormalsIntegrator {
+    fn radiance(&self, r: &Ray, scene: &SceneView, _sampler: &mut dyn Sampler) -> Color {
+        let mut rec = HitRecord::default();
+        if !scene.world.hit(r, Interval::new(0.001, INFINITY), &mut rec) {
+            return scene.background.value(r);
+        }
+        (rec.normal + Color::ones()) * 0.5
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{by_name, Integrator, LightWeighting, SceneView, INTEGRATORS};
+    use crate::camera::Background;
+    use crate::material::{DiffuseLight, Lambertian, Material, Metal};
+    use crate::quad::Quad;
+    use crate::ray::{Hittable, HittableList, Ray, Sphere};
+    use crate::sampler::IndependentSampler;
+    use crate::vec3::{Color, Point3, Vec3};
+
+    #[test]
+    fn test_integrators() {
+        let mut world = HittableList::new();
+        world.add(Arc::new(Sphere::new(
+            Point3::new(0.0, 0.0, -3.0),
+            1.0,
+            Arc::new(DiffuseLight::new(Color::new(2.0, 3.0, 4.0))),
+        )));
+        let background = Background::Solid(Color::new(0.1, 0.1, 0.1));
+        let scene = SceneView {
+            world: &world,
+            lights: &HittableList::new(),
+            background: &background,
+            max_depth: 5,
+            roulette_depth: 5,
+        };
+        let mut sampler = IndependentSampler;
+        let at_sphere = Ray::new(Point3::zero(), Vec3::new(0.0, 0.0, -1.0));
+        let away = Ray::new(Point3::zero(), Vec3::new(0.0, 0.0, 1.0));
+
+        let path = by_name("path").unwrap();
+        assert_eq!(
+            path.radiance(&at_sphere, &scene, &mut sampler),
+            Color::new(2.0, 3.0, 4.0)
+        );
+        assert_eq!(
+            path.radiance(&away, &scene, &mut sampler),
+            Color::new(0.1, 0.1, 0.1)
+        );
+
+        let normals = by_name("Normals").unwrap();
+        assert_eq!(
+            normals.radiance(&at_sphere, &scene, &mut sampler),
+            Color::new(0.5, 0.5, 1.0)
+        );
+
+        assert_eq!(INTEGRATORS.len(), 4);
+        assert!(INTEGRATORS.iter().all(|name| by_name(name).is_ok()));
+        assert!(by_name("photon").is_err());
+    }
+
+    // A floor of `floor` under a small light facing down at (1, 2, 0), as the world and the
+    // list of lights.
+    fn lit_floor(floor: Arc<dyn Material>) -> (HittableList, HittableList) {
+        let mut world = HittableList::new();
+        world.add(Arc::new(Quad::new(
+            Point3::new(-10.0, 0.0, 10.0),
+            Vec3::new(20.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -20.0),
+            floor,
+        )));
+        let light: Arc<dyn Hittable> = Arc::new(Quad::new(
+            Point3::new(0.5, 2.0, -0.5),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            Arc::new(DiffuseLight::new(Color::new(8.0, 8.0, 8.0))),
+        ));
+        world.add(light.clone());
+        (world, HittableList::from_object(light))
+    }
+
+    // Red channel of the average of `n` radiance estimates along `r`.
+    fn mean_radiance(integrator: &dyn Integrator, r: &Ray, scene: &SceneView, n: usize) -> f32 {
+        let mut sampler = IndependentSampler;
+        let sum = (0..n).fold(Color::zero(), |sum, _| {
+            sum + integrator.radiance(r, scene, &mut sampler)
+        });
+        sum.x / n as f32
+    }
+
+    #[test]
+    fn test_light_sampling_matches_brute_force() {
+        // A diffuse floor seen from just below the light.
+        let (world, lights) = lit_floor(Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))));
+        let background = Background::Solid(Color::zero());
+        let no_lights = HittableList::new();
+        let view = |lights| SceneView {
+            world: &world,
+            lights,
+            background: &background,
+            max_depth: 2,
+            roulette_depth: 2,
+        };
+
+        let r = Ray::new(Point3::new(1.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let path = by_name("path").unwrap();
+        let sampled = mean_radiance(path.as_ref(), &r, &view(&lights), 2000);
+        let brute_force = mean_radiance(path.as_ref(), &r, &view(&no_lights), 200000);
+        assert!(
+            (sampled - brute_force).abs() < 0.05 * brute_force,
+            "{} vs {}",
+            sampled,
+            brute_force
+        );
+
+        // Looking straight at a sampled light still shows it.
+        let up = Ray::new(Point3::new(1.0, 1.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(
+            path.radiance(&up, &view(&lights), &mut IndependentSampler),
+            Color::new(8.0, 8.0, 8.0)
+        );
+    }
+
+    #[test]
+    fn test_mis_matches_brute_force() {
+        // A glossy floor reflecting the light toward a viewer off to the side, where light
+        // samples alone are noisy and scattered rays alone often miss the light. The second,
+        // rougher floor is seen at a grazing angle, where many fuzzed reflections end up below
+        // the surface and are absorbed.
+        for (fuzz, r) in [
+            (
+                0.3,
+                Ray::new(Point3::new(-1.0, 1.0, 0.0), Vec3::new(1.0, -1.0, 0.0)),
+            ),
+            (
+                0.8,
+                Ray::new(Point3::new(-5.0, 1.0, 0.0), Vec3::new(4.0, -1.0, 0.0)),
+            ),
+        ] {
+            let metal = Metal::new(Color::new(0.8, 0.8, 0.8), fuzz);
+            let (world, lights) = lit_floor(Arc::new(metal));
+            let background = Background::Solid(Color::zero());
+            let no_lights = HittableList::new();
+            let view = |lights| SceneView {
+                world: &world,
+                lights,
+                background: &background,
+                max_depth: 2,
+                roulette_depth: 2,
+            };
+
+            let path = by_name("path").unwrap();
+            let brute_force = mean_radiance(path.as_ref(), &r, &view(&no_lights), 200000);
+            assert!(brute_force > 0.1, "{}: {}", fuzz, brute_force);
+            // Light samples alone converge too, only more slowly.
+            for (name, n) in [("mis", 20000), ("mis-balance", 20000), ("path", 200000)] {
+                let integrator = by_name(name).unwrap();
+                let sampled = mean_radiance(integrator.as_ref(), &r, &view(&lights), n);
+                assert!(
+                    (sampled - brute_force).abs() < 0.05 * brute_force,
+                    "{} at fuzz {}: {} vs {}",
+                    name,
+                    fuzz,
+                    sampled,
+                    brute_force
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_heuristic_weights() {
+        for weighting in [LightWeighting::Balance, LightWeighting::Power] {
+            for (a, b) in [(1.0, 1.0), (0.2, 3.0), (5.0, 0.0)] {
+                let total = weighting.light_weight(a, b) + weighting.bsdf_weight(b, a);
+                assert!((total - 1.0).abs() < 1e-6);
+            }
+        }
+        assert_eq!(LightWeighting::Balance.light_weight(1.0, 3.0), 0.25);
+        assert_eq!(LightWeighting::Power.light_weight(1.0, 3.0), 0.1);
+        assert_eq!(LightWeighting::LightSamples.light_weight(1.0, 3.0), 1.0);
+        assert_eq!(LightWeighting::LightSamples.bsdf_weight(3.0, 1.0), 0.0);
+    }
+
+    #[test]
+    fn test_russian_roulette_is_unbiased() {
+        // A gray floor under a white sky reflects exactly half of it, with or without paths
+        // ended at random.
+        let mut world = HittableList::new();
+        world.add(Arc::new(Quad::new(
+            Point3::new(-100.0, 0.0, 100.0),
+            Vec3::new(200.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -200.0),
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        )));
+        let background = Background::Solid(Color::ones());
+        let lights = HittableList::new();
+        let view = |roulette_depth| SceneView {
+            world: &world,
+            lights: &lights,
+            background: &background,
+            max_depth: 50,
+            roulette_depth,
+        };
+
+        let r = Ray::new(Point3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let path = by_name("path").unwrap();
+        assert_eq!(mean_radiance(path.as_ref(), &r, &view(50), 100), 0.5);
+        let rouletted = mean_radiance(path.as_ref(), &r, &view(0), 20000);
+        assert!((rouletted - 0.5).abs() < 0.02, "{}", rouletted);
+    }
+
+    #[test]
+    fn test_deep_paths() {
+        // Inside a white sphere nothing is absorbed, so roulette never ends the path and it
+        // runs as deep as it can without exhausting the stack. Nothing shines, not even through
+        // the odd path that slips out between two hits.
+        let mut world = HittableList::new();
+        world.add(Arc::new(Sphere::new(
+            Point3::zero(),
+            1.0,
+            Arc::new(Lambertian::new(Color::ones())),
+        )));
+        let background = Background::Solid(Color::zero());
+        let lights = HittableList::new();
+        let scene = SceneView {
+            world: &world,
+            lights: &lights,
+            background: &background,
+            max_depth: 100_000,
+            roulette_depth: 0,
+        };
+        let r = Ray::new(Point3::zero(), Vec3::new(0.0, 0.0, -1.0));
+        let path = by_name("path").unwrap();
+        assert_eq!(
+            path.radiance(&r, &scene, &mut IndependentSampler),
+            Color::zero()
+        );
+    }
+}
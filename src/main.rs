@@ -1,35 +1,90 @@
+mod aabb;
+mod bvh;
 mod camera;
-mod color;
+mod cli;
+mod film;
+mod hdr;
+mod integrator;
+mod material;
+mod medium;
+mod mesh;
+mod noise;
+mod obj;
+mod quad;
 mod ray;
 mod rtweekend;
+mod sampler;
+mod scene;
+mod scenes;
+mod texture;
+mod tonemap;
+mod transform;
 mod vec3;
+mod volume;
 
-use std::sync::Arc;
+use std::process;
 
-use camera::Camera;
+use clap::Parser;
+use cli::Args;
 use flexi_logger::{Logger, WriteMode};
-use ray::{HittableList, Sphere};
-use vec3::Point3;
+use log::{error, info, warn};
+use scene::load_scene;
 
 fn main() {
+    let args = Args::parse();
+
     // Initialize the logger with buffered output and directing to stderr
-    Logger::try_with_str("info")
+    // Keep the handle alive so buffered records get flushed.
+    let logger = Logger::try_with_str(&args.log_level)
         .unwrap()
         .write_mode(WriteMode::BufferAndFlush)
         .log_to_stderr() // Ensure output goes to stderr
         .start()
         .unwrap();
+    let fail = |message: String| -> ! {
+        error!("{}", message);
+        logger.flush();
+        process::exit(1);
+    };
+
+    let output = args.output_path().unwrap_or_else(|e| fail(e));
 
-    // world
-    let mut world = HittableList::new();
-    world.add(Arc::new(Sphere::new(Point3::new(0.0, 0.0, -1.0), 0.5)));
-    world.add(Arc::new(Sphere::new(Point3::new(0.0, -100.5, -1.0), 100.0)));
+    // Render the scene file or built-in scene named on the command line.
+    let mut scene = if args.scene_is_file() {
+        load_scene(&args.scene).unwrap_or_else(|e| fail(e.to_string()))
+    } else {
+        scenes::builtin(&args.scene, args.seed.unwrap_or_default()).unwrap_or_else(|| {
+            fail(format!(
+                "`{}` is neither a scene file nor a built-in scene ({})",
+                args.scene,
+                scenes::BUILTIN_SCENES.join(", ")
+            ))
+        })
+    };
+    args.apply(&mut scene.camera).unwrap_or_else(|e| fail(e));
+    let camera = &scene.camera;
+    if camera.adaptive_threshold > 0.0 && !camera.adaptive_sampling() {
+        warn!(
+            "Adaptive sampling has no effect: pixels take at least {} samples, but only {} in all",
+            camera.min_samples.max(2),
+            camera.samples_per_pixel
+        );
+    }
 
-    let mut camera = Camera::default();
-    camera.aspect_ratio = 16.0 / 9.0;
-    camera.img_width = 400;
-    camera.samples_per_pixel = 100;
-    camera.max_depth = 50;
+    let film = scene
+        .camera
+        .render_film(scene.world.as_ref(), &scene.lights);
+    film.save(&output)
+        .unwrap_or_else(|e| fail(format!("failed to write `{}`: {}", output.display(), e)));
+    info!("Wrote {}", output.display());
 
-    camera.render(&world);
+    if scene.camera.adaptive_sampling() {
+        info!("Average samples per pixel: {:.1}", film.mean_sample_count());
+    }
+    if let Some(path) = &args.sample_map {
+        film.sample_map(scene.camera.samples_per_pixel as u32)
+            .save(path)
+            .unwrap_or_else(|e| fail(format!("failed to write `{}`: {}", path.display(), e)));
+        info!("Wrote sample map {}", path.display());
+    }
 }
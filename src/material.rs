@@ -0,0 +1,607 @@
+use std::sync::Arc;
+
+use crate::{
+    ray::{HitRecord, Ray},
+    rtweekend::PI,
+    sampler::{uniform_sphere, Sampler},
+    texture::{SolidColor, Texture},
+    vec3::{Color, Vec3},
+};
+
+pub trait Material: Send + Sync {
+    // Returns the attenuation and the scattered ray, or None if the ray is absorbed. Random
+    // choices draw on `sampler`.
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)>;
+
+    // Radiance emitted from the hit point; only light sources return a non-zero value.
+    fn emitted(&self, _rec: &HitRecord) -> Color {
+        Color::zero()
+    }
+
+    // The BSDF or phase function times the cosine term for scattering toward `direction`, for
+    // weighting light samples. None if the material only scatters into directions it picks
+    // itself, like mirrors and glass, so lights can't be sampled from it.
+    fn eval(&self, _r_in: &Ray, _rec: &HitRecord, _direction: &Vec3) -> Option<Color> {
+        None
+    }
+
+    // Density, per unit solid angle, of `scatter` choosing `direction`; 0 where `eval` is None.
+    fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _direction: &Vec3) -> f32 {
+        0.0
+    }
+}
+
+pub struct Lambertian {
+    tex: Arc<dyn Texture>,
+}
+
+impl Lambertian {
+    pub fn new(albedo: Color) -> Self {
+        Self::from_texture(Arc::new(SolidColor::new(albedo)))
+    }
+
+    pub fn from_texture(tex: Arc<dyn Texture>) -> Self {
+        Self { tex }
+    }
+}
+
+impl Material for Lambertian {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
+        let mut scatter_direction = rec.normal + uniform_sphere(sampler.get_2d());
+
+        // Catch degenerate scatter direction
+        if scatter_direction.near_zero() {
+            scatter_direction = rec.normal;
+        }
+
+        let attenuation = self.tex.value(rec.u, rec.v, &rec.point);
+        Some((
+            attenuation,
+            Ray::with_time(rec.point, scatter_direction, r_in.time),
+        ))
+    }
+
+    fn eval(&self, _r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> Option<Color> {
+        let cosine = rec.normal.dot(&direction.unit()).max(0.0);
+        Some(self.tex.value(rec.u, rec.v, &rec.point) * (cosine / PI))
+    }
+
+    fn scattering_pdf(&self, _r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> f32 {
+        rec.normal.dot(&direction.unit()).max(0.0) / PI
+    }
+}
+
+pub struct Metal {
+    albedo: Color,
+    fuzz: f32,
+}
+
+impl Metal {
+    pub fn new(albedo: Color, fuzz: f32) -> Self {
+        Self {
+            albedo,
+            fuzz: fuzz.min(1.0),
+        }
+    }
+
+    // Density of the fuzzed reflection toward `direction`. `scatter` aims at a uniform point
+    // on the sphere of radius `fuzz` around the tip of the unit mirror direction, so each
+    // point where `direction` crosses that sphere adds its area density times distance^2 / cos.
+    fn fuzz_pdf(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> f32 {
+        let center = Vec3::reflect(&r_in.direction, &rec.normal).unit();
+        let w = direction.unit();
+        let b = w.dot(&center);
+        let discriminant = b * b - (1.0 - self.fuzz * self.fuzz);
+        if discriminant <= 0.0 {
+            return 0.0;
+        }
+        let sqrtd = discriminant.sqrt();
+        [b - sqrtd, b + sqrtd]
+            .into_iter()
+            .filter(|&t| t > 0.0)
+            .map(|t| {
+                let cosine = (w * t - center).dot(&w).abs() / self.fuzz;
+                t * t / (4.0 * PI * self.fuzz * self.fuzz * cosine.max(1e-6))
+            })
+            .sum()
+    }
+
+    // A mirror too sharp to weigh directions other than the one it reflects into.
+    fn is_specular(&self) -> bool {
+        self.fuzz < 1e-3
+    }
+}
+
+impl Material for Metal {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
+        let reflected = Vec3::reflect(&r_in.direction, &rec.normal).unit()
+            + uniform_sphere(sampler.get_2d()) * self.fuzz;
+        let scattered = Ray::with_time(rec.point, reflected, r_in.time);
+
+        // Fuzzed rays that end up below the surface are absorbed.
+        if scattered.direction.dot(&rec.normal) > 0.0 {
+            Some((self.albedo, scattered))
+        } else {
+            None
+        }
+    }
+
+    // Scattering keeps the albedo for every direction above the surface, so the BSDF times
+    // the cosine is the albedo times the sampling density there.
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> Option<Color> {
+        if self.is_specular() {
+            return None;
+        }
+        if direction.dot(&rec.normal) <= 0.0 {
+            return Some(Color::zero());
+        }
+        Some(self.albedo * self.fuzz_pdf(r_in, rec, direction))
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> f32 {
+        if self.is_specular() {
+            return 0.0;
+        }
+        self.fuzz_pdf(r_in, rec, direction)
+    }
+}
+
+pub struct Dielectric {
+    // Refractive index in vacuum or air, or the ratio of the material's refractive index over
+    // the refractive index of the enclosing media
+    refraction_index: f32,
+}
+
+impl Dielectric {
+    pub fn new(refraction_index: f32) -> Self {
+        Self { refraction_index }
+    }
+
+    fn reflectance(cosine: f32, refraction_index: f32) -> f32 {
+        // Use Schlick's approximation for reflectance.
+        let r0 = (1.0 - refraction_index) / (1.0 + refraction_index);
+        let r0 = r0 * r0;
+        r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
+    }
+}
+
+impl Material for Dielectric {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
+        let ri = if rec.front_face {
+            1.0 / self.refraction_index
+        } else {
+            self.refraction_index
+        };
+
+        let unit_direction = r_in.direction.unit();
+        let cos_theta = (-unit_direction).dot(&rec.normal).min(1.0);
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+
+        let cannot_refract = ri * sin_theta > 1.0;
+        let direction = if cannot_refract || Self::reflectance(cos_theta, ri) > sampler.get_1d() {
+            Vec3::reflect(&unit_direction, &rec.normal)
+        } else {
+            Vec3::refract(&unit_direction, &rec.normal, ri)
+        };
+
+        Some((
+            Color::ones(),
+            Ray::with_time(rec.point, direction, r_in.time),
+        ))
+    }
+}
+
+pub struct DiffuseLight {
+    tex: Arc<dyn Texture>,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> Self {
+        Self::from_texture(Arc::new(SolidColor::new(emit)))
+    }
+
+    pub fn from_texture(tex: Arc<dyn Texture>) -> Self {
+        Self { tex }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(
+        &self,
+        _r_in: &Ray,
+        _rec: &HitRecord,
+        _sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
+        None
+    }
+
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        // Lights only shine from their outward-facing side.
+        if rec.front_face {
+            self.tex.value(rec.u, rec.v, &rec.point)
+        } else {
+            Color::zero()
+        }
+    }
+}
+
+// Phase function of a participating medium that scatters equally in all directions.
+pub struct Isotropic {
+    tex: Arc<dyn Texture>,
+}
+
+impl Isotropic {
+    pub fn new(albedo: Color) -> Self {
+        Self::from_texture(Arc::new(SolidColor::new(albedo)))
+    }
+
+    pub fn from_texture(tex: Arc<dyn Texture>) -> Self {
+        Self { tex }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
+        let attenuation = self.tex.value(rec.u, rec.v, &rec.point);
+        Some((
+            attenuation,
+            Ray::with_time(rec.point, uniform_sphere(sampler.get_2d()), r_in.time),
+        ))
+    }
+
+    fn eval(&self, _r_in: &Ray, rec: &HitRecord, _direction: &Vec3) -> Option<Color> {
+        Some(self.tex.value(rec.u, rec.v, &rec.point) / (4.0 * PI))
+    }
+
+    fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _direction: &Vec3) -> f32 {
+        1.0 / (4.0 * PI)
+    }
+}
+
+// Henyey-Greenstein phase function. The asymmetry `g` in (-1, 1) is the mean cosine of the
+// scattering angle: positive values scatter forward, as in haze, and negative ones backward.
+pub struct HenyeyGreenstein {
+    tex: Arc<dyn Texture>,
+    g: f32,
+}
+
+impl HenyeyGreenstein {
+    pub fn new(albedo: Color, g: f32) -> Self {
+        Self::from_texture(Arc::new(SolidColor::new(albedo)), g)
+    }
+
+    pub fn from_texture(tex: Arc<dyn Texture>, g: f32) -> Self {
+        Self {
+            tex,
+            g: g.clamp(-0.999, 0.999),
+        }
+    }
+
+    // Density of scattering by the angle whose cosine is `cos_theta`, per unit solid angle.
+    pub fn phase(&self, cos_theta: f32) -> f32 {
+        henyey_greenstein(cos_theta, self.g)
+    }
+}
+
+// The Henyey-Greenstein phase function of asymmetry `g`, per unit solid angle.
+fn henyey_greenstein(cos_theta: f32, g: f32) -> f32 {
+    let denom = 1.0 + g * g - 2.0 * g * cos_theta;
+    (1.0 - g * g) / (4.0 * PI * denom * denom.sqrt())
+}
+
+// Samples a direction around `forward` (a unit vector) with the Henyey-Greenstein distribution
+// of asymmetry `g`, driven by a point `(xi, v)` of the unit square.
+fn sample_henyey_greenstein(forward: &Vec3, g: f32, (xi, v): (f32, f32)) -> Vec3 {
+    let cos_theta = if g.abs() < 1e-3 {
+        1.0 - 2.0 * xi
+    } else {
+        let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * xi);
+        ((1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+    };
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * v;
+
+    let (tangent, bitangent) = forward.tangent_frame();
+    tangent * (sin_theta * phi.cos()) + bitangent * (sin_theta * phi.sin()) + *forward * cos_theta
+}
+
+impl Material for HenyeyGreenstein {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
+        let attenuation = self.tex.value(rec.u, rec.v, &rec.point);
+        let direction = sample_henyey_greenstein(&r_in.direction.unit(), self.g, sampler.get_2d());
+        Some((attenuation, Ray::with_time(rec.point, direction, r_in.time)))
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> Option<Color> {
+        let cos_theta = r_in.direction.unit().dot(&direction.unit());
+        Some(self.tex.value(rec.u, rec.v, &rec.point) * self.phase(cos_theta))
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, _rec: &HitRecord, direction: &Vec3) -> f32 {
+        self.phase(r_in.direction.unit().dot(&direction.unit()))
+    }
+}
+
+// A collision inside a heterogeneous medium. `albedo` is the fraction of the collisions that
+// scatter; the rest absorb and emit `emission`, so a medium that only glows has zero albedo.
+pub struct VolumeMaterial {
+    albedo: Arc<dyn Texture>,
+    emission: Arc<dyn Texture>,
+    g: f32,
+}
+
+impl VolumeMaterial {
+    pub fn new(albedo: Arc<dyn Texture>, emission: Arc<dyn Texture>, g: f32) -> Self {
+        Self {
+            albedo,
+            emission,
+            g: g.clamp(-0.999, 0.999),
+        }
+    }
+}
+
+impl Material for VolumeMaterial {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
+        let albedo = self.albedo.value(rec.u, rec.v, &rec.point);
+        if albedo.near_zero() {
+            return None;
+        }
+        let direction = sample_henyey_greenstein(&r_in.direction.unit(), self.g, sampler.get_2d());
+        Some((albedo, Ray::with_time(rec.point, direction, r_in.time)))
+    }
+
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        let albedo = self.albedo.value(rec.u, rec.v, &rec.point);
+        let absorbed = Color::ones() - albedo;
+        Vec3::elemul(self.emission.value(rec.u, rec.v, &rec.point), absorbed)
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> Option<Color> {
+        let cos_theta = r_in.direction.unit().dot(&direction.unit());
+        let albedo = self.albedo.value(rec.u, rec.v, &rec.point);
+        Some(albedo * henyey_greenstein(cos_theta, self.g))
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, _rec: &HitRecord, direction: &Vec3) -> f32 {
+        henyey_greenstein(r_in.direction.unit().dot(&direction.unit()), self.g)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{
+        Dielectric, DiffuseLight, HenyeyGreenstein, Isotropic, Lambertian, Material, Metal,
+    };
+    use crate::ray::{HitRecord, Ray};
+    use crate::sampler::IndependentSampler;
+    use crate::texture::CheckerTexture;
+    use crate::vec3::{Color, Vec3};
+
+    fn record_facing_up() -> HitRecord {
+        HitRecord {
+            normal: Vec3::new(0.0, 1.0, 0.0),
+            front_face: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_lambertian_scatters_above_surface() {
+        let mat = Lambertian::new(Color::new(0.1, 0.2, 0.3));
+        let r = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let (attenuation, scattered) = mat
+            .scatter(&r, &record_facing_up(), &mut IndependentSampler)
+            .unwrap();
+        assert_eq!(attenuation, Color::new(0.1, 0.2, 0.3));
+        assert!(scattered.direction.dot(&Vec3::new(0.0, 1.0, 0.0)) >= 0.0);
+    }
+
+    #[test]
+    fn test_metal_mirror_reflection() {
+        let mat = Metal::new(Color::ones(), 0.0);
+        let r = Ray::new(Vec3::new(-1.0, 1.0, 0.0), Vec3::new(1.0, -1.0, 0.0));
+        let (_, scattered) = mat
+            .scatter(&r, &record_facing_up(), &mut IndependentSampler)
+            .unwrap();
+        assert!((scattered.direction - Vec3::new(1.0, 1.0, 0.0).unit()).length() < 1e-6);
+    }
+
+    #[test]
+    fn test_dielectric_total_internal_reflection() {
+        // Leaving glass at a grazing angle must always reflect.
+        let mat = Dielectric::new(1.5);
+        let rec = HitRecord {
+            front_face: false,
+            ..record_facing_up()
+        };
+        let r = Ray::new(Vec3::zero(), Vec3::new(1.0, -0.1, 0.0));
+        let (attenuation, scattered) = mat.scatter(&r, &rec, &mut IndependentSampler).unwrap();
+        assert_eq!(attenuation, Color::ones());
+        assert!(scattered.direction.y > 0.0);
+    }
+
+    #[test]
+    fn test_diffuse_light_emits_without_scattering() {
+        let mat = DiffuseLight::new(Color::new(4.0, 4.0, 4.0));
+        let r = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let rec = record_facing_up();
+        assert!(mat.scatter(&r, &rec, &mut IndependentSampler).is_none());
+        assert_eq!(mat.emitted(&rec), Color::new(4.0, 4.0, 4.0));
+
+        let back = HitRecord {
+            front_face: false,
+            ..record_facing_up()
+        };
+        assert_eq!(mat.emitted(&back), Color::zero());
+    }
+
+    #[test]
+    fn test_non_emissive_materials_are_black() {
+        let mat = Lambertian::new(Color::ones());
+        assert_eq!(mat.emitted(&record_facing_up()), Color::zero());
+    }
+
+    #[test]
+    fn test_lambertian_samples_texture_at_hit() {
+        let checker = CheckerTexture::from_colors(1.0, Color::zero(), Color::ones());
+        let mat = Lambertian::from_texture(Arc::new(checker));
+        let r = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let even = HitRecord {
+            point: Vec3::new(0.5, 0.0, 0.5),
+            ..record_facing_up()
+        };
+        let odd = HitRecord {
+            point: Vec3::new(1.5, 0.0, 0.5),
+            ..record_facing_up()
+        };
+        assert_eq!(
+            mat.scatter(&r, &even, &mut IndependentSampler).unwrap().0,
+            Color::zero()
+        );
+        assert_eq!(
+            mat.scatter(&r, &odd, &mut IndependentSampler).unwrap().0,
+            Color::ones()
+        );
+    }
+
+    #[test]
+    fn test_eval_for_light_sampling() {
+        let r = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let rec = record_facing_up();
+        let up = Vec3::new(0.0, 2.0, 0.0);
+        let pi = std::f32::consts::PI;
+
+        let lambertian = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+        let f = lambertian.eval(&r, &rec, &up).unwrap();
+        assert!((f.x - 0.5 / pi).abs() < 1e-6);
+        let below = Vec3::new(1.0, -1.0, 0.0);
+        assert_eq!(lambertian.eval(&r, &rec, &below), Some(Color::zero()));
+
+        let isotropic = Isotropic::new(Color::ones());
+        assert!((isotropic.eval(&r, &rec, &below).unwrap().x - 1.0 / (4.0 * pi)).abs() < 1e-6);
+        let hg = HenyeyGreenstein::new(Color::ones(), 0.5);
+        assert_eq!(hg.eval(&r, &rec, &-up).unwrap().x, hg.phase(1.0));
+
+        // Mirrors, glass and lights leave nothing for light sampling to weigh.
+        assert!(Metal::new(Color::ones(), 0.0).eval(&r, &rec, &up).is_none());
+        assert!(Dielectric::new(1.5).eval(&r, &rec, &up).is_none());
+        assert!(DiffuseLight::new(Color::ones())
+            .eval(&r, &rec, &up)
+            .is_none());
+    }
+
+    #[test]
+    fn test_fuzzy_metal_density() {
+        // The density integrates to 1 over the sphere of directions, and eval is the albedo
+        // times it above the surface. The lobe is symmetric about the mirror direction and
+        // ends at the angle asin(fuzz), so integrate over the angle from it.
+        let r = Ray::new(Vec3::new(-1.0, 1.0, 0.0), Vec3::new(1.0, -1.0, 0.0));
+        let rec = record_facing_up();
+        let mirror = Vec3::new(1.0, 1.0, 0.0).unit();
+        let (tangent, _) = mirror.tangent_frame();
+        let pi = std::f32::consts::PI;
+        for fuzz in [0.1f32, 0.5, 1.0] {
+            let metal = Metal::new(Color::new(0.5, 0.5, 0.5), fuzz);
+            let n = 100000;
+            let step = fuzz.asin() / n as f32;
+            let total: f32 = (0..n)
+                .map(|i| {
+                    let theta = (i as f32 + 0.5) * step;
+                    let direction = mirror * theta.cos() + tangent * theta.sin();
+                    metal.scattering_pdf(&r, &rec, &direction) * 2.0 * pi * theta.sin() * step
+                })
+                .sum();
+            assert!((total - 1.0).abs() < 0.02, "fuzz {}: {}", fuzz, total);
+        }
+
+        let metal = Metal::new(Color::new(0.5, 0.5, 0.5), 0.3);
+        let (_, scattered) = metal.scatter(&r, &rec, &mut IndependentSampler).unwrap();
+        let f = metal.eval(&r, &rec, &scattered.direction).unwrap();
+        let pdf = metal.scattering_pdf(&r, &rec, &scattered.direction);
+        assert!(pdf > 0.0 && (f.x - 0.5 * pdf).abs() < 1e-4 * pdf);
+        let below = Vec3::new(1.0, -0.1, 0.0);
+        assert_eq!(metal.eval(&r, &rec, &below), Some(Color::zero()));
+    }
+
+    #[test]
+    fn test_phase_functions_mean_cosine() {
+        let forward = Vec3::new(0.0, 0.0, -2.0);
+        let r = Ray::new(Vec3::zero(), forward);
+        let rec = record_facing_up();
+        let mean_cosine = |mat: &dyn Material| {
+            let n = 20000;
+            let sum: f32 = (0..n)
+                .map(|_| {
+                    let (_, scattered) = mat.scatter(&r, &rec, &mut IndependentSampler).unwrap();
+                    assert!((scattered.direction.length() - 1.0).abs() < 1e-4);
+                    scattered.direction.dot(&forward.unit())
+                })
+                .sum();
+            sum / n as f32
+        };
+
+        // The mean cosine of Henyey-Greenstein scattering is its asymmetry parameter.
+        assert!(mean_cosine(&Isotropic::new(Color::ones())).abs() < 0.03);
+        for g in [-0.5, 0.0, 0.8] {
+            let hg = HenyeyGreenstein::new(Color::ones(), g);
+            assert!((mean_cosine(&hg) - g).abs() < 0.03, "g = {}", g);
+        }
+    }
+
+    #[test]
+    fn test_henyey_greenstein_phase_is_normalized() {
+        // Integrate over the sphere: 2 pi * integral of phase(cos) d(cos) over [-1, 1].
+        for g in [-0.7, 0.0, 0.3, 0.9] {
+            let hg = HenyeyGreenstein::new(Color::ones(), g);
+            let n = 100000;
+            let total: f32 = (0..n)
+                .map(|i| {
+                    let cos = -1.0 + 2.0 * (i as f32 + 0.5) / n as f32;
+                    hg.phase(cos) * 2.0 / n as f32
+                })
+                .sum();
+            assert!(
+                (2.0 * std::f32::consts::PI * total - 1.0).abs() < 1e-2,
+                "g = {}",
+                g
+            );
+        }
+    }
+}
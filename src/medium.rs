@@ -0,0 +1,157 @@
+// Participating media: volumes that scatter light inside a boundary instead of at a surface.
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    material::Material,
+    ray::{HitRecord, Hittable, Interval, Ray, UNIVERSE_INTERVAL},
+    rtweekend::random_double,
+    vec3::Vec3,
+};
+
+// A homogeneous medium filling a closed `boundary`. Rays travel an exponentially distributed
+// distance through it before scattering by the phase function, which is any material; use
+// `Isotropic` or `HenyeyGreenstein`.
+pub struct ConstantMedium {
+    boundary: Arc<dyn Hittable>,
+    neg_inv_density: f32,
+    phase_function: Arc<dyn Material>,
+}
+
+impl ConstantMedium {
+    pub fn new(
+        boundary: Arc<dyn Hittable>,
+        density: f32,
+        phase_function: Arc<dyn Material>,
+    ) -> Self {
+        Self {
+            boundary,
+            neg_inv_density: -1.0 / density,
+            phase_function,
+        }
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        // Find where the ray's line enters and leaves the boundary, even behind the origin, so
+        // rays starting inside the volume are handled too.
+        let mut rec1 = HitRecord::default();
+        let mut rec2 = HitRecord::default();
+        if !self.boundary.hit(r, UNIVERSE_INTERVAL, &mut rec1) {
+            return false;
+        }
+        let after_entry = Interval::new(rec1.t + 0.0001, f32::INFINITY);
+        if !self.boundary.hit(r, after_entry, &mut rec2) {
+            return false;
+        }
+
+        let t_enter = rec1.t.max(ray_t.min).max(0.0);
+        let t_exit = rec2.t.min(ray_t.max);
+        if t_enter >= t_exit {
+            return false;
+        }
+
+        let ray_length = r.direction.length();
+        let distance_inside_boundary = (t_exit - t_enter) * ray_length;
+        // Drawn from the random stream, like the heterogeneous volumes' tracking.
+        let hit_distance = self.neg_inv_density * random_double().ln();
+        if hit_distance > distance_inside_boundary {
+            return false;
+        }
+
+        rec.t = t_enter + hit_distance / ray_length;
+        rec.point = r.at(rec.t);
+        // A scattering event has no surface, so the normal and side are arbitrary.
+        rec.normal = Vec3::new(1.0, 0.0, 0.0);
+        rec.front_face = true;
+        rec.u = 0.0;
+        rec.v = 0.0;
+        rec.mat = Some(self.phase_function.clone());
+
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::ConstantMedium;
+    use crate::material::Isotropic;
+    use crate::quad::BoxShape;
+    use crate::ray::{HitRecord, Hittable, Interval, Ray, Sphere};
+    use crate::vec3::{Color, Point3, Vec3};
+
+    fn fog(density: f32) -> ConstantMedium {
+        let boundary = Arc::new(BoxShape::new(
+            Point3::new(-1.0, -1.0, -1.0),
+            Point3::new(1.0, 1.0, 1.0),
+            Arc::new(Isotropic::new(Color::ones())),
+        ));
+        ConstantMedium::new(boundary, density, Arc::new(Isotropic::new(Color::ones())))
+    }
+
+    fn scatter_count(medium: &ConstantMedium, r: &Ray, ray_t: Interval, n: usize) -> usize {
+        (0..n)
+            .filter(|_| {
+                let mut rec = HitRecord::default();
+                if !medium.hit(r, ray_t, &mut rec) {
+                    return false;
+                }
+                assert!(ray_t.contains(rec.t));
+                assert!(rec.point.x.abs() <= 1.0 + 1e-4);
+                true
+            })
+            .count()
+    }
+
+    #[test]
+    fn test_transmittance_follows_beer_lambert() {
+        // Crossing 2 units at density 0.5 scatters with probability 1 - e^-1.
+        let medium = fog(0.5);
+        let r = Ray::new(Point3::new(-5.0, 0.0, 0.0), Vec3::new(2.0, 0.0, 0.0));
+        let n = 20000;
+        let hits = scatter_count(&medium, &r, Interval::new(0.001, f32::INFINITY), n);
+        let expected = 1.0 - (-1.0f32).exp();
+        assert!((hits as f32 / n as f32 - expected).abs() < 0.02, "{}", hits);
+    }
+
+    #[test]
+    fn test_rays_inside_and_clipped() {
+        let medium = fog(0.5);
+        // Starting at the center, only the 1 unit to the boundary counts.
+        let r = Ray::new(Point3::zero(), Vec3::new(0.0, 1.0, 0.0));
+        let n = 20000;
+        let hits = scatter_count(&medium, &r, Interval::new(0.001, f32::INFINITY), n);
+        let expected = 1.0 - (-0.5f32).exp();
+        assert!((hits as f32 / n as f32 - expected).abs() < 0.02, "{}", hits);
+
+        // A ray that stops before reaching the volume never scatters in it.
+        let r = Ray::new(Point3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(
+            scatter_count(&fog(100.0), &r, Interval::new(0.001, 3.9), 100),
+            0
+        );
+        // Nor does one that misses the boundary.
+        let r = Ray::new(Point3::new(-5.0, 2.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let infinite = Interval::new(0.001, f32::INFINITY);
+        assert_eq!(scatter_count(&fog(100.0), &r, infinite, 100), 0);
+    }
+
+    #[test]
+    fn test_foggy_sphere_bbox() {
+        let sphere = Arc::new(Sphere::new(
+            Point3::new(1.0, 2.0, 3.0),
+            0.5,
+            Arc::new(Isotropic::new(Color::ones())),
+        ));
+        let medium = ConstantMedium::new(sphere, 1.0, Arc::new(Isotropic::new(Color::ones())));
+        let bbox = medium.bounding_box();
+        assert_eq!((bbox.x.min, bbox.y.max, bbox.z.min), (0.5, 2.5, 2.5));
+    }
+}
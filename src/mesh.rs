@@ -0,0 +1,317 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    material::Material,
+    quad::area_pdf,
+    ray::{HitRecord, Hittable, HittableList, Interval, Ray},
+    vec3::{Point3, Vec3},
+};
+
+// Indices of one triangle corner into the mesh's shared vertex buffers.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MeshVertex {
+    pub position: usize,
+    pub normal: Option<usize>,
+    pub uv: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MeshFace {
+    pub vertices: [MeshVertex; 3],
+    pub material: usize,
+}
+
+// Triangle soup sharing position, normal and texture coordinate buffers between faces.
+pub struct TriangleMesh {
+    pub positions: Vec<Point3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<(f32, f32)>,
+    pub faces: Vec<MeshFace>,
+    pub materials: Vec<Arc<dyn Material>>,
+}
+
+impl TriangleMesh {
+    pub fn new(
+        positions: Vec<Point3>,
+        normals: Vec<Vec3>,
+        uvs: Vec<(f32, f32)>,
+        faces: Vec<MeshFace>,
+        materials: Vec<Arc<dyn Material>>,
+    ) -> Self {
+        Self {
+            positions,
+            normals,
+            uvs,
+            faces,
+            materials,
+        }
+    }
+
+    // One hittable triangle per face, all referencing this mesh.
+    pub fn triangles(self: &Arc<Self>) -> Vec<Triangle> {
+        (0..self.faces.len())
+            .map(|face| Triangle::new(self.clone(), face))
+            .collect()
+    }
+
+    pub fn to_hittable_list(self: &Arc<Self>) -> HittableList {
+        let mut list = HittableList::new();
+        for triangle in self.triangles() {
+            list.add(Arc::new(triangle));
+        }
+        list
+    }
+}
+
+pub struct Triangle {
+    mesh: Arc<TriangleMesh>,
+    face: usize,
+    bbox: Aabb,
+}
+
+impl Triangle {
+    pub fn new(mesh: Arc<TriangleMesh>, face: usize) -> Self {
+        let corner = |i: usize| mesh.positions[mesh.faces[face].vertices[i].position];
+        let (a, b, c) = (corner(0), corner(1), corner(2));
+        let bbox = Aabb::from_points(
+            Point3::new(
+                a.x.min(b.x).min(c.x),
+                a.y.min(b.y).min(c.y),
+                a.z.min(b.z).min(c.z),
+            ),
+            Point3::new(
+                a.x.max(b.x).max(c.x),
+                a.y.max(b.y).max(c.y),
+                a.z.max(b.z).max(c.z),
+            ),
+        );
+        Self { mesh, face, bbox }
+    }
+
+    // A standalone triangle, backed by a mesh of its own; (u, v) are the barycentric
+    // coordinates of `b` and `c`.
+    pub fn from_points(a: Point3, b: Point3, c: Point3, mat: Arc<dyn Material>) -> Self {
+        let corner = |position| MeshVertex {
+            position,
+            ..Default::default()
+        };
+        let mesh = TriangleMesh::new(
+            vec![a, b, c],
+            Vec::new(),
+            Vec::new(),
+            vec![MeshFace {
+                vertices: [corner(0), corner(1), corner(2)],
+                material: 0,
+            }],
+            vec![mat],
+        );
+        Self::new(Arc::new(mesh), 0)
+    }
+
+    fn vertex(&self, corner: usize) -> MeshVertex {
+        self.mesh.faces[self.face].vertices[corner]
+    }
+
+    fn position(&self, corner: usize) -> Point3 {
+        self.mesh.positions[self.vertex(corner).position]
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        // Möller-Trumbore ray/triangle intersection.
+        let p0 = self.position(0);
+        let e1 = self.position(1) - p0;
+        let e2 = self.position(2) - p0;
+
+        let pvec = r.direction.cross(&e2);
+        let det = e1.dot(&pvec);
+        if det.abs() < 1e-12 {
+            return false;
+        }
+        let inv_det = 1.0 / det;
+
+        let tvec = r.origin - p0;
+        let b1 = tvec.dot(&pvec) * inv_det;
+        if !(0.0..=1.0).contains(&b1) {
+            return false;
+        }
+
+        let qvec = tvec.cross(&e1);
+        let b2 = r.direction.dot(&qvec) * inv_det;
+        if b2 < 0.0 || b1 + b2 > 1.0 {
+            return false;
+        }
+
+        let t = e2.dot(&qvec) * inv_det;
+        if !ray_t.surrounds(t) {
+            return false;
+        }
+
+        let b0 = 1.0 - b1 - b2;
+        let corners = [self.vertex(0), self.vertex(1), self.vertex(2)];
+
+        rec.t = t;
+        rec.point = r.at(t);
+        rec.set_face_normal(r, &e1.cross(&e2).unit());
+
+        // Smooth shading when every corner has a normal; keep it on the geometric side.
+        if let [Some(n0), Some(n1), Some(n2)] = corners.map(|c| c.normal) {
+            let shading = (self.mesh.normals[n0] * b0
+                + self.mesh.normals[n1] * b1
+                + self.mesh.normals[n2] * b2)
+                .unit();
+            rec.normal = if shading.dot(&rec.normal) < 0.0 {
+                -shading
+            } else {
+                shading
+            };
+        }
+
+        (rec.u, rec.v) = match corners.map(|c| c.uv) {
+            [Some(t0), Some(t1), Some(t2)] => {
+                let (uv0, uv1, uv2) = (self.mesh.uvs[t0], self.mesh.uvs[t1], self.mesh.uvs[t2]);
+                (
+                    uv0.0 * b0 + uv1.0 * b1 + uv2.0 * b2,
+                    uv0.1 * b0 + uv1.1 * b1 + uv2.1 * b2,
+                )
+            }
+            _ => (b1, b2),
+        };
+
+        let material = self.mesh.faces[self.face].material;
+        rec.mat = Some(self.mesh.materials[material].clone());
+
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, _time: f32) -> f32 {
+        let p0 = self.position(0);
+        let n = (self.position(1) - p0).cross(&(self.position(2) - p0));
+        let area = 0.5 * n.length();
+        if area <= 0.0 {
+            return 0.0;
+        }
+        area_pdf(self, origin, direction, area, &n.unit())
+    }
+
+    fn random(&self, origin: &Point3, _time: f32, (u, v): (f32, f32)) -> Vec3 {
+        // Uniform barycentric coordinates from the square-root warp.
+        let s = u.sqrt();
+        let b1 = s * v;
+        let b0 = 1.0 - s;
+        let p = self.position(0) * b0 + self.position(1) * b1 + self.position(2) * (s - b1);
+        p - *origin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{MeshFace, MeshVertex, Triangle, TriangleMesh};
+    use crate::material::Lambertian;
+    use crate::ray::{HitRecord, Hittable, Interval, Ray};
+    use crate::vec3::{Color, Point3, Vec3};
+
+    fn corner(position: usize, normal: Option<usize>, uv: Option<usize>) -> MeshVertex {
+        MeshVertex {
+            position,
+            normal,
+            uv,
+        }
+    }
+
+    fn unit_triangle(normals: Vec<Vec3>, with_attributes: bool) -> Arc<TriangleMesh> {
+        let attr = |i| with_attributes.then_some(i);
+        Arc::new(TriangleMesh::new(
+            vec![
+                Point3::new(0.0, 0.0, 0.0),
+                Point3::new(1.0, 0.0, 0.0),
+                Point3::new(0.0, 1.0, 0.0),
+            ],
+            normals,
+            vec![(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)],
+            vec![MeshFace {
+                vertices: [
+                    corner(0, attr(0), attr(0)),
+                    corner(1, attr(1), attr(1)),
+                    corner(2, attr(2), attr(2)),
+                ],
+                material: 0,
+            }],
+            vec![Arc::new(Lambertian::new(Color::ones()))],
+        ))
+    }
+
+    #[test]
+    fn test_triangle_hit() {
+        let mesh = unit_triangle(Vec::new(), false);
+        let tri = &mesh.triangles()[0];
+        let r = Ray::new(Vec3::new(0.25, 0.25, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut rec = HitRecord::default();
+        assert!(tri.hit(&r, Interval::new(0.001, f32::INFINITY), &mut rec));
+        assert_eq!(rec.t, 1.0);
+        assert_eq!(rec.normal, Vec3::new(0.0, 0.0, 1.0));
+        assert!(rec.front_face);
+        assert_eq!((rec.u, rec.v), (0.25, 0.25));
+    }
+
+    #[test]
+    fn test_triangle_miss() {
+        let mesh = unit_triangle(Vec::new(), false);
+        let tri = &mesh.triangles()[0];
+        let mut rec = HitRecord::default();
+        let outside = Ray::new(Vec3::new(0.75, 0.75, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(!tri.hit(&outside, Interval::new(0.001, f32::INFINITY), &mut rec));
+        let parallel = Ray::new(Vec3::new(0.25, 0.25, 1.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(!tri.hit(&parallel, Interval::new(0.001, f32::INFINITY), &mut rec));
+    }
+
+    #[test]
+    fn test_triangle_back_face() {
+        let mesh = unit_triangle(Vec::new(), false);
+        let tri = &mesh.triangles()[0];
+        let r = Ray::new(Vec3::new(0.25, 0.25, -1.0), Vec3::new(0.0, 0.0, 1.0));
+        let mut rec = HitRecord::default();
+        assert!(tri.hit(&r, Interval::new(0.001, f32::INFINITY), &mut rec));
+        assert!(!rec.front_face);
+        assert_eq!(rec.normal, Vec3::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn test_triangle_interpolates_attributes() {
+        let n = Vec3::new(1.0, 0.0, 1.0).unit();
+        let mesh = unit_triangle(vec![n, n, n], true);
+        let tri = &mesh.triangles()[0];
+        let r = Ray::new(Vec3::new(0.5, 0.25, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut rec = HitRecord::default();
+        assert!(tri.hit(&r, Interval::new(0.001, f32::INFINITY), &mut rec));
+        assert!((rec.normal - n).length() < 1e-6);
+        assert!((rec.u - 0.5).abs() < 1e-6 && (rec.v - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_standalone_triangle() {
+        let tri = Triangle::from_points(
+            Point3::new(0.0, 0.0, -2.0),
+            Point3::new(2.0, 0.0, -2.0),
+            Point3::new(0.0, 2.0, -2.0),
+            Arc::new(Lambertian::new(Color::ones())),
+        );
+        let bbox = tri.bounding_box();
+        assert_eq!((bbox.x.max, bbox.y.max), (2.0, 2.0));
+
+        let r = Ray::new(Point3::new(0.5, 1.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut rec = HitRecord::default();
+        assert!(tri.hit(&r, Interval::new(0.001, f32::INFINITY), &mut rec));
+        assert_eq!(rec.t, 2.0);
+        assert!(rec.front_face);
+        assert!((rec.u - 0.25).abs() < 1e-6 && (rec.v - 0.5).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,239 @@
+// Procedural noise: Perlin gradient noise with turbulence and fBm, and Worley cellular noise.
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{
+    rtweekend::{mix_seed, seeded_rng},
+    vec3::{Point3, Vec3},
+};
+
+const POINT_COUNT: usize = 256;
+
+// Perlin gradient noise over random unit gradients, returning values in about [-1, 1].
+pub struct Perlin {
+    randvec: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    // Builds the gradient and permutation tables from `seed`; equal seeds give equal noise.
+    pub fn new(seed: u64) -> Self {
+        let mut rng = seeded_rng(seed);
+        let randvec = (0..POINT_COUNT)
+            .map(|_| loop {
+                let p = Vec3::new(
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                );
+                let lensq = p.squared_length();
+                if 1e-8 < lensq && lensq <= 1.0 {
+                    break p / lensq.sqrt();
+                }
+            })
+            .collect();
+        let mut permute = || {
+            let mut perm: Vec<usize> = (0..POINT_COUNT).collect();
+            perm.shuffle(&mut rng);
+            perm
+        };
+        Self {
+            randvec,
+            perm_x: permute(),
+            perm_y: permute(),
+            perm_z: permute(),
+        }
+    }
+
+    pub fn noise(&self, p: &Point3) -> f32 {
+        let u = p.x - p.x.floor();
+        let v = p.y - p.y.floor();
+        let w = p.z - p.z.floor();
+
+        let i = p.x.floor() as i64;
+        let j = p.y.floor() as i64;
+        let k = p.z.floor() as i64;
+
+        let mut c = [[[Vec3::zero(); 2]; 2]; 2];
+        for (di, plane) in c.iter_mut().enumerate() {
+            for (dj, row) in plane.iter_mut().enumerate() {
+                for (dk, corner) in row.iter_mut().enumerate() {
+                    let index = self.perm_x[((i + di as i64) & 255) as usize]
+                        ^ self.perm_y[((j + dj as i64) & 255) as usize]
+                        ^ self.perm_z[((k + dk as i64) & 255) as usize];
+                    *corner = self.randvec[index];
+                }
+            }
+        }
+
+        Self::perlin_interp(&c, u, v, w)
+    }
+
+    fn perlin_interp(c: &[[[Vec3; 2]; 2]; 2], u: f32, v: f32, w: f32) -> f32 {
+        // Hermite smoothing removes the grid artifacts of plain trilinear interpolation.
+        let uu = u * u * (3.0 - 2.0 * u);
+        let vv = v * v * (3.0 - 2.0 * v);
+        let ww = w * w * (3.0 - 2.0 * w);
+
+        let mut accum = 0.0;
+        for (i, plane) in c.iter().enumerate() {
+            for (j, row) in plane.iter().enumerate() {
+                for (k, corner) in row.iter().enumerate() {
+                    let (fi, fj, fk) = (i as f32, j as f32, k as f32);
+                    let weight_v = Vec3::new(u - fi, v - fj, w - fk);
+                    accum += (fi * uu + (1.0 - fi) * (1.0 - uu))
+                        * (fj * vv + (1.0 - fj) * (1.0 - vv))
+                        * (fk * ww + (1.0 - fk) * (1.0 - ww))
+                        * corner.dot(&weight_v);
+                }
+            }
+        }
+        accum
+    }
+
+    // Sum of `depth` octaves of absolute noise, each at twice the frequency and half the weight.
+    pub fn turb(&self, p: &Point3, depth: u32) -> f32 {
+        let mut accum = 0.0;
+        let mut temp_p = *p;
+        let mut weight = 1.0;
+
+        for _ in 0..depth {
+            accum += weight * self.noise(&temp_p).abs();
+            weight *= 0.5;
+            temp_p = temp_p * 2.0;
+        }
+        accum
+    }
+
+    // Fractional Brownian motion: signed octaves scaled in frequency by `lacunarity` and in
+    // amplitude by `gain`, normalized back to about [-1, 1].
+    pub fn fbm(&self, p: &Point3, octaves: u32, lacunarity: f32, gain: f32) -> f32 {
+        let mut accum = 0.0;
+        let mut total = 0.0;
+        let mut temp_p = *p;
+        let mut amplitude = 1.0;
+
+        for _ in 0..octaves {
+            accum += amplitude * self.noise(&temp_p);
+            total += amplitude;
+            amplitude *= gain;
+            temp_p = temp_p * lacunarity;
+        }
+        if total > 0.0 {
+            accum / total
+        } else {
+            0.0
+        }
+    }
+}
+
+// Worley (cellular) noise with one jittered feature point per unit cell.
+pub struct Worley {
+    seed: u64,
+}
+
+impl Worley {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    // The feature point of cell (i, j, k), hashed from the seed so no tables are needed.
+    fn feature_point(&self, i: i64, j: i64, k: i64) -> Point3 {
+        let cell =
+            (i as u64 & 0x1F_FFFF) | (j as u64 & 0x1F_FFFF) << 21 | (k as u64 & 0x1F_FFFF) << 42;
+        let h = mix_seed(self.seed, cell);
+        let unit = |bits: u64| (bits & 0x1F_FFFF) as f32 / (1u64 << 21) as f32;
+        Point3::new(
+            i as f32 + unit(h),
+            j as f32 + unit(h >> 21),
+            k as f32 + unit(h >> 42),
+        )
+    }
+
+    // Distances from `p` to the nearest and second-nearest feature points (F1 and F2).
+    pub fn distances(&self, p: &Point3) -> (f32, f32) {
+        let i = p.x.floor() as i64;
+        let j = p.y.floor() as i64;
+        let k = p.z.floor() as i64;
+
+        let mut f1 = f32::INFINITY;
+        let mut f2 = f32::INFINITY;
+        for di in -1..=1 {
+            for dj in -1..=1 {
+                for dk in -1..=1 {
+                    let d = (self.feature_point(i + di, j + dj, k + dk) - *p).length();
+                    if d < f1 {
+                        f2 = f1;
+                        f1 = d;
+                    } else if d < f2 {
+                        f2 = d;
+                    }
+                }
+            }
+        }
+        (f1, f2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Perlin, Worley};
+    use crate::vec3::Point3;
+
+    fn sample_points() -> impl Iterator<Item = Point3> {
+        (0..200).map(|i| {
+            let t = i as f32 * 0.37;
+            Point3::new(t.sin() * 7.3, t * 0.41, (t * 1.7).cos() * 3.1 - t)
+        })
+    }
+
+    #[test]
+    fn test_perlin_is_deterministic() {
+        let (a, b, c) = (Perlin::new(5), Perlin::new(5), Perlin::new(6));
+        let p = Point3::new(1.3, -2.7, 0.45);
+        assert_eq!(a.noise(&p), b.noise(&p));
+        assert_ne!(a.noise(&p), c.noise(&p));
+    }
+
+    #[test]
+    fn test_perlin_range_and_lattice() {
+        let perlin = Perlin::new(1);
+        for p in sample_points() {
+            let n = perlin.noise(&p);
+            assert!((-1.0..=1.0).contains(&n), "{}", n);
+            let f = perlin.fbm(&p, 5, 2.0, 0.5);
+            assert!((-1.0..=1.0).contains(&f), "{}", f);
+            assert!(perlin.turb(&p, 7) >= 0.0);
+        }
+        // Gradient noise vanishes on the integer lattice.
+        assert_eq!(perlin.noise(&Point3::new(3.0, -4.0, 7.0)), 0.0);
+    }
+
+    #[test]
+    fn test_perlin_is_continuous() {
+        let perlin = Perlin::new(2);
+        for p in sample_points() {
+            let q = p + Point3::new(1e-3, 1e-3, 1e-3);
+            assert!((perlin.noise(&p) - perlin.noise(&q)).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn test_worley_distances() {
+        let worley = Worley::new(3);
+        for p in sample_points() {
+            let (f1, f2) = worley.distances(&p);
+            assert!(f1 <= f2);
+            // The own cell's feature point is always within a cell diagonal.
+            assert!(f1 <= 3f32.sqrt());
+        }
+        let p = Point3::new(0.5, 0.5, 0.5);
+        assert_eq!(worley.distances(&p), Worley::new(3).distances(&p));
+        assert_ne!(worley.distances(&p), Worley::new(4).distances(&p));
+
+        // Feature points are at distance zero from themselves.
+        let feature = worley.feature_point(2, -1, 5);
+        assert!(worley.distances(&feature).0 < 1e-6);
+    }
+}
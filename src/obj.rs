@@ -0,0 +1,450 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    str::SplitWhitespace,
+    sync::Arc,
+};
+
+use log::warn;
+
+use crate::{
+    material::{Dielectric, DiffuseLight, Lambertian, Material, Metal},
+    mesh::{MeshFace, MeshVertex, TriangleMesh},
+    vec3::{Color, Vec3},
+};
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Io { path, source } => {
+                write!(f, "failed to read {}: {}", path.display(), source)
+            }
+            ObjError::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+        }
+    }
+}
+
+impl Error for ObjError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ObjError::Io { source, .. } => Some(source),
+            ObjError::Parse { .. } => None,
+        }
+    }
+}
+
+fn read_file(path: &Path) -> Result<String, ObjError> {
+    fs::read_to_string(path).map_err(|source| ObjError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+// Tracks the file and line being parsed so every error can point at its origin.
+struct LineContext<'a> {
+    path: &'a Path,
+    line: usize,
+}
+
+impl LineContext<'_> {
+    fn error(&self, message: impl Into<String>) -> ObjError {
+        ObjError::Parse {
+            path: self.path.to_path_buf(),
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    fn float(&self, keyword: &str, token: Option<&str>) -> Result<f32, ObjError> {
+        let token = token.ok_or_else(|| self.error(format!("`{}` is missing a value", keyword)))?;
+        token
+            .parse::<f32>()
+            .map_err(|_| self.error(format!("invalid number `{}` in `{}`", token, keyword)))
+    }
+
+    fn vec3(&self, keyword: &str, tokens: &mut SplitWhitespace) -> Result<Vec3, ObjError> {
+        Ok(Vec3::new(
+            self.float(keyword, tokens.next())?,
+            self.float(keyword, tokens.next())?,
+            self.float(keyword, tokens.next())?,
+        ))
+    }
+
+    // Resolves a 1-based (or negative, relative) OBJ index into a buffer of `len` elements.
+    fn index(&self, token: &str, len: usize, kind: &str) -> Result<usize, ObjError> {
+        let raw = token
+            .parse::<i64>()
+            .map_err(|_| self.error(format!("invalid {} index `{}`", kind, token)))?;
+        let resolved = match raw {
+            0 => None,
+            i if i > 0 => Some(i as usize - 1),
+            i => (len as i64 + i).try_into().ok(),
+        };
+        match resolved {
+            Some(i) if i < len => Ok(i),
+            _ => Err(self.error(format!(
+                "{} index {} out of range ({} defined)",
+                kind, raw, len
+            ))),
+        }
+    }
+}
+
+// Loads a Wavefront OBJ file, along with any MTL libraries it references.
+pub fn load_obj<P: AsRef<Path>>(path: P) -> Result<Arc<TriangleMesh>, ObjError> {
+    let path = path.as_ref();
+    let source = read_file(path)?;
+    parse_obj(&source, path).map(Arc::new)
+}
+
+// Parses OBJ source; `path` is used for error messages and to resolve `mtllib` statements.
+pub fn parse_obj(source: &str, path: &Path) -> Result<TriangleMesh, ObjError> {
+    let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    let mut faces = Vec::new();
+
+    // Faces that precede any `usemtl` get a neutral grey.
+    let mut materials: Vec<Arc<dyn Material>> =
+        vec![Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))];
+    let mut material_slots: HashMap<String, usize> = HashMap::new();
+    let mut library: HashMap<String, Arc<dyn Material>> = HashMap::new();
+    let mut current_material = 0;
+
+    for (i, raw_line) in source.lines().enumerate() {
+        let ctx = LineContext { path, line: i + 1 };
+        let line = raw_line.split('#').next().unwrap_or("");
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+
+        match keyword {
+            "v" => positions.push(ctx.vec3(keyword, &mut tokens)?),
+            "vn" => {
+                let normal = ctx.vec3(keyword, &mut tokens)?;
+                let length = normal.length();
+                if !(length > 0.0 && length.is_finite()) {
+                    return Err(ctx.error("`vn` must be a finite, non-zero normal"));
+                }
+                normals.push(normal / length);
+            }
+            "vt" => {
+                let u = ctx.float(keyword, tokens.next())?;
+                let v = match tokens.next() {
+                    Some(token) => ctx.float(keyword, Some(token))?,
+                    None => 0.0,
+                };
+                uvs.push((u, v));
+            }
+            "f" => {
+                let mut polygon = Vec::new();
+                for token in tokens {
+                    let mut parts = token.split('/');
+                    let position = parts.next().unwrap_or("");
+                    let uv = parts.next().filter(|s| !s.is_empty());
+                    let normal = parts.next().filter(|s| !s.is_empty());
+                    if parts.next().is_some() {
+                        return Err(ctx.error(format!("malformed face vertex `{}`", token)));
+                    }
+                    polygon.push(MeshVertex {
+                        position: ctx.index(position, positions.len(), "vertex")?,
+                        uv: uv
+                            .map(|t| ctx.index(t, uvs.len(), "texture coordinate"))
+                            .transpose()?,
+                        normal: normal
+                            .map(|t| ctx.index(t, normals.len(), "normal"))
+                            .transpose()?,
+                    });
+                }
+                if polygon.len() < 3 {
+                    return Err(ctx.error(format!(
+                        "face needs at least 3 vertices, found {}",
+                        polygon.len()
+                    )));
+                }
+                // Fan-triangulate polygons.
+                for k in 1..polygon.len() - 1 {
+                    faces.push(MeshFace {
+                        vertices: [polygon[0], polygon[k], polygon[k + 1]],
+                        material: current_material,
+                    });
+                }
+            }
+            "mtllib" => {
+                let names: Vec<&str> = tokens.collect();
+                if names.is_empty() {
+                    return Err(ctx.error("`mtllib` is missing a file name"));
+                }
+                for name in names {
+                    library.extend(load_mtl(base_dir.join(name))?);
+                }
+            }
+            "usemtl" => {
+                let name = tokens
+                    .next()
+                    .ok_or_else(|| ctx.error("`usemtl` is missing a material name"))?;
+                current_material = match material_slots.get(name) {
+                    Some(&slot) => slot,
+                    None => {
+                        let material = library
+                            .get(name)
+                            .ok_or_else(|| ctx.error(format!("unknown material `{}`", name)))?;
+                        materials.push(material.clone());
+                        material_slots.insert(name.to_string(), materials.len() - 1);
+                        materials.len() - 1
+                    }
+                };
+            }
+            // Grouping and smoothing statements don't affect the geometry we build.
+            "o" | "g" | "s" => {}
+            _ => warn!(
+                "{}:{}: ignoring unsupported OBJ statement `{}`",
+                path.display(),
+                ctx.line,
+                keyword
+            ),
+        }
+    }
+
+    Ok(TriangleMesh::new(positions, normals, uvs, faces, materials))
+}
+
+// Material parameters as written in an MTL file.
+struct MtlRecord {
+    diffuse: Color,
+    specular: Color,
+    emission: Color,
+    shininess: f32,
+    optical_density: f32,
+    dissolve: f32,
+    illum: u32,
+}
+
+impl Default for MtlRecord {
+    fn default() -> Self {
+        Self {
+            diffuse: Color::new(0.8, 0.8, 0.8),
+            specular: Color::zero(),
+            emission: Color::zero(),
+            shininess: 0.0,
+            optical_density: 1.5,
+            dissolve: 1.0,
+            illum: 2,
+        }
+    }
+}
+
+impl MtlRecord {
+    // Maps the MTL illumination model onto the closest material we support.
+    fn to_material(&self) -> Arc<dyn Material> {
+        if !self.emission.near_zero() {
+            Arc::new(DiffuseLight::new(self.emission))
+        } else if matches!(self.illum, 4 | 6 | 7 | 9) || self.dissolve < 1.0 {
+            Arc::new(Dielectric::new(self.optical_density))
+        } else if matches!(self.illum, 3 | 5 | 8) {
+            let fuzz = (1.0 - self.shininess / 1000.0).clamp(0.0, 1.0);
+            Arc::new(Metal::new(self.specular, fuzz))
+        } else {
+            Arc::new(Lambertian::new(self.diffuse))
+        }
+    }
+}
+
+pub fn load_mtl<P: AsRef<Path>>(path: P) -> Result<HashMap<String, Arc<dyn Material>>, ObjError> {
+    let path = path.as_ref();
+    let source = read_file(path)?;
+    parse_mtl(&source, path)
+}
+
+pub fn parse_mtl(
+    source: &str,
+    path: &Path,
+) -> Result<HashMap<String, Arc<dyn Material>>, ObjError> {
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlRecord)> = None;
+
+    for (i, raw_line) in source.lines().enumerate() {
+        let ctx = LineContext { path, line: i + 1 };
+        let line = raw_line.split('#').next().unwrap_or("");
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+
+        if keyword == "newmtl" {
+            let name = tokens
+                .next()
+                .ok_or_else(|| ctx.error("`newmtl` is missing a material name"))?;
+            if let Some((name, record)) = current.take() {
+                materials.insert(name, record.to_material());
+            }
+            current = Some((name.to_string(), MtlRecord::default()));
+            continue;
+        }
+
+        let Some((_, record)) = current.as_mut() else {
+            return Err(ctx.error(format!("`{}` appears before any `newmtl`", keyword)));
+        };
+
+        match keyword {
+            "Kd" => record.diffuse = ctx.vec3(keyword, &mut tokens)?,
+            "Ks" => record.specular = ctx.vec3(keyword, &mut tokens)?,
+            "Ke" => record.emission = ctx.vec3(keyword, &mut tokens)?,
+            "Ns" => record.shininess = ctx.float(keyword, tokens.next())?,
+            "Ni" => record.optical_density = ctx.float(keyword, tokens.next())?,
+            "d" => record.dissolve = ctx.float(keyword, tokens.next())?,
+            "Tr" => record.dissolve = 1.0 - ctx.float(keyword, tokens.next())?,
+            "illum" => {
+                let token = tokens
+                    .next()
+                    .ok_or_else(|| ctx.error("`illum` is missing a value"))?;
+                record.illum = token
+                    .parse()
+                    .map_err(|_| ctx.error(format!("invalid illumination model `{}`", token)))?;
+            }
+            _ => warn!(
+                "{}:{}: ignoring unsupported MTL statement `{}`",
+                path.display(),
+                ctx.line,
+                keyword
+            ),
+        }
+    }
+
+    if let Some((name, record)) = current {
+        materials.insert(name, record.to_material());
+    }
+
+    Ok(materials)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use super::{load_obj, parse_mtl, parse_obj, ObjError};
+
+    #[test]
+    fn test_parse_quad_is_triangulated() {
+        let source = "\
+# unit quad
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 2
+f 1/1/1 2/2/1 3/3/1 4/4/1
+";
+        let mesh = parse_obj(source, Path::new("quad.obj")).unwrap();
+        assert_eq!(mesh.faces.len(), 2);
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.normals[0].length(), 1.0);
+        assert_eq!(mesh.faces[1].vertices[2].position, 3);
+        assert_eq!(mesh.faces[1].vertices[2].uv, Some(3));
+        assert_eq!(mesh.faces[1].vertices[2].normal, Some(0));
+    }
+
+    #[test]
+    fn test_parse_negative_and_normal_only_indices() {
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\nf -3//1 -2//1 -1//1\n";
+        let mesh = parse_obj(source, Path::new("tri.obj")).unwrap();
+        let face = mesh.faces[0];
+        assert_eq!(face.vertices[0].position, 0);
+        assert_eq!(face.vertices[2].position, 2);
+        assert_eq!(face.vertices[1].uv, None);
+        assert_eq!(face.vertices[1].normal, Some(0));
+    }
+
+    fn parse_error(source: &str) -> (usize, String) {
+        match parse_obj(source, Path::new("bad.obj")) {
+            Err(ObjError::Parse { line, message, .. }) => (line, message),
+            Err(other) => panic!("unexpected error {}", other),
+            Ok(_) => panic!("expected a parse error"),
+        }
+    }
+
+    #[test]
+    fn test_malformed_obj_errors() {
+        assert_eq!(parse_error("v 0 0 zero\n").0, 1);
+        assert_eq!(parse_error("v 0 0 0\nv 1 0 0\nf 1 2\n").0, 3);
+
+        let (line, message) = parse_error("v 0 0 0\n\nf 1 2 7\n");
+        assert_eq!(line, 3);
+        assert!(message.contains("out of range"), "{}", message);
+
+        let (line, message) = parse_error("vn 0 1 0\nvn 0 0 0\n");
+        assert_eq!(line, 2);
+        assert!(message.contains("non-zero normal"), "{}", message);
+        assert_eq!(parse_error("vn 0 inf 0\n").0, 1);
+
+        let (_, message) = parse_error("v 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl missing\n");
+        assert!(message.contains("unknown material"), "{}", message);
+    }
+
+    #[test]
+    fn test_parse_mtl() {
+        let source = "\
+newmtl red
+Kd 1 0 0
+newmtl lamp
+Ke 4 4 4
+newmtl glass
+Ni 1.5
+illum 7
+";
+        let materials = parse_mtl(source, Path::new("scene.mtl")).unwrap();
+        assert_eq!(materials.len(), 3);
+        assert!(materials.contains_key("lamp"));
+
+        assert!(parse_mtl("Kd 1 1 1\n", Path::new("scene.mtl")).is_err());
+    }
+
+    #[test]
+    fn test_load_obj_with_mtllib() {
+        let dir = std::env::temp_dir().join(format!("ray1-obj-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("tri.mtl"), "newmtl red\nKd 1 0 0\n").unwrap();
+        fs::write(
+            dir.join("tri.obj"),
+            "mtllib tri.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl red\nf 1 2 3\n",
+        )
+        .unwrap();
+
+        let mesh = load_obj(dir.join("tri.obj")).unwrap();
+        assert_eq!(mesh.faces.len(), 1);
+        assert_eq!(mesh.materials.len(), 2);
+        assert_eq!(mesh.faces[0].material, 1);
+
+        assert!(matches!(
+            load_obj(dir.join("missing.obj")),
+            Err(ObjError::Io { .. })
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
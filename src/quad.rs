@@ -0,0 +1,418 @@
+// Planar primitives: parallelograms, disks and the boxes built from them.
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    material::Material,
+    ray::{HitRecord, Hittable, HittableList, Interval, Ray},
+    rtweekend::{INFINITY, PI},
+    sampler::concentric_disk,
+    vec3::{Point3, Vec3},
+};
+
+// Solid-angle density of aiming from `origin` along `direction` at a uniformly sampled point
+// of a flat `shape` with the given area and unit `normal`: the area density times
+// distance^2 / cos.
+pub(crate) fn area_pdf(
+    shape: &dyn Hittable,
+    origin: &Point3,
+    direction: &Vec3,
+    area: f32,
+    normal: &Vec3,
+) -> f32 {
+    let mut rec = HitRecord::default();
+    let r = Ray::new(*origin, *direction);
+    if area <= 0.0 || !shape.hit(&r, Interval::new(0.001, INFINITY), &mut rec) {
+        return 0.0;
+    }
+    let distance_squared = rec.t * rec.t * direction.squared_length();
+    let cosine = (direction.dot(normal) / direction.length()).abs();
+    if cosine < 1e-8 {
+        return 0.0;
+    }
+    distance_squared / (cosine * area)
+}
+
+// Parallelogram spanned by the edges `u` and `v` from the corner `q`.
+pub struct Quad {
+    q: Point3,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    normal: Vec3,
+    d: f32,
+    area: f32,
+    mat: Arc<dyn Material>,
+    bbox: Aabb,
+}
+
+impl Quad {
+    pub fn new(q: Point3, u: Vec3, v: Vec3, mat: Arc<dyn Material>) -> Self {
+        let n = u.cross(&v);
+        let normal = n.unit();
+        // Bound all four vertices at once; only an axis the quad is flat along gets padded.
+        let corners = [q + u, q + v, q + u + v];
+        let lo = corners.iter().fold(q, |m, c| {
+            Point3::new(m.x.min(c.x), m.y.min(c.y), m.z.min(c.z))
+        });
+        let hi = corners.iter().fold(q, |m, c| {
+            Point3::new(m.x.max(c.x), m.y.max(c.y), m.z.max(c.z))
+        });
+        let bbox = Aabb::from_points(lo, hi);
+        Self {
+            q,
+            u,
+            v,
+            w: n / n.dot(&n),
+            normal,
+            d: normal.dot(&q),
+            area: n.length(),
+            mat,
+            bbox,
+        }
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        let denom = self.normal.dot(&r.direction);
+
+        // No hit if the ray is parallel to the plane.
+        if denom.abs() < 1e-8 {
+            return false;
+        }
+
+        let t = (self.d - self.normal.dot(&r.origin)) / denom;
+        if !ray_t.contains(t) {
+            return false;
+        }
+
+        // Express the hit point in the plane's (u, v) frame and check it lies inside.
+        let intersection = r.at(t);
+        let planar_hitpt = intersection - self.q;
+        let alpha = self.w.dot(&planar_hitpt.cross(&self.v));
+        let beta = self.w.dot(&self.u.cross(&planar_hitpt));
+        let unit = Interval::new(0.0, 1.0);
+        if !unit.contains(alpha) || !unit.contains(beta) {
+            return false;
+        }
+
+        rec.t = t;
+        rec.point = intersection;
+        rec.u = alpha;
+        rec.v = beta;
+        rec.set_face_normal(r, &self.normal);
+        rec.mat = Some(self.mat.clone());
+
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, _time: f32) -> f32 {
+        area_pdf(self, origin, direction, self.area, &self.normal)
+    }
+
+    fn random(&self, origin: &Point3, _time: f32, (s, t): (f32, f32)) -> Vec3 {
+        let p = self.q + self.u * s + self.v * t;
+        p - *origin
+    }
+}
+
+// Flat disk facing along `normal`. u is the angle around the normal, v the distance from the
+// center as a fraction of the radius.
+pub struct Disk {
+    center: Point3,
+    normal: Vec3,
+    radius: f32,
+    tangent: Vec3,
+    bitangent: Vec3,
+    mat: Arc<dyn Material>,
+    bbox: Aabb,
+}
+
+impl Disk {
+    pub fn new(center: Point3, normal: Vec3, radius: f32, mat: Arc<dyn Material>) -> Self {
+        let normal = normal.unit();
+        let radius = radius.max(0.0);
+        let (tangent, bitangent) = normal.tangent_frame();
+
+        // The disk's extent along each axis shrinks as the normal lines up with it.
+        let half = Vec3::new(
+            radius * (1.0 - normal.x * normal.x).max(0.0).sqrt(),
+            radius * (1.0 - normal.y * normal.y).max(0.0).sqrt(),
+            radius * (1.0 - normal.z * normal.z).max(0.0).sqrt(),
+        );
+        Self {
+            center,
+            normal,
+            radius,
+            tangent,
+            bitangent,
+            mat,
+            bbox: Aabb::from_points(center - half, center + half),
+        }
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        let denom = self.normal.dot(&r.direction);
+        if denom.abs() < 1e-8 {
+            return false;
+        }
+
+        let t = self.normal.dot(&(self.center - r.origin)) / denom;
+        if !ray_t.contains(t) {
+            return false;
+        }
+
+        let intersection = r.at(t);
+        let offset = intersection - self.center;
+        let dist_squared = offset.squared_length();
+        if dist_squared > self.radius * self.radius {
+            return false;
+        }
+
+        let phi = offset.dot(&self.bitangent).atan2(offset.dot(&self.tangent));
+        rec.t = t;
+        rec.point = intersection;
+        rec.u = (phi + PI) / (2.0 * PI);
+        rec.v = dist_squared.sqrt() / self.radius;
+        rec.set_face_normal(r, &self.normal);
+        rec.mat = Some(self.mat.clone());
+
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, _time: f32) -> f32 {
+        let area = PI * self.radius * self.radius;
+        area_pdf(self, origin, direction, area, &self.normal)
+    }
+
+    fn random(&self, origin: &Point3, _time: f32, u: (f32, f32)) -> Vec3 {
+        let (x, y) = concentric_disk(u);
+        let p = self.center + (self.tangent * x + self.bitangent * y) * self.radius;
+        p - *origin
+    }
+}
+
+// Axis-aligned box with opposite corners `a` and `b`, made of six outward-facing quads.
+pub struct BoxShape {
+    sides: HittableList,
+    bbox: Aabb,
+}
+
+impl BoxShape {
+    pub fn new(a: Point3, b: Point3, mat: Arc<dyn Material>) -> Self {
+        let min = Point3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z));
+        let max = Point3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z));
+
+        let dx = Vec3::new(max.x - min.x, 0.0, 0.0);
+        let dy = Vec3::new(0.0, max.y - min.y, 0.0);
+        let dz = Vec3::new(0.0, 0.0, max.z - min.z);
+
+        let mut sides = HittableList::new();
+        let mut side = |q: Point3, u: Vec3, v: Vec3| {
+            sides.add(Arc::new(Quad::new(q, u, v, mat.clone())));
+        };
+        side(Point3::new(min.x, min.y, max.z), dx, dy); // front
+        side(Point3::new(max.x, min.y, max.z), -dz, dy); // right
+        side(Point3::new(max.x, min.y, min.z), -dx, dy); // back
+        side(Point3::new(min.x, min.y, min.z), dz, dy); // left
+        side(Point3::new(min.x, max.y, max.z), dx, -dz); // top
+        side(Point3::new(min.x, min.y, min.z), dx, dz); // bottom
+
+        // The padded boxes of the flat sides would overshoot the box itself.
+        Self {
+            sides,
+            bbox: Aabb::from_points(min, max),
+        }
+    }
+}
+
+impl Hittable for BoxShape {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        self.sides.hit(r, ray_t, rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, time: f32) -> f32 {
+        self.sides.pdf_value(origin, direction, time)
+    }
+
+    fn random(&self, origin: &Point3, time: f32, u: (f32, f32)) -> Vec3 {
+        self.sides.random(origin, time, u)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{BoxShape, Disk, Quad};
+    use crate::material::Lambertian;
+    use crate::ray::{HitRecord, Hittable, Interval, Ray};
+    use crate::rtweekend::PI;
+    use crate::sampler::{IndependentSampler, Sampler};
+    use crate::vec3::{Color, Point3, Vec3};
+
+    fn gray() -> Arc<Lambertian> {
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    fn cast(object: &dyn Hittable, origin: Point3, direction: Vec3) -> Option<HitRecord> {
+        let mut rec = HitRecord::default();
+        let r = Ray::new(origin, direction);
+        object
+            .hit(&r, Interval::new(0.001, f32::INFINITY), &mut rec)
+            .then_some(rec)
+    }
+
+    #[test]
+    fn test_quad_hit_and_uv() {
+        let quad = Quad::new(
+            Point3::new(-1.0, -1.0, -3.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 4.0, 0.0),
+            gray(),
+        );
+        let down_z = Vec3::new(0.0, 0.0, -1.0);
+        let rec = cast(&quad, Point3::new(0.5, 2.0, 0.0), down_z).unwrap();
+        assert_eq!(rec.t, 3.0);
+        assert_eq!((rec.u, rec.v), (0.75, 0.75));
+        assert!(rec.front_face);
+        assert_eq!(rec.normal, Vec3::new(0.0, 0.0, 1.0));
+
+        let back = cast(&quad, Point3::new(0.0, 0.0, -5.0), -down_z).unwrap();
+        assert!(!back.front_face);
+        assert_eq!(back.normal, Vec3::new(0.0, 0.0, -1.0));
+
+        assert!(cast(&quad, Point3::new(1.5, 0.0, 0.0), down_z).is_none());
+        assert!(cast(&quad, Point3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn test_flat_quad_has_padded_bbox() {
+        let quad = Quad::new(
+            Point3::zero(),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            gray(),
+        );
+        let bbox = quad.bounding_box();
+        assert!(bbox.y.size() > 0.0);
+        assert_eq!((bbox.x.max, bbox.z.max), (1.0, 1.0));
+    }
+
+    #[test]
+    fn test_disk() {
+        let disk = Disk::new(
+            Point3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 2.0, 0.0),
+            2.0,
+            gray(),
+        );
+        let down = Vec3::new(0.0, -1.0, 0.0);
+
+        let rec = cast(&disk, Point3::new(1.0, 3.0, 0.0), down).unwrap();
+        assert_eq!(rec.t, 2.0);
+        assert!(rec.front_face);
+        assert!((rec.v - 0.5).abs() < 1e-6);
+        assert!((0.0..=1.0).contains(&rec.u));
+        assert!(cast(&disk, Point3::new(1.5, 3.0, 1.5), down).is_none());
+
+        let bbox = disk.bounding_box();
+        assert_eq!((bbox.x.min, bbox.x.max, bbox.z.max), (-2.0, 2.0, 2.0));
+        assert!(bbox.y.contains(1.0) && bbox.y.size() < 0.01);
+    }
+
+    #[test]
+    fn test_box_faces_point_outward() {
+        let cube = BoxShape::new(
+            Point3::new(1.0, 1.0, 1.0),
+            Point3::new(-1.0, -1.0, -1.0),
+            gray(),
+        );
+        let bbox = cube.bounding_box();
+        assert_eq!((bbox.x.min, bbox.y.max, bbox.z.min), (-1.0, 1.0, -1.0));
+
+        let axes = [
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        ];
+        for axis in axes {
+            for dir in [axis, -axis] {
+                // From outside, the first hit is the face toward the ray origin.
+                let rec = cast(&cube, dir * 5.0, -dir).unwrap();
+                assert_eq!(rec.t, 4.0);
+                assert!(rec.front_face);
+                assert_eq!(rec.normal, dir);
+                // From inside, every face is seen from the back.
+                let rec = cast(&cube, Point3::zero(), dir).unwrap();
+                assert!(!rec.front_face);
+                assert_eq!(rec.normal, -dir);
+            }
+        }
+    }
+
+    // Monte Carlo estimate of the density `object` assigns to the whole sphere of directions
+    // from `origin`, which is 1 for an object that can be sampled.
+    fn total_pdf(object: &dyn Hittable, origin: Point3) -> f32 {
+        let n = 200000;
+        let sum: f32 = (0..n)
+            .map(|_| object.pdf_value(&origin, &Vec3::random_unit_vec(), 0.0))
+            .sum();
+        sum / n as f32 * 4.0 * PI
+    }
+
+    #[test]
+    fn test_light_sampling_densities() {
+        let origin = Point3::new(0.3, 0.2, 0.0);
+        let quad = Quad::new(
+            Point3::new(-1.0, -1.0, -1.5),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 2.0, -0.5),
+            gray(),
+        );
+        let disk = Disk::new(
+            Point3::new(0.0, -1.0, 0.0),
+            Vec3::new(0.2, 1.0, 0.0),
+            1.5,
+            gray(),
+        );
+        let block = BoxShape::new(
+            Point3::new(-1.0, -1.0, -3.0),
+            Point3::new(1.0, 1.0, -2.0),
+            gray(),
+        );
+        for object in [&quad as &dyn Hittable, &disk, &block] {
+            let total = total_pdf(object, origin);
+            assert!((total - 1.0).abs() < 0.05, "{}", total);
+
+            // Sampled directions point at the object.
+            for _ in 0..100 {
+                let direction = object.random(&origin, 0.0, IndependentSampler.get_2d());
+                assert!(cast(object, origin, direction).is_some());
+                assert!(object.pdf_value(&origin, &direction, 0.0) > 0.0);
+            }
+        }
+
+        // Seen edge-on, a quad can't be sampled.
+        let along = Vec3::new(1.0, 0.0, 0.0);
+        assert_eq!(
+            quad.pdf_value(&Point3::new(-5.0, 0.0, -1.5), &along, 0.0),
+            0.0
+        );
+    }
+}
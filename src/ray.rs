@@ -3,10 +3,14 @@ use core::f32;
 use std::sync::Arc;
 
 use crate::{
-    rtweekend::INFINITY,
+    aabb::Aabb,
+    material::Material,
+    rtweekend::{INFINITY, PI},
+    sampler::{pick, uniform_sphere},
     vec3::{Point3, Vec3},
 };
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Interval {
     pub min: f32,
     pub max: f32,
@@ -24,6 +28,14 @@ impl Interval {
         Self { min, max }
     }
 
+    // The tightest interval enclosing both `a` and `b`.
+    pub fn enclosing(a: &Interval, b: &Interval) -> Self {
+        Self {
+            min: a.min.min(b.min),
+            max: a.max.max(b.max),
+        }
+    }
+
     pub fn size(&self) -> f32 {
         self.max - self.min
     }
@@ -45,6 +57,11 @@ impl Interval {
             x
         }
     }
+
+    pub fn expand(&self, delta: f32) -> Self {
+        let padding = delta / 2.0;
+        Self::new(self.min - padding, self.max + padding)
+    }
 }
 
 pub const EMPTY_INTERVAL: Interval = Interval {
@@ -53,18 +70,29 @@ pub const EMPTY_INTERVAL: Interval = Interval {
 };
 
 pub const UNIVERSE_INTERVAL: Interval = Interval {
-    min: f32::INFINITY,
-    max: f32::NEG_INFINITY,
+    min: f32::NEG_INFINITY,
+    max: f32::INFINITY,
 };
 
+#[derive(Clone, Copy, Debug)]
 pub struct Ray {
     pub origin: Point3,
     pub direction: Vec3,
+    pub time: f32,
 }
 
 impl Ray {
     pub fn new(origin: Point3, direction: Vec3) -> Self {
-        Self { origin, direction }
+        Self::with_time(origin, direction, 0.0)
+    }
+
+    // A ray sent at `time` within the camera's shutter interval.
+    pub fn with_time(origin: Point3, direction: Vec3, time: f32) -> Self {
+        Self {
+            origin,
+            direction,
+            time,
+        }
     }
 
     pub fn at(&self, t: f32) -> Point3 {
@@ -76,12 +104,15 @@ impl Ray {
 pub struct HitRecord {
     pub point: Point3,
     pub normal: Vec3,
+    pub mat: Option<Arc<dyn Material>>,
     pub t: f32,
-    front_face: bool,
+    pub u: f32,
+    pub v: f32,
+    pub front_face: bool,
 }
 
 impl HitRecord {
-    fn set_face_normal(&mut self, r: &Ray, outward_normal: &Vec3) {
+    pub fn set_face_normal(&mut self, r: &Ray, outward_normal: &Vec3) {
         self.front_face = r.direction.dot(outward_normal) < 0.0;
         self.normal = match self.front_face {
             true => *outward_normal,
@@ -90,27 +121,92 @@ impl HitRecord {
     }
 }
 
-pub trait Hittable {
+pub trait Hittable: Send + Sync {
     fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool;
+
+    fn bounding_box(&self) -> Aabb;
+
+    // Density, per unit solid angle, of `random` choosing `direction` from `origin` at `time`.
+    // Objects that can't be sampled as lights return 0.
+    fn pdf_value(&self, _origin: &Point3, _direction: &Vec3, _time: f32) -> f32 {
+        0.0
+    }
+
+    // A direction from `origin` toward the point of the object, as it stands at `time`, that
+    // the sample `u` maps to; not necessarily unit.
+    fn random(&self, _origin: &Point3, _time: f32, _u: (f32, f32)) -> Vec3 {
+        Vec3::new(1.0, 0.0, 0.0)
+    }
+
+    // Fraction of the light along `r` within `ray_t` that passes the object, for shadow rays.
+    // Solid objects block all of it wherever the ray hits them.
+    fn transmittance(&self, r: &Ray, ray_t: Interval) -> f32 {
+        if self.hit(r, ray_t, &mut HitRecord::default()) {
+            0.0
+        } else {
+            1.0
+        }
+    }
 }
 
 pub struct Sphere {
-    center: Vec3,
+    // Path of the center: at `center.origin` at time 0, moving by `center.direction` per unit
+    // of time.
+    center: Ray,
     radius: f32,
+    mat: Arc<dyn Material>,
+    bbox: Aabb,
 }
 
 impl Sphere {
-    pub fn new(center: Vec3, radius: f32) -> Self {
+    pub fn new(center: Vec3, radius: f32, mat: Arc<dyn Material>) -> Self {
+        let radius = radius.max(0.0);
+        let rvec = Vec3::new(radius, radius, radius);
+        Self {
+            center: Ray::new(center, Vec3::zero()),
+            radius,
+            mat,
+            bbox: Aabb::from_points(center - rvec, center + rvec),
+        }
+    }
+
+    // A sphere moving in a straight line from `center1` at time 0 to `center2` at time 1.
+    pub fn moving(center1: Point3, center2: Point3, radius: f32, mat: Arc<dyn Material>) -> Self {
+        let radius = radius.max(0.0);
+        let rvec = Vec3::new(radius, radius, radius);
+        let box1 = Aabb::from_points(center1 - rvec, center1 + rvec);
+        let box2 = Aabb::from_points(center2 - rvec, center2 + rvec);
         Self {
-            center,
-            radius: radius.max(0.0),
+            center: Ray::new(center1, center2 - center1),
+            radius,
+            mat,
+            bbox: Aabb::surrounding(&box1, &box2),
         }
     }
+
+    // Maps a point on the unit sphere to (u, v): u is the angle around the Y axis from X = -1,
+    // v the angle from Y = -1 up to Y = +1, both normalized to [0, 1].
+    fn get_sphere_uv(p: &Point3) -> (f32, f32) {
+        let theta = (-p.y).clamp(-1.0, 1.0).acos();
+        let phi = (-p.z).atan2(p.x) + PI;
+        (phi / (2.0 * PI), theta / PI)
+    }
+
+    // The direction that `(r1, r2)` maps to within the cone that a sphere of `radius`, at
+    // squared distance `distance_squared`, subtends around +Z, uniform in solid angle.
+    fn random_to_sphere(radius: f32, distance_squared: f32, (r1, r2): (f32, f32)) -> Vec3 {
+        let cos_theta_max = (1.0 - radius * radius / distance_squared).max(0.0).sqrt();
+        let z = 1.0 + r2 * (cos_theta_max - 1.0);
+        let phi = 2.0 * PI * r1;
+        let sin_theta = (1.0 - z * z).max(0.0).sqrt();
+        Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, z)
+    }
 }
 
 impl Hittable for Sphere {
     fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
-        let oc = self.center - r.origin;
+        let current_center = self.center.at(r.time);
+        let oc = current_center - r.origin;
         let a = r.direction.squared_length();
         let h = r.direction.dot(&oc);
         let c = oc.squared_length() - self.radius * self.radius;
@@ -133,16 +229,52 @@ impl Hittable for Sphere {
 
         rec.t = root;
         rec.point = r.at(rec.t);
-        let outward_normal = (rec.point - self.center) / self.radius;
+        let outward_normal = (rec.point - current_center) / self.radius;
         rec.set_face_normal(r, &outward_normal);
+        (rec.u, rec.v) = Self::get_sphere_uv(&outward_normal);
+        rec.mat = Some(self.mat.clone());
 
         true
     }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, time: f32) -> f32 {
+        let mut rec = HitRecord::default();
+        let r = Ray::with_time(*origin, *direction, time);
+        if !self.hit(&r, Interval::new(0.001, INFINITY), &mut rec) {
+            return 0.0;
+        }
+
+        let distance_squared = (self.center.at(time) - *origin).squared_length();
+        if distance_squared <= self.radius * self.radius {
+            // From inside, every direction hits the sphere.
+            return 1.0 / (4.0 * PI);
+        }
+        let cos_theta_max = (1.0 - self.radius * self.radius / distance_squared).sqrt();
+        let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
+        1.0 / solid_angle
+    }
+
+    fn random(&self, origin: &Point3, time: f32, sample: (f32, f32)) -> Vec3 {
+        let direction = self.center.at(time) - *origin;
+        let distance_squared = direction.squared_length();
+        if distance_squared <= self.radius * self.radius {
+            return uniform_sphere(sample);
+        }
+        let w = direction.unit();
+        let (u, v) = w.tangent_frame();
+        let local = Self::random_to_sphere(self.radius, distance_squared, sample);
+        u * local.x + v * local.y + w * local.z
+    }
 }
 
 // HittableList struct to hold a list of hittable objects.
 pub struct HittableList {
-    objects: Vec<Arc<dyn Hittable>>,
+    pub objects: Vec<Arc<dyn Hittable>>,
+    bbox: Aabb,
 }
 
 impl HittableList {
@@ -150,6 +282,7 @@ impl HittableList {
     pub fn new() -> Self {
         Self {
             objects: Vec::new(),
+            bbox: Aabb::default(),
         }
     }
 
@@ -163,12 +296,22 @@ impl HittableList {
     // Method to clear all objects from the list.
     pub fn clear(&mut self) {
         self.objects.clear();
+        self.bbox = Aabb::default();
     }
 
     // Method to add a hittable object to the list.
     pub fn add(&mut self, object: Arc<dyn Hittable>) {
+        self.bbox = Aabb::surrounding(&self.bbox, &object.bounding_box());
         self.objects.push(object);
     }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
 }
 
 // Implement the Hittable trait for HittableList.
@@ -189,16 +332,192 @@ impl Hittable for HittableList {
 
         hit_anything
     }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    // Sampling picks one object uniformly, so the density is the average of theirs.
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, time: f32) -> f32 {
+        if self.objects.is_empty() {
+            return 0.0;
+        }
+        let sum: f32 = self
+            .objects
+            .iter()
+            .map(|object| object.pdf_value(origin, direction, time))
+            .sum();
+        sum / self.objects.len() as f32
+    }
+
+    fn random(&self, origin: &Point3, time: f32, (u, v): (f32, f32)) -> Vec3 {
+        if self.objects.is_empty() {
+            return Vec3::new(1.0, 0.0, 0.0);
+        }
+        let (index, u) = pick(u, self.objects.len());
+        self.objects[index].random(origin, time, (u, v))
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: Interval) -> f32 {
+        let mut transmittance = 1.0;
+        for object in &self.objects {
+            transmittance *= object.transmittance(r, ray_t);
+            if transmittance <= 0.0 {
+                return 0.0;
+            }
+        }
+        transmittance
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Ray;
-    use super::Vec3;
+    use std::sync::Arc;
+
+    use super::{HitRecord, Hittable, HittableList, Ray, Sphere, UNIVERSE_INTERVAL};
+    use super::{Point3, Vec3};
+    use crate::material::Lambertian;
+    use crate::rtweekend::PI;
+    use crate::sampler::{IndependentSampler, Sampler};
+    use crate::vec3::Color;
 
     #[test]
     fn test_at() {
         let ray = Ray::new(Vec3::new(1.0, 1.0, 1.0), Vec3::new(1.0, 2.0, 3.0));
         assert_eq!(ray.at(3.0), Vec3::new(4.0, 7.0, 10.0));
     }
+
+    #[test]
+    fn test_sphere_uv() {
+        let close = |(u, v): (f32, f32), (eu, ev): (f32, f32)| {
+            (u - eu).abs() < 1e-5 && (v - ev).abs() < 1e-5
+        };
+        assert!(close(
+            Sphere::get_sphere_uv(&Vec3::new(1.0, 0.0, 0.0)),
+            (0.5, 0.5)
+        ));
+        assert!(close(
+            Sphere::get_sphere_uv(&Vec3::new(0.0, 1.0, 0.0)),
+            (0.5, 1.0)
+        ));
+        assert!(close(
+            Sphere::get_sphere_uv(&Vec3::new(0.0, -1.0, 0.0)),
+            (0.5, 0.0)
+        ));
+        assert!(close(
+            Sphere::get_sphere_uv(&Vec3::new(-1.0, 0.0, 0.0)),
+            (0.0, 0.5)
+        ));
+        assert!(close(
+            Sphere::get_sphere_uv(&Vec3::new(0.0, 0.0, 1.0)),
+            (0.25, 0.5)
+        ));
+        assert!(close(
+            Sphere::get_sphere_uv(&Vec3::new(0.0, 0.0, -1.0)),
+            (0.75, 0.5)
+        ));
+    }
+
+    #[test]
+    fn test_sphere_hit_sets_uv() {
+        let mat = Arc::new(Lambertian::new(Color::ones()));
+        let sphere = Sphere::new(Point3::new(0.0, 0.0, -5.0), 2.0, mat);
+        let r = Ray::new(Point3::zero(), Vec3::new(0.0, 0.0, -1.0));
+        let mut rec = HitRecord::default();
+        assert!(sphere.hit(&r, UNIVERSE_INTERVAL, &mut rec));
+        assert_eq!(rec.t, 3.0);
+        assert!((rec.u - 0.25).abs() < 1e-5 && (rec.v - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_moving_sphere() {
+        let mat = Arc::new(Lambertian::new(Color::ones()));
+        let sphere = Sphere::moving(
+            Point3::new(0.0, 0.0, -5.0),
+            Point3::new(0.0, 4.0, -5.0),
+            1.0,
+            mat,
+        );
+        let mut rec = HitRecord::default();
+        let at = |time| Ray::with_time(Point3::new(0.0, 2.0, 0.0), Vec3::new(0.0, 0.0, -1.0), time);
+        assert!(!sphere.hit(&at(0.0), UNIVERSE_INTERVAL, &mut rec));
+        assert!(sphere.hit(&at(0.5), UNIVERSE_INTERVAL, &mut rec));
+        assert_eq!(rec.t, 4.0);
+        assert_eq!(rec.normal, Vec3::new(0.0, 0.0, 1.0));
+        assert!(!sphere.hit(&at(1.0), UNIVERSE_INTERVAL, &mut rec));
+
+        let bbox = sphere.bounding_box();
+        assert_eq!((bbox.y.min, bbox.y.max), (-1.0, 5.0));
+
+        // Light samples aim at the sphere where it is at the sample's time.
+        let origin = Point3::new(0.0, 2.0, 0.0);
+        for _ in 0..100 {
+            let direction = sphere.random(&origin, 0.5, IndependentSampler.get_2d());
+            assert!(sphere.hit(
+                &Ray::with_time(origin, direction, 0.5),
+                UNIVERSE_INTERVAL,
+                &mut rec
+            ));
+            assert!(sphere.pdf_value(&origin, &direction, 0.5) > 0.0);
+        }
+        let ahead = Vec3::new(0.0, 0.0, -1.0);
+        assert!(sphere.pdf_value(&origin, &ahead, 0.5) > 0.0);
+        assert_eq!(sphere.pdf_value(&origin, &ahead, 0.0), 0.0);
+    }
+
+    #[test]
+    fn test_sphere_light_sampling() {
+        let mat = Arc::new(Lambertian::new(Color::ones()));
+        let sphere = Sphere::new(Point3::new(0.0, 0.0, -3.0), 1.0, mat);
+        let origin = Point3::zero();
+
+        // Samples stay within the cone around the sphere, where the density is 1 / its solid
+        // angle.
+        let cone = 2.0 * PI * (1.0 - (8.0f32 / 9.0).sqrt());
+        for _ in 0..1000 {
+            let direction = sphere.random(&origin, 0.0, IndependentSampler.get_2d());
+            let pdf = sphere.pdf_value(&origin, &direction, 0.0);
+            assert!((pdf * cone - 1.0).abs() < 1e-3, "{}", pdf);
+        }
+        assert_eq!(
+            sphere.pdf_value(&origin, &Vec3::new(0.0, 0.0, 1.0), 0.0),
+            0.0
+        );
+
+        // From inside, directions are uniform over the whole sphere.
+        let inside = Point3::new(0.0, 0.5, -3.0);
+        let pdf = sphere.pdf_value(
+            &inside,
+            &sphere.random(&inside, 0.0, IndependentSampler.get_2d()),
+            0.0,
+        );
+        assert!((pdf - 1.0 / (4.0 * PI)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_list_pdf_averages_objects() {
+        let mat = Arc::new(Lambertian::new(Color::ones()));
+        let mut list = HittableList::new();
+        let origin = Point3::zero();
+        let ahead = Vec3::new(0.0, 0.0, -1.0);
+        assert_eq!(list.pdf_value(&origin, &ahead, 0.0), 0.0);
+
+        let near = Arc::new(Sphere::new(Point3::new(0.0, 0.0, -3.0), 1.0, mat.clone()));
+        list.add(near.clone());
+        list.add(Arc::new(Sphere::new(Point3::new(0.0, 5.0, 0.0), 1.0, mat)));
+        let expected = near.pdf_value(&origin, &ahead, 0.0) / 2.0;
+        assert!((list.pdf_value(&origin, &ahead, 0.0) - expected).abs() < 1e-6);
+
+        // The first sample value picks the sphere, evenly, and is then reused within it.
+        let ups = (0..1000)
+            .filter(|i| {
+                let u = (*i as f32 + 0.5) / 1000.0;
+                list.random(&origin, 0.0, (u, 0.5)).y > 0.5
+            })
+            .count();
+        assert_eq!(ups, 500);
+        let first = list.random(&origin, 0.0, (0.2, 0.3));
+        let alone = near.random(&origin, 0.0, (0.4, 0.3));
+        assert!((first - alone).length() < 1e-5);
+    }
 }
@@ -1,21 +1,55 @@
 #![allow(dead_code)]
 
-use rand::Rng;
+use std::cell::RefCell;
+
+use rand::{
+    rngs::{SmallRng, StdRng},
+    Rng, SeedableRng,
+};
 pub const INFINITY: f32 = f32::INFINITY;
-pub const PI: f32 = 3.1415926535897932385;
+pub const PI: f32 = std::f32::consts::PI;
+
+thread_local! {
+    // Each thread draws from its own generator; renderers reseed it for every pixel sample so
+    // the output doesn't depend on which thread picked the work up, or in what order. That
+    // happens once per camera ray, so it uses a generator that is cheap to seed.
+    static RNG: RefCell<SmallRng> = RefCell::new(SmallRng::from_entropy());
+}
 
 pub fn degrees_to_radians(degrees: f32) -> f32 {
     degrees * PI / 180.0
 }
 
+// Reseeds the calling thread's random number generator.
+pub fn seed_rng(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = SmallRng::seed_from_u64(seed));
+}
+
+// Derives an independent seed for `stream` from `seed` (SplitMix64 finalizer).
+pub fn mix_seed(seed: u64, stream: u64) -> u64 {
+    let mut z = seed ^ stream.wrapping_add(1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+// The seed of the stream for `pixel`, derived from a render's base `seed`.
+pub fn pixel_seed(seed: u64, pixel: (i32, i32)) -> u64 {
+    mix_seed(mix_seed(seed, pixel.0 as u32 as u64), pixel.1 as u32 as u64)
+}
+
+// A standalone generator for building seeded data such as noise tables, so that it neither
+// depends on nor disturbs the per-thread stream used while rendering.
+pub fn seeded_rng(seed: u64) -> StdRng {
+    StdRng::seed_from_u64(seed)
+}
+
 pub fn random_double() -> f32 {
     // Returns a random real in [0, 1)
-    let mut rng = rand::thread_rng();
-    rng.gen::<f32>() // Generates a random value in the range [0, 1)
+    RNG.with(|rng| rng.borrow_mut().gen::<f32>())
 }
 
 pub fn random_double_in_range(min: f32, max: f32) -> f32 {
     // Returns a random real in [min, max)
-    let mut rng = rand::thread_rng();
-    rng.gen_range(min..max) // Generates a random value in the range [min, max)
+    RNG.with(|rng| rng.borrow_mut().gen_range(min..max))
 }
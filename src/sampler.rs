@@ -0,0 +1,535 @@
+// Sources of the sample values that the camera and integrators turn into rays and paths.
+use std::str::FromStr;
+
+use crate::{
+    rtweekend::{mix_seed, pixel_seed, random_double, PI},
+    vec3::Vec3,
+};
+
+// Camera rays, scattering and light samples take their values from a sampler. Media don't:
+// they pick collision distances within `Hittable::hit`, and tracking through a heterogeneous
+// volume takes a value per tentative collision, with no bound a sampler could lay out
+// dimensions for. They draw from the pixel sample's seeded random stream instead.
+pub trait Sampler {
+    // Begins sample `index` of the pixel at `pixel`. The values that follow come from the
+    // successive dimensions of that sample, so a well-distributed sampler spreads each
+    // dimension evenly over the pixel's samples.
+    fn start_pixel_sample(&mut self, _pixel: (i32, i32), _index: u32) {}
+
+    // A value in [0, 1).
+    fn get_1d(&mut self) -> f32;
+
+    // A point in [0, 1)^2.
+    fn get_2d(&mut self) -> (f32, f32) {
+        (self.get_1d(), self.get_1d())
+    }
+}
+
+pub const SAMPLERS: &[&str] = &["independent", "stratified", "halton", "sobol"];
+
+// The kinds of sampler the camera can render with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SamplerKind {
+    #[default]
+    Independent,
+    Stratified,
+    Halton,
+    Sobol,
+}
+
+impl SamplerKind {
+    // A sampler of this kind for `samples_per_pixel` samples in each pixel, randomized by `seed`.
+    pub fn create(self, samples_per_pixel: u32, seed: u64) -> Box<dyn Sampler> {
+        match self {
+            SamplerKind::Independent => Box::new(IndependentSampler),
+            SamplerKind::Stratified => Box::new(StratifiedSampler::new(samples_per_pixel, seed)),
+            SamplerKind::Halton => Box::new(HaltonSampler::new(seed)),
+            SamplerKind::Sobol => Box::new(SobolSampler::new(seed)),
+        }
+    }
+}
+
+impl FromStr for SamplerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "independent" => Ok(SamplerKind::Independent),
+            "stratified" => Ok(SamplerKind::Stratified),
+            "halton" => Ok(SamplerKind::Halton),
+            "sobol" => Ok(SamplerKind::Sobol),
+            _ => Err(format!(
+                "unknown sampler `{}`; expected one of {}",
+                s,
+                SAMPLERS.join(", ")
+            )),
+        }
+    }
+}
+
+// Uncorrelated uniform values from the thread's random number generator.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct IndependentSampler;
+
+impl Sampler for IndependentSampler {
+    fn get_1d(&mut self) -> f32 {
+        random_double()
+    }
+}
+
+// The pixel sample a sampler is in and how many of its dimensions have been used. Every
+// dimension of every pixel gets its own hash, from which samplers derive their permutations
+// and scrambles, so the values depend only on the seed, pixel, sample index and dimension.
+#[derive(Clone, Copy, Debug, Default)]
+struct SampleCursor {
+    seed: u64,
+    pixel_hash: u64,
+    index: u32,
+    dimension: u32,
+}
+
+impl SampleCursor {
+    fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            ..Default::default()
+        }
+    }
+
+    fn start(&mut self, pixel: (i32, i32), index: u32) {
+        self.pixel_hash = pixel_seed(self.seed, pixel);
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    // Moves on to the next dimension, returning it and its hash.
+    fn next_dimension(&mut self) -> (u32, u64) {
+        let dimension = self.dimension;
+        self.dimension += 1;
+        (dimension, mix_seed(self.pixel_hash, dimension as u64))
+    }
+}
+
+// Jittered stratification: each dimension splits [0, 1) into one stratum per pixel sample, and
+// each 2D pair splits the square into a grid of them. The samples visit the strata in a
+// different random order per dimension, which keeps the dimensions uncorrelated.
+#[derive(Clone, Debug)]
+pub struct StratifiedSampler {
+    samples_per_pixel: u32,
+    // Grid of 2D strata; as square as `samples_per_pixel` allows.
+    x_strata: u32,
+    y_strata: u32,
+    cursor: SampleCursor,
+}
+
+impl StratifiedSampler {
+    pub fn new(samples_per_pixel: u32, seed: u64) -> Self {
+        let samples_per_pixel = samples_per_pixel.max(1);
+        let mut x_strata = (samples_per_pixel as f32).sqrt() as u32;
+        while samples_per_pixel % x_strata != 0 {
+            x_strata -= 1;
+        }
+        Self {
+            samples_per_pixel,
+            x_strata,
+            y_strata: samples_per_pixel / x_strata,
+            cursor: SampleCursor::new(seed),
+        }
+    }
+
+    // The stratum this sample takes in the dimension with hash `hash`, and the hash for its
+    // jitter. Samples past `samples_per_pixel` start another round with a fresh order.
+    fn stratum(&self, hash: u64) -> (u32, u64) {
+        let n = self.samples_per_pixel;
+        let round = mix_seed(hash, (self.cursor.index / n) as u64);
+        let stratum = permutation_element(self.cursor.index % n, n, round as u32);
+        (stratum, mix_seed(round, self.cursor.index as u64))
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel_sample(&mut self, pixel: (i32, i32), index: u32) {
+        self.cursor.start(pixel, index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let (_, hash) = self.cursor.next_dimension();
+        let (stratum, jitter) = self.stratum(hash);
+        let u = (stratum as f32 + bits_to_unit(jitter as u32)) / self.samples_per_pixel as f32;
+        u.min(ONE_MINUS_EPSILON)
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let (_, hash) = self.cursor.next_dimension();
+        let (stratum, jitter) = self.stratum(hash);
+        let (x, y) = (stratum % self.x_strata, stratum / self.x_strata);
+        let u = (x as f32 + bits_to_unit(jitter as u32)) / self.x_strata as f32;
+        let v = (y as f32 + bits_to_unit((jitter >> 32) as u32)) / self.y_strata as f32;
+        (u.min(ONE_MINUS_EPSILON), v.min(ONE_MINUS_EPSILON))
+    }
+}
+
+// Bases of the Halton sequence's dimensions; dimensions beyond them get hashed values.
+const PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+// The Halton sequence: dimension d of sample i is the radical inverse of i in the d-th prime
+// base. Each pixel shifts every dimension by its own random offset, modulo 1 (a Cranley-Patterson
+// rotation), so neighboring pixels don't repeat the same pattern.
+#[derive(Clone, Debug)]
+pub struct HaltonSampler {
+    cursor: SampleCursor,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            cursor: SampleCursor::new(seed),
+        }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel_sample(&mut self, pixel: (i32, i32), index: u32) {
+        self.cursor.start(pixel, index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let (dimension, hash) = self.cursor.next_dimension();
+        let offset = bits_to_unit(hash as u32);
+        let Some(&base) = PRIMES.get(dimension as usize) else {
+            return bits_to_unit(mix_seed(hash, self.cursor.index as u64) as u32);
+        };
+        let u = radical_inverse(base, self.cursor.index) + offset;
+        (if u >= 1.0 { u - 1.0 } else { u }).min(ONE_MINUS_EPSILON)
+    }
+}
+
+// Owen-scrambled Sobol points, generated and scrambled per pair of dimensions as in Burley's
+// "Practical Hash-based Owen Scrambling" (2020): every dimension uses the first one or two
+// Sobol dimensions, with the sample order shuffled independently for each, so any number of
+// dimensions can be drawn.
+#[derive(Clone, Debug)]
+pub struct SobolSampler {
+    cursor: SampleCursor,
+}
+
+impl SobolSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            cursor: SampleCursor::new(seed),
+        }
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_pixel_sample(&mut self, pixel: (i32, i32), index: u32) {
+        self.cursor.start(pixel, index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let (_, hash) = self.cursor.next_dimension();
+        let index = nested_uniform_scramble(self.cursor.index, hash as u32);
+        bits_to_unit(nested_uniform_scramble(
+            sobol(index, 0),
+            (hash >> 32) as u32,
+        ))
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let (_, hash) = self.cursor.next_dimension();
+        let index = nested_uniform_scramble(self.cursor.index, hash as u32);
+        let scramble = mix_seed(hash, 1);
+        (
+            bits_to_unit(nested_uniform_scramble(sobol(index, 0), scramble as u32)),
+            bits_to_unit(nested_uniform_scramble(
+                sobol(index, 1),
+                (scramble >> 32) as u32,
+            )),
+        )
+    }
+}
+
+// The largest f32 below 1.
+const ONE_MINUS_EPSILON: f32 = 1.0 - f32::EPSILON / 2.0;
+
+// Maps 32 random bits to [0, 1), keeping the 24 that an f32 can represent.
+fn bits_to_unit(bits: u32) -> f32 {
+    (bits >> 8) as f32 / (1u32 << 24) as f32
+}
+
+// The digits of `index` in `base`, mirrored about the radix point.
+fn radical_inverse(base: u32, mut index: u32) -> f32 {
+    let inv_base = 1.0 / base as f64;
+    let mut reversed = 0u64;
+    let mut inv_base_n = 1.0;
+    while index > 0 {
+        let next = index / base;
+        reversed = reversed * base as u64 + (index - next * base) as u64;
+        inv_base_n *= inv_base;
+        index = next;
+    }
+    ((reversed as f64 * inv_base_n) as f32).min(ONE_MINUS_EPSILON)
+}
+
+// Element `i` of a random permutation of [0, n) chosen by `seed`, without storing the
+// permutation (Kensler, "Correlated Multi-Jittered Sampling", 2013).
+fn permutation_element(mut i: u32, n: u32, seed: u32) -> u32 {
+    let mut w = n - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= seed >> 16;
+        i ^= (i & w) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= seed >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < n {
+            break;
+        }
+    }
+    (i.wrapping_add(seed)) % n
+}
+
+// Point `index` of the first (van der Corput) or second Sobol dimension, as 32 bits of a
+// fraction.
+fn sobol(mut index: u32, dimension: usize) -> u32 {
+    if dimension == 0 {
+        return index.reverse_bits();
+    }
+    let mut result = 0;
+    let mut v = 1u32 << 31;
+    while index != 0 {
+        if index & 1 != 0 {
+            result ^= v;
+        }
+        index >>= 1;
+        v ^= v >> 1;
+    }
+    result
+}
+
+// Owen scrambling of the bits of `x`, read as a fraction: a random permutation of each
+// half-interval's halves at every level, chosen by `seed` (Laine-Karras hash on reversed bits).
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    let mut x = x.reverse_bits();
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+    x.reverse_bits()
+}
+
+// Picks one of `n` items with `u`, returning its index and the position of `u` within the
+// item's share of [0, 1), rescaled to [0, 1) so the value can be used again.
+pub fn pick(u: f32, n: usize) -> (usize, f32) {
+    let scaled = u * n as f32;
+    let index = (scaled as usize).min(n.max(1) - 1);
+    (index, (scaled - index as f32).clamp(0.0, ONE_MINUS_EPSILON))
+}
+
+// Maps a point of the unit square onto the unit disk, keeping neighboring points close
+// (Shirley-Chiu concentric mapping), so well-spread samples stay well spread.
+pub fn concentric_disk((u, v): (f32, f32)) -> (f32, f32) {
+    let (x, y) = (2.0 * u - 1.0, 2.0 * v - 1.0);
+    if x == 0.0 && y == 0.0 {
+        return (0.0, 0.0);
+    }
+    let (r, theta) = if x.abs() > y.abs() {
+        (x, PI / 4.0 * (y / x))
+    } else {
+        (y, PI / 2.0 - PI / 4.0 * (x / y))
+    };
+    (r * theta.cos(), r * theta.sin())
+}
+
+// Maps a point of the unit square to a point of the unit sphere, preserving area.
+pub fn uniform_sphere((u, v): (f32, f32)) -> Vec3 {
+    let z = 1.0 - 2.0 * u;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * v;
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        concentric_disk, permutation_element, pick, uniform_sphere, IndependentSampler, Sampler,
+        SamplerKind, StratifiedSampler, SAMPLERS,
+    };
+    use crate::rtweekend::seed_rng;
+
+    #[test]
+    fn test_independent_sampler_range() {
+        let mut sampler = IndependentSampler;
+        for _ in 0..1000 {
+            let (u, v) = sampler.get_2d();
+            assert!((0.0..1.0).contains(&u) && (0.0..1.0).contains(&v));
+        }
+    }
+
+    #[test]
+    fn test_concentric_disk() {
+        assert_eq!(concentric_disk((0.5, 0.5)), (0.0, 0.0));
+        let (x, y) = concentric_disk((1.0, 0.5));
+        assert!((x - 1.0).abs() < 1e-6 && y.abs() < 1e-6);
+        let (x, y) = concentric_disk((0.5, 0.0));
+        assert!(x.abs() < 1e-6 && (y + 1.0).abs() < 1e-6);
+        for i in 0..=20 {
+            for j in 0..=20 {
+                let (x, y) = concentric_disk((i as f32 / 20.0, j as f32 / 20.0));
+                assert!(x * x + y * y <= 1.0 + 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn test_uniform_sphere() {
+        for (u, v) in [(0.0, 0.0), (0.3, 0.7), (0.999, 0.5)] {
+            assert!((uniform_sphere((u, v)).length() - 1.0).abs() < 1e-5);
+        }
+        assert_eq!(uniform_sphere((0.0, 0.0)).z, 1.0);
+    }
+
+    #[test]
+    fn test_pick() {
+        assert_eq!(pick(0.0, 4), (0, 0.0));
+        assert_eq!(pick(0.625, 4), (2, 0.5));
+        let (index, u) = pick(0.99999994, 4);
+        assert_eq!(index, 3);
+        assert!(u < 1.0);
+        assert_eq!(pick(0.7, 1), (0, 0.7));
+    }
+
+    #[test]
+    fn test_permutation_element() {
+        for n in [1, 5, 16, 100] {
+            for seed in [0, 7, 0xdead_beef] {
+                let mut seen: Vec<u32> = (0..n).map(|i| permutation_element(i, n, seed)).collect();
+                seen.sort_unstable();
+                assert_eq!(seen, (0..n).collect::<Vec<_>>());
+            }
+        }
+    }
+
+    // The first `n` samples of `kind` in dimensions 0 and (1, 2) of a pixel.
+    fn pixel_samples(kind: SamplerKind, n: u32, pixel: (i32, i32)) -> Vec<(f32, (f32, f32))> {
+        let mut sampler = kind.create(n, 5);
+        (0..n)
+            .map(|i| {
+                sampler.start_pixel_sample(pixel, i);
+                (sampler.get_1d(), sampler.get_2d())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_samplers_are_deterministic_and_in_range() {
+        for name in SAMPLERS {
+            let kind: SamplerKind = name.parse().unwrap();
+            let samples = pixel_samples(kind, 64, (3, 9));
+            for &(u, (v, w)) in &samples {
+                for x in [u, v, w] {
+                    assert!((0.0..1.0).contains(&x), "{}: {}", name, x);
+                }
+            }
+            if kind != SamplerKind::Independent {
+                assert_eq!(samples, pixel_samples(kind, 64, (3, 9)), "{}", name);
+                assert_ne!(samples, pixel_samples(kind, 64, (4, 9)), "{}", name);
+            }
+        }
+        assert_eq!("Sobol".parse::<SamplerKind>(), Ok(SamplerKind::Sobol));
+        assert!("blue-noise".parse::<SamplerKind>().is_err());
+    }
+
+    #[test]
+    fn test_stratified_fills_every_stratum() {
+        let samples = pixel_samples(SamplerKind::Stratified, 16, (0, 0));
+        let mut strata_1d: Vec<usize> = samples.iter().map(|s| (s.0 * 16.0) as usize).collect();
+        strata_1d.sort_unstable();
+        assert_eq!(strata_1d, (0..16).collect::<Vec<_>>());
+        let mut cells: Vec<usize> = samples
+            .iter()
+            .map(|&(_, (u, v))| (v * 4.0) as usize * 4 + (u * 4.0) as usize)
+            .collect();
+        cells.sort_unstable();
+        assert_eq!(cells, (0..16).collect::<Vec<_>>());
+
+        // Sample counts without a square root still get a grid.
+        let sampler = StratifiedSampler::new(12, 0);
+        assert_eq!((sampler.x_strata, sampler.y_strata), (3, 4));
+    }
+
+    #[test]
+    fn test_low_discrepancy_sequences() {
+        // The first 8 Halton values of a dimension land one per eighth, rotation or not.
+        let samples = pixel_samples(SamplerKind::Halton, 8, (1, 2));
+        let mut eighths: Vec<usize> = samples.iter().map(|s| (s.0 * 8.0) as usize).collect();
+        eighths.sort_unstable();
+        assert_eq!(eighths, (0..8).collect::<Vec<_>>());
+
+        // Scrambled Sobol points are a (0, 4, 2)-net: every box of area 1/16 with power of two
+        // sides holds exactly one of the first 16.
+        let samples = pixel_samples(SamplerKind::Sobol, 16, (1, 2));
+        for log_x in 0..=4 {
+            let (nx, ny) = (1 << log_x, 16 >> log_x);
+            let mut cells: Vec<usize> = samples
+                .iter()
+                .map(|&(_, (u, v))| (v * ny as f32) as usize * nx + (u * nx as f32) as usize)
+                .collect();
+            cells.sort_unstable();
+            assert_eq!(cells, (0..16).collect::<Vec<_>>(), "{} x {}", nx, ny);
+        }
+    }
+
+    #[test]
+    fn test_well_spread_samples_converge_faster() {
+        // Integrate u * v over the unit square (exactly 1/4) with 64 samples in many pixels.
+        seed_rng(11);
+        let mean_error = |kind: SamplerKind| {
+            let pixels = 400;
+            let total: f32 = (0..pixels)
+                .map(|x| {
+                    let samples = pixel_samples(kind, 64, (x, 0));
+                    let estimate: f32 = samples.iter().map(|&(_, (u, v))| u * v).sum();
+                    (estimate / 64.0 - 0.25).abs()
+                })
+                .sum();
+            total / pixels as f32
+        };
+        let independent = mean_error(SamplerKind::Independent);
+        for kind in [
+            SamplerKind::Stratified,
+            SamplerKind::Halton,
+            SamplerKind::Sobol,
+        ] {
+            let error = mean_error(kind);
+            assert!(
+                error < independent / 2.0,
+                "{:?}: {} vs {}",
+                kind,
+                error,
+                independent
+            );
+        }
+    }
+}
@@ -0,0 +1,1862 @@
+// TOML scene description: camera settings, named textures and materials and a list of objects.
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    error::Error,
+    fmt, fs, io,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use serde::{de::DeserializeOwned, Deserialize};
+use toml::Spanned;
+
+use crate::{
+    aabb::Aabb,
+    bvh::BvhNode,
+    camera::{Background, Camera},
+    integrator,
+    material::{
+        Dielectric, DiffuseLight, HenyeyGreenstein, Isotropic, Lambertian, Material, Metal,
+        VolumeMaterial,
+    },
+    medium::ConstantMedium,
+    mesh::Triangle,
+    obj::{load_obj, ObjError},
+    quad::{BoxShape, Disk, Quad},
+    ray::{Hittable, HittableList, Sphere},
+    sampler::SamplerKind,
+    texture::{
+        CheckerTexture, Filter, GridTexture, ImageTexture, MarbleTexture, NoiseTexture, SolidColor,
+        Texture, WoodTexture, WorleyMetric, WorleyTexture, WrapMode,
+    },
+    tonemap::{ToneMap, Transfer},
+    transform::{AnimatedTransform, Instance, Mat4, Motion, Transform},
+    vec3::{Color, Vec3},
+    volume::{GridEncoding, GridError, HeterogeneousVolume, NoiseDensity, VoxelGrid},
+};
+
+pub struct Scene {
+    pub camera: Camera,
+    pub world: Arc<dyn Hittable>,
+    // The emitters in `world` that integrators sample directly.
+    pub lights: HittableList,
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    Mesh {
+        path: PathBuf,
+        line: usize,
+        source: ObjError,
+    },
+    Texture {
+        path: PathBuf,
+        line: usize,
+        file: PathBuf,
+        source: image::ImageError,
+    },
+    Grid {
+        path: PathBuf,
+        line: usize,
+        source: GridError,
+    },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io { path, source } => {
+                write!(f, "failed to read {}: {}", path.display(), source)
+            }
+            SceneError::Parse {
+                path,
+                line,
+                column,
+                message,
+            } => write!(f, "{}:{}:{}: {}", path.display(), line, column, message),
+            SceneError::Mesh { path, line, source } => {
+                write!(
+                    f,
+                    "{}:{}: failed to load mesh: {}",
+                    path.display(),
+                    line,
+                    source
+                )
+            }
+            SceneError::Texture {
+                path,
+                line,
+                file,
+                source,
+            } => write!(
+                f,
+                "{}:{}: failed to load texture {}: {}",
+                path.display(),
+                line,
+                file.display(),
+                source
+            ),
+            SceneError::Grid { path, line, source } => {
+                write!(
+                    f,
+                    "{}:{}: failed to load voxel grid: {}",
+                    path.display(),
+                    line,
+                    source
+                )
+            }
+        }
+    }
+}
+
+impl Error for SceneError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SceneError::Io { source, .. } => Some(source),
+            SceneError::Mesh { source, .. } => Some(source),
+            SceneError::Texture { source, .. } => Some(source),
+            SceneError::Grid { source, .. } => Some(source),
+            SceneError::Parse { .. } => None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneDesc {
+    camera: Option<Spanned<CameraDesc>>,
+    #[serde(default)]
+    textures: BTreeMap<String, Spanned<TextureDesc>>,
+    #[serde(default)]
+    materials: BTreeMap<String, Spanned<MaterialDesc>>,
+    #[serde(default)]
+    objects: Vec<Spanned<ObjectDesc>>,
+}
+
+// The tables of a scene file before their contents are checked, to find which key a
+// deserialization error is about.
+#[derive(Deserialize)]
+struct RawSceneDesc {
+    #[serde(default)]
+    textures: BTreeMap<String, Spanned<toml::Table>>,
+    #[serde(default)]
+    materials: BTreeMap<String, Spanned<toml::Table>>,
+    #[serde(default)]
+    objects: Vec<Spanned<toml::Table>>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct CameraDesc {
+    aspect_ratio: Option<f32>,
+    image_width: Option<i32>,
+    samples_per_pixel: Option<i32>,
+    sampler: Option<String>,
+    // Relative error at which a pixel stops sampling, and the samples it takes before that.
+    adaptive_threshold: Option<f32>,
+    min_samples: Option<i32>,
+    max_depth: Option<i32>,
+    roulette_depth: Option<i32>,
+    integrator: Option<String>,
+    seed: Option<u64>,
+    vfov: Option<f32>,
+    lookfrom: Option<[f32; 3]>,
+    lookat: Option<[f32; 3]>,
+    vup: Option<[f32; 3]>,
+    defocus_angle: Option<f32>,
+    focus_dist: Option<f32>,
+    // Shutter interval within the [0, 1] span over which objects move.
+    shutter_open: Option<f32>,
+    shutter_close: Option<f32>,
+    background: Option<BackgroundDesc>,
+    exposure: Option<f32>,
+    tone_map: Option<String>,
+    transfer: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BackgroundDesc {
+    Solid([f32; 3]),
+    Named(String),
+    Gradient { bottom: [f32; 3], top: [f32; 3] },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum TextureDesc {
+    Solid {
+        color: [f32; 3],
+    },
+    Checker {
+        scale: f32,
+        even: [f32; 3],
+        odd: [f32; 3],
+    },
+    // Image file, resolved relative to the scene file.
+    Image {
+        file: String,
+        wrap: Option<String>,
+        filter: Option<String>,
+    },
+    // Procedural textures; `scale` is the feature frequency and `seed` picks the pattern.
+    Noise {
+        scale: f32,
+        #[serde(default)]
+        seed: u64,
+        octaves: Option<u32>,
+        color: Option<[f32; 3]>,
+    },
+    Marble {
+        scale: f32,
+        #[serde(default)]
+        seed: u64,
+        turbulence: Option<f32>,
+        base: Option<[f32; 3]>,
+        vein: Option<[f32; 3]>,
+    },
+    Wood {
+        scale: f32,
+        #[serde(default)]
+        seed: u64,
+        turbulence: Option<f32>,
+        light: Option<[f32; 3]>,
+        dark: Option<[f32; 3]>,
+    },
+    Worley {
+        scale: f32,
+        #[serde(default)]
+        seed: u64,
+        metric: Option<String>,
+        cell: Option<[f32; 3]>,
+        edge: Option<[f32; 3]>,
+    },
+    // Voxel grid looked up at the hit point. `.vol` files carry their own bounds, which `min`
+    // and `max` override; any other file holds raw values and needs `resolution`, `min` and
+    // `max`, with `encoding` "u8" (the default) or "f32".
+    Grid {
+        file: String,
+        resolution: Option<[usize; 3]>,
+        encoding: Option<String>,
+        min: Option<[f32; 3]>,
+        max: Option<[f32; 3]>,
+        tint: Option<[f32; 3]>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NoiseDensityDesc {
+    scale: f32,
+    #[serde(default)]
+    seed: u64,
+    octaves: Option<u32>,
+    threshold: Option<f32>,
+}
+
+// Surfaces take either a constant color or the name of a texture.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialDesc {
+    Lambertian {
+        albedo: Option<[f32; 3]>,
+        texture: Option<String>,
+    },
+    Metal {
+        albedo: [f32; 3],
+        #[serde(default)]
+        fuzz: f32,
+    },
+    Dielectric {
+        refraction_index: f32,
+    },
+    DiffuseLight {
+        emit: Option<[f32; 3]>,
+        texture: Option<String>,
+    },
+    // Phase functions, for objects filled with a participating medium.
+    Isotropic {
+        albedo: Option<[f32; 3]>,
+        texture: Option<String>,
+    },
+    HenyeyGreenstein {
+        albedo: Option<[f32; 3]>,
+        texture: Option<String>,
+        g: f32,
+    },
+}
+
+// A shape, optionally placed by a list of transform steps applied in order, then moved by
+// motion steps that blend from `from` at time 0 to `to` at time 1. Given a `density`, the
+// closed shape instead bounds a uniform medium that scatters by the shape's material.
+#[derive(Deserialize)]
+struct ObjectDesc {
+    #[serde(flatten)]
+    shape: ShapeDesc,
+    #[serde(default)]
+    transform: Vec<TransformDesc>,
+    #[serde(default)]
+    motion: Vec<MotionDesc>,
+    density: Option<f32>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ShapeDesc {
+    // A sphere given `center_end` moves from `center` at time 0 to `center_end` at time 1.
+    Sphere {
+        center: [f32; 3],
+        center_end: Option<[f32; 3]>,
+        radius: f32,
+        material: String,
+    },
+    // Parallelogram with corner `q` and edges `u` and `v`.
+    Quad {
+        q: [f32; 3],
+        u: [f32; 3],
+        v: [f32; 3],
+        material: String,
+    },
+    Disk {
+        center: [f32; 3],
+        normal: [f32; 3],
+        radius: f32,
+        material: String,
+    },
+    Triangle {
+        vertices: [[f32; 3]; 3],
+        material: String,
+    },
+    // Axis-aligned box between two opposite corners.
+    #[serde(rename = "box")]
+    BoxShape {
+        min: [f32; 3],
+        max: [f32; 3],
+        material: String,
+    },
+    // Wavefront OBJ file, resolved relative to the scene file; materials come from its MTL.
+    // Objects naming the same file share one copy of the mesh.
+    Mesh {
+        file: String,
+    },
+    // Heterogeneous medium whose density comes from a voxel grid `file`, read like a grid
+    // texture, or from `noise` within `min` and `max`. The extinction coefficient is
+    // `extinction` times the density, per unit of distance in the object's own space.
+    // Collisions scatter with probability `albedo` and otherwise absorb and glow with
+    // `emission`; either can instead name a texture with `albedo_texture` or
+    // `emission_texture`.
+    Volume {
+        file: Option<String>,
+        resolution: Option<[usize; 3]>,
+        encoding: Option<String>,
+        noise: Option<NoiseDensityDesc>,
+        min: Option<[f32; 3]>,
+        max: Option<[f32; 3]>,
+        extinction: f32,
+        albedo: Option<[f32; 3]>,
+        albedo_texture: Option<String>,
+        emission: Option<[f32; 3]>,
+        emission_texture: Option<String>,
+        #[serde(default)]
+        g: f32,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum TransformDesc {
+    Translate([f32; 3]),
+    // Counterclockwise rotation in degrees about `axis` through the origin.
+    Rotate { axis: [f32; 3], angle: f32 },
+    Scale(ScaleDesc),
+    // Row-major affine matrix; the last row must be [0, 0, 0, 1].
+    Matrix([[f32; 4]; 4]),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum MotionDesc {
+    Translate { from: [f32; 3], to: [f32; 3] },
+    Rotate { axis: [f32; 3], from: f32, to: f32 },
+    Scale { from: ScaleDesc, to: ScaleDesc },
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ScaleDesc {
+    Uniform(f32),
+    Axes([f32; 3]),
+}
+
+impl ShapeDesc {
+    fn material(&self) -> Option<&str> {
+        match self {
+            ShapeDesc::Sphere { material, .. }
+            | ShapeDesc::Quad { material, .. }
+            | ShapeDesc::Disk { material, .. }
+            | ShapeDesc::Triangle { material, .. }
+            | ShapeDesc::BoxShape { material, .. } => Some(material),
+            ShapeDesc::Mesh { .. } | ShapeDesc::Volume { .. } => None,
+        }
+    }
+}
+
+fn vec3(v: [f32; 3]) -> Vec3 {
+    Vec3::new(v[0], v[1], v[2])
+}
+
+impl ScaleDesc {
+    fn factors(&self) -> Vec3 {
+        match self {
+            ScaleDesc::Uniform(f) => Vec3::new(*f, *f, *f),
+            ScaleDesc::Axes(f) => vec3(*f),
+        }
+    }
+}
+
+// Builds the camera, materials and objects described by a scene file.
+struct Loader<'a> {
+    source: &'a str,
+    path: &'a Path,
+    textures: BTreeMap<String, Arc<dyn Texture>>,
+    materials: BTreeMap<String, Arc<dyn Material>>,
+    // Names of the `diffuse_light` materials; objects made of them are sampled as lights.
+    emitters: BTreeSet<String>,
+    meshes: HashMap<PathBuf, Arc<dyn Hittable>>,
+}
+
+impl Loader<'_> {
+    // 1-based line and column of a byte offset in the source.
+    fn location(&self, offset: usize) -> (usize, usize) {
+        let before = &self.source[..offset.min(self.source.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+        (line, column)
+    }
+
+    fn error(&self, span: Range<usize>, message: impl Into<String>) -> SceneError {
+        let (line, column) = self.location(span.start);
+        SceneError::Parse {
+            path: self.path.to_path_buf(),
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+
+    // Locates a deserialization error at the key it is about and names that key. Tables
+    // tagged with a `type` are read whole before their fields, so errors in their values
+    // carry only the table's span; there, the key is found by trying the table without it.
+    fn parse_error(&self, e: &toml::de::Error) -> SceneError {
+        let span = e.span().unwrap_or(0..0);
+        let message = e.message().trim_end();
+        let tagged = match toml::from_str::<RawSceneDesc>(self.source) {
+            Ok(raw) => {
+                let at_span = |table: &&Spanned<toml::Table>| table.span() == span;
+                if let Some(table) = raw.textures.values().find(at_span) {
+                    Some(culprit::<TextureDesc>(table.get_ref()))
+                } else if let Some(table) = raw.materials.values().find(at_span) {
+                    Some(culprit::<MaterialDesc>(table.get_ref()))
+                } else {
+                    let table = raw.objects.iter().find(at_span);
+                    table.map(|table| culprit::<ObjectDesc>(table.get_ref()))
+                }
+            }
+            Err(_) => None,
+        };
+        let (span, key) = match tagged {
+            Some(Some(key)) => (self.field_span(&span, &key), key),
+            Some(None) => return self.error(span, message),
+            None => match self.key_before(span.start) {
+                Some(key) => (span.clone(), key.to_string()),
+                None => return self.error(span, message),
+            },
+        };
+        if message.contains(&format!("`{}`", key)) {
+            self.error(span, message)
+        } else {
+            self.error(span, format!("`{}`: {}", key, message))
+        }
+    }
+
+    // Error about `field` of the table at `span`, located at the field's key.
+    fn field_error(
+        &self,
+        span: &Range<usize>,
+        field: &str,
+        message: impl fmt::Display,
+    ) -> SceneError {
+        self.error(
+            self.field_span(span, field),
+            format!("`{}`: {}", field, message),
+        )
+    }
+
+    fn check(
+        &self,
+        span: &Range<usize>,
+        ok: bool,
+        field: &str,
+        requirement: &str,
+    ) -> Result<(), SceneError> {
+        if ok {
+            Ok(())
+        } else {
+            let message = format!("`{}` {}", field, requirement);
+            Err(self.error(self.field_span(span, field), message))
+        }
+    }
+
+    // Span of the key of `field` in the table at `span`, or the whole table if the key isn't
+    // written there. Dotted fields name keys of inline tables within the table.
+    fn field_span(&self, span: &Range<usize>, field: &str) -> Range<usize> {
+        let mut start = span.start;
+        let mut end = span.start;
+        for key in field.split('.') {
+            match self.find_key(start..span.end, key) {
+                Some(offset) => (start, end) = (offset, offset + key.len()),
+                None => return span.clone(),
+            }
+        }
+        start..end
+    }
+
+    // Offset of the first place in `range` where `key` is written as a key: at the start of
+    // a line or after `{`, `,` or the `.` of a dotted key, and followed by `=` or `.`.
+    fn find_key(&self, range: Range<usize>, key: &str) -> Option<usize> {
+        let text = &self.source[range.clone()];
+        text.match_indices(key)
+            .map(|(i, _)| i)
+            .find(|&i| {
+                let before = text[..i].trim_end_matches([' ', '\t']);
+                let after = text[i + key.len()..].trim_start_matches([' ', '\t']);
+                matches!(before.chars().last(), None | Some('\n' | '{' | ',' | '.'))
+                    && (after.starts_with('=') || after.starts_with('.'))
+            })
+            .map(|i| range.start + i)
+    }
+
+    // The key on the line of `offset` whose value starts before it.
+    fn key_before(&self, offset: usize) -> Option<&str> {
+        let before = &self.source[..offset.min(self.source.len())];
+        let line = &before[before.rfind('\n').map_or(0, |i| i + 1)..];
+        let key = line[..line.rfind('=')?].trim_end();
+        let start = key
+            .rfind(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+            .map_or(0, |i| i + 1);
+        Some(&key[start..]).filter(|key| !key.is_empty())
+    }
+
+    fn color(&self, span: &Range<usize>, field: &str, c: [f32; 3]) -> Result<Color, SceneError> {
+        let ok = c.iter().all(|v| v.is_finite() && *v >= 0.0);
+        self.check(span, ok, field, "must have finite, non-negative components")?;
+        Ok(vec3(c))
+    }
+
+    fn camera(&self, desc: &Spanned<CameraDesc>) -> Result<Camera, SceneError> {
+        let span = desc.span();
+        let desc = desc.get_ref();
+        let mut camera = Camera::default();
+
+        if let Some(aspect_ratio) = desc.aspect_ratio {
+            self.check(
+                &span,
+                aspect_ratio > 0.0,
+                "aspect_ratio",
+                "must be positive",
+            )?;
+            camera.aspect_ratio = aspect_ratio;
+        }
+        if let Some(width) = desc.image_width {
+            self.check(&span, width > 0, "image_width", "must be positive")?;
+            camera.img_width = width;
+        }
+        if let Some(spp) = desc.samples_per_pixel {
+            self.check(&span, spp > 0, "samples_per_pixel", "must be positive")?;
+            camera.samples_per_pixel = spp;
+        }
+        if let Some(threshold) = desc.adaptive_threshold {
+            self.check(
+                &span,
+                threshold >= 0.0,
+                "adaptive_threshold",
+                "must not be negative",
+            )?;
+            camera.adaptive_threshold = threshold;
+        }
+        if let Some(min_samples) = desc.min_samples {
+            self.check(&span, min_samples > 0, "min_samples", "must be positive")?;
+            camera.min_samples = min_samples;
+        }
+        if let Some(sampler) = &desc.sampler {
+            camera.sampler = sampler
+                .parse::<SamplerKind>()
+                .map_err(|e| self.field_error(&span, "sampler", e))?;
+        }
+        if let Some(depth) = desc.max_depth {
+            self.check(&span, depth > 0, "max_depth", "must be positive")?;
+            camera.max_depth = depth;
+        }
+        if let Some(depth) = desc.roulette_depth {
+            self.check(&span, depth >= 0, "roulette_depth", "must not be negative")?;
+            camera.roulette_depth = depth;
+        }
+        if let Some(seed) = desc.seed {
+            camera.seed = seed;
+        }
+        if let Some(name) = &desc.integrator {
+            camera.integrator =
+                integrator::by_name(name).map_err(|e| self.field_error(&span, "integrator", e))?;
+        }
+        if let Some(vfov) = desc.vfov {
+            let ok = vfov > 0.0 && vfov < 180.0;
+            self.check(&span, ok, "vfov", "must be between 0 and 180 degrees")?;
+            camera.vfov = vfov;
+        }
+        if let Some(lookfrom) = desc.lookfrom {
+            camera.lookfrom = vec3(lookfrom);
+        }
+        if let Some(lookat) = desc.lookat {
+            camera.lookat = vec3(lookat);
+        }
+        if let Some(vup) = desc.vup {
+            self.check(&span, !vec3(vup).near_zero(), "vup", "must not be zero")?;
+            camera.vup = vec3(vup);
+        }
+        let ok = !(camera.lookfrom - camera.lookat).near_zero();
+        self.check(&span, ok, "lookat", "must differ from `lookfrom`")?;
+        let ok = !camera.vup_is_degenerate();
+        self.check(
+            &span,
+            ok,
+            "vup",
+            "must not be parallel to the view direction",
+        )?;
+        if let Some(angle) = desc.defocus_angle {
+            self.check(&span, angle >= 0.0, "defocus_angle", "must not be negative")?;
+            camera.defocus_angle = angle;
+        }
+        if let Some(dist) = desc.focus_dist {
+            self.check(&span, dist > 0.0, "focus_dist", "must be positive")?;
+            camera.focus_dist = dist;
+        }
+        if let Some(open) = desc.shutter_open {
+            let ok = (0.0..=1.0).contains(&open);
+            self.check(&span, ok, "shutter_open", "must be between 0 and 1")?;
+            camera.shutter_open = open;
+        }
+        if let Some(close) = desc.shutter_close {
+            let ok = (0.0..=1.0).contains(&close);
+            self.check(&span, ok, "shutter_close", "must be between 0 and 1")?;
+            camera.shutter_close = close;
+        }
+        let ok = camera.shutter_close >= camera.shutter_open;
+        self.check(
+            &span,
+            ok,
+            "shutter_close",
+            "must not be before `shutter_open`",
+        )?;
+
+        if let Some(background) = &desc.background {
+            camera.background = match background {
+                BackgroundDesc::Solid(c) => {
+                    Background::Solid(self.color(&span, "background", *c)?)
+                }
+                BackgroundDesc::Gradient { bottom, top } => Background::Gradient {
+                    bottom: self.color(&span, "background.bottom", *bottom)?,
+                    top: self.color(&span, "background.top", *top)?,
+                },
+                BackgroundDesc::Named(name) if name == "sky" => Background::sky(),
+                BackgroundDesc::Named(name) => {
+                    return Err(self.error(span, format!("unknown background `{}`", name)))
+                }
+            };
+        }
+
+        if let Some(exposure) = desc.exposure {
+            self.check(&span, exposure.is_finite(), "exposure", "must be finite")?;
+            camera.display.exposure = exposure;
+        }
+        if let Some(tone_map) = &desc.tone_map {
+            camera.display.tone_map = tone_map
+                .parse::<ToneMap>()
+                .map_err(|e| self.field_error(&span, "tone_map", e))?;
+        }
+        if let Some(transfer) = &desc.transfer {
+            camera.display.transfer = transfer
+                .parse::<Transfer>()
+                .map_err(|e| self.field_error(&span, "transfer", e))?;
+        }
+
+        Ok(camera)
+    }
+
+    // Resolves a path in the scene file relative to the scene file's directory.
+    fn resolve(&self, file: &str) -> PathBuf {
+        let base_dir = self.path.parent().unwrap_or_else(|| Path::new(""));
+        base_dir.join(file)
+    }
+
+    // Overwrites `target` with the optional `field` color.
+    fn set_color(
+        &self,
+        span: &Range<usize>,
+        field: &str,
+        c: &Option<[f32; 3]>,
+        target: &mut Color,
+    ) -> Result<(), SceneError> {
+        if let Some(c) = c {
+            *target = self.color(span, field, *c)?;
+        }
+        Ok(())
+    }
+
+    fn texture(&self, desc: &Spanned<TextureDesc>) -> Result<Arc<dyn Texture>, SceneError> {
+        let span = desc.span();
+        let desc = desc.get_ref();
+        let scale = match desc {
+            TextureDesc::Noise { scale, .. }
+            | TextureDesc::Marble { scale, .. }
+            | TextureDesc::Wood { scale, .. }
+            | TextureDesc::Worley { scale, .. } => Some(*scale),
+            _ => None,
+        };
+        if let Some(scale) = scale {
+            let ok = scale.is_finite() && scale > 0.0;
+            self.check(&span, ok, "scale", "must be positive")?;
+        }
+
+        Ok(match desc {
+            TextureDesc::Solid { color } => {
+                Arc::new(SolidColor::new(self.color(&span, "color", *color)?))
+            }
+            TextureDesc::Checker { scale, even, odd } => {
+                self.check(&span, *scale > 0.0, "scale", "must be positive")?;
+                Arc::new(CheckerTexture::from_colors(
+                    *scale,
+                    self.color(&span, "even", *even)?,
+                    self.color(&span, "odd", *odd)?,
+                ))
+            }
+            TextureDesc::Image { file, wrap, filter } => {
+                let file = self.resolve(file);
+                let mut texture =
+                    ImageTexture::load(&file).map_err(|source| SceneError::Texture {
+                        path: self.path.to_path_buf(),
+                        line: self.location(span.start).0,
+                        file,
+                        source,
+                    })?;
+                if let Some(wrap) = wrap {
+                    texture.wrap = wrap
+                        .parse::<WrapMode>()
+                        .map_err(|e| self.field_error(&span, "wrap", e))?;
+                }
+                if let Some(filter) = filter {
+                    texture.filter = filter
+                        .parse::<Filter>()
+                        .map_err(|e| self.field_error(&span, "filter", e))?;
+                }
+                Arc::new(texture)
+            }
+            TextureDesc::Noise {
+                scale,
+                seed,
+                octaves,
+                color,
+            } => {
+                let mut texture = NoiseTexture::new(*seed, *scale);
+                if let Some(octaves) = *octaves {
+                    let ok = (1..=16).contains(&octaves);
+                    self.check(&span, ok, "octaves", "must be between 1 and 16")?;
+                    texture.octaves = octaves;
+                }
+                self.set_color(&span, "color", color, &mut texture.color)?;
+                Arc::new(texture)
+            }
+            TextureDesc::Marble {
+                scale,
+                seed,
+                turbulence,
+                base,
+                vein,
+            } => {
+                let mut texture = MarbleTexture::new(*seed, *scale);
+                if let Some(turbulence) = *turbulence {
+                    texture.turbulence = turbulence;
+                }
+                self.set_color(&span, "base", base, &mut texture.base)?;
+                self.set_color(&span, "vein", vein, &mut texture.vein)?;
+                Arc::new(texture)
+            }
+            TextureDesc::Wood {
+                scale,
+                seed,
+                turbulence,
+                light,
+                dark,
+            } => {
+                let mut texture = WoodTexture::new(*seed, *scale);
+                if let Some(turbulence) = *turbulence {
+                    texture.turbulence = turbulence;
+                }
+                self.set_color(&span, "light", light, &mut texture.light)?;
+                self.set_color(&span, "dark", dark, &mut texture.dark)?;
+                Arc::new(texture)
+            }
+            TextureDesc::Worley {
+                scale,
+                seed,
+                metric,
+                cell,
+                edge,
+            } => {
+                let mut texture = WorleyTexture::new(*seed, *scale);
+                if let Some(metric) = metric {
+                    texture.metric = metric
+                        .parse::<WorleyMetric>()
+                        .map_err(|e| self.field_error(&span, "metric", e))?;
+                }
+                self.set_color(&span, "cell", cell, &mut texture.cell)?;
+                self.set_color(&span, "edge", edge, &mut texture.edge)?;
+                Arc::new(texture)
+            }
+            TextureDesc::Grid {
+                file,
+                resolution,
+                encoding,
+                min,
+                max,
+                tint,
+            } => {
+                let grid = self.grid(&span, file, *resolution, encoding.as_ref(), (*min, *max))?;
+                let mut texture = GridTexture::new(Arc::new(grid));
+                self.set_color(&span, "tint", tint, &mut texture.tint)?;
+                Arc::new(texture)
+            }
+        })
+    }
+
+    // Loads a voxel grid file; see `TextureDesc::Grid`.
+    fn grid(
+        &self,
+        span: &Range<usize>,
+        file: &str,
+        resolution: Option<[usize; 3]>,
+        encoding: Option<&String>,
+        bounds: (Option<[f32; 3]>, Option<[f32; 3]>),
+    ) -> Result<VoxelGrid, SceneError> {
+        let bounds = match bounds {
+            (Some(min), Some(max)) => {
+                let ok =
+                    (0..3).all(|i| min[i].is_finite() && max[i].is_finite() && min[i] < max[i]);
+                self.check(
+                    span,
+                    ok,
+                    "min",
+                    "must be finite and below `max` on every axis",
+                )?;
+                Some(Aabb::from_points(vec3(min), vec3(max)))
+            }
+            (None, None) => None,
+            _ => return Err(self.error(span.clone(), "`min` and `max` must be given together")),
+        };
+        let path = self.resolve(file);
+        let is_vol = path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("vol"));
+        let grid = if is_vol {
+            VoxelGrid::load_vol(&path).map(|mut grid| {
+                if let Some(bounds) = bounds {
+                    grid.set_bounds(bounds);
+                }
+                grid
+            })
+        } else {
+            let (Some(resolution), Some(bounds)) = (resolution, bounds) else {
+                return Err(
+                    self.error(span.clone(), "raw grids need `resolution`, `min` and `max`")
+                );
+            };
+            let ok = resolution.iter().all(|&n| n > 0);
+            self.check(span, ok, "resolution", "must be positive")?;
+            let encoding = match encoding {
+                Some(encoding) => encoding
+                    .parse::<GridEncoding>()
+                    .map_err(|e| self.field_error(span, "encoding", e))?,
+                None => GridEncoding::U8,
+            };
+            VoxelGrid::load_raw(&path, resolution, encoding, bounds)
+        };
+        grid.map_err(|source| SceneError::Grid {
+            path: self.path.to_path_buf(),
+            line: self.location(span.start).0,
+            source,
+        })
+    }
+
+    // The texture called `name`, given by the `field` key.
+    fn texture_ref(
+        &self,
+        span: &Range<usize>,
+        field: &str,
+        name: &str,
+    ) -> Result<Arc<dyn Texture>, SceneError> {
+        self.textures.get(name).cloned().ok_or_else(|| {
+            let message = format!("unknown texture `{}`", name);
+            self.error(self.field_span(span, field), message)
+        })
+    }
+
+    // A volume channel given as a `field` color, a `<field>_texture` name, or neither for
+    // `default`.
+    fn channel(
+        &self,
+        span: &Range<usize>,
+        field: &str,
+        color: Option<[f32; 3]>,
+        texture: Option<&String>,
+        default: Color,
+    ) -> Result<Arc<dyn Texture>, SceneError> {
+        match (color, texture) {
+            (None, None) => Ok(Arc::new(SolidColor::new(default))),
+            (Some(c), None) => Ok(Arc::new(SolidColor::new(self.color(span, field, c)?))),
+            (None, Some(name)) => self.texture_ref(span, &format!("{}_texture", field), name),
+            _ => Err(self.error(
+                span.clone(),
+                format!("`{}` and `{}_texture` can't both be given", field, field),
+            )),
+        }
+    }
+
+    // The texture of a surface given either as a constant `field` color or a texture name.
+    fn surface(
+        &self,
+        span: &Range<usize>,
+        field: &str,
+        color: Option<[f32; 3]>,
+        texture: Option<&String>,
+    ) -> Result<Arc<dyn Texture>, SceneError> {
+        match (color, texture) {
+            (Some(c), None) => Ok(Arc::new(SolidColor::new(self.color(span, field, c)?))),
+            (None, Some(name)) => self.texture_ref(span, "texture", name),
+            _ => Err(self.error(
+                span.clone(),
+                format!("exactly one of `{}` and `texture` must be given", field),
+            )),
+        }
+    }
+
+    fn material(&self, desc: &Spanned<MaterialDesc>) -> Result<Arc<dyn Material>, SceneError> {
+        let span = desc.span();
+        Ok(match desc.get_ref() {
+            MaterialDesc::Lambertian { albedo, texture } => Arc::new(Lambertian::from_texture(
+                self.surface(&span, "albedo", *albedo, texture.as_ref())?,
+            )),
+            MaterialDesc::Metal { albedo, fuzz } => {
+                let ok = (0.0..=1.0).contains(fuzz);
+                self.check(&span, ok, "fuzz", "must be between 0 and 1")?;
+                Arc::new(Metal::new(self.color(&span, "albedo", *albedo)?, *fuzz))
+            }
+            MaterialDesc::Dielectric { refraction_index } => {
+                let ok = *refraction_index > 0.0;
+                self.check(&span, ok, "refraction_index", "must be positive")?;
+                Arc::new(Dielectric::new(*refraction_index))
+            }
+            MaterialDesc::DiffuseLight { emit, texture } => Arc::new(DiffuseLight::from_texture(
+                self.surface(&span, "emit", *emit, texture.as_ref())?,
+            )),
+            MaterialDesc::Isotropic { albedo, texture } => Arc::new(Isotropic::from_texture(
+                self.surface(&span, "albedo", *albedo, texture.as_ref())?,
+            )),
+            MaterialDesc::HenyeyGreenstein { albedo, texture, g } => {
+                let ok = *g > -1.0 && *g < 1.0;
+                self.check(&span, ok, "g", "must be between -1 and 1")?;
+                let tex = self.surface(&span, "albedo", *albedo, texture.as_ref())?;
+                Arc::new(HenyeyGreenstein::from_texture(tex, *g))
+            }
+        })
+    }
+
+    fn material_ref(
+        &self,
+        span: &Range<usize>,
+        name: &str,
+    ) -> Result<Arc<dyn Material>, SceneError> {
+        self.materials.get(name).cloned().ok_or_else(|| {
+            let message = format!("unknown material `{}`", name);
+            self.error(self.field_span(span, "material"), message)
+        })
+    }
+
+    fn transform(
+        &self,
+        span: &Range<usize>,
+        steps: &[TransformDesc],
+    ) -> Result<Transform, SceneError> {
+        let mut transform = Transform::identity();
+        for step in steps {
+            let next = match step {
+                TransformDesc::Translate(offset) => Transform::translate(vec3(*offset)),
+                TransformDesc::Rotate { axis, angle } => {
+                    self.check(span, !vec3(*axis).near_zero(), "axis", "must not be zero")?;
+                    Transform::rotate(vec3(*axis), *angle)
+                }
+                TransformDesc::Scale(scale) => {
+                    Transform::scale(scale.factors()).ok_or_else(|| {
+                        let message = "`scale` factors must not be zero";
+                        self.error(self.field_span(span, "scale"), message)
+                    })?
+                }
+                TransformDesc::Matrix(m) => {
+                    let ok = m[3] == [0.0, 0.0, 0.0, 1.0];
+                    self.check(span, ok, "matrix", "must have [0, 0, 0, 1] as its last row")?;
+                    Transform::new(Mat4::new(*m)).ok_or_else(|| {
+                        let message = "`matrix` must be invertible";
+                        self.error(self.field_span(span, "matrix"), message)
+                    })?
+                }
+            };
+            transform = transform.then(&next);
+        }
+        Ok(transform)
+    }
+
+    fn motion(&self, span: &Range<usize>, steps: &[MotionDesc]) -> Result<Vec<Motion>, SceneError> {
+        steps
+            .iter()
+            .map(|step| match step {
+                MotionDesc::Translate { from, to } => Ok(Motion::Translate {
+                    from: vec3(*from),
+                    to: vec3(*to),
+                }),
+                MotionDesc::Rotate { axis, from, to } => {
+                    self.check(span, !vec3(*axis).near_zero(), "axis", "must not be zero")?;
+                    Ok(Motion::Rotate {
+                        axis: vec3(*axis),
+                        from: *from,
+                        to: *to,
+                    })
+                }
+                MotionDesc::Scale { from, to } => {
+                    let (from, to) = (from.factors(), to.factors());
+                    // The factors pass through zero in between if any axis changes sign.
+                    let ok = (0..3).all(|i| from[i] * to[i] > 0.0);
+                    self.check(
+                        span,
+                        ok,
+                        "scale",
+                        "factors must keep one sign and not be zero",
+                    )?;
+                    Ok(Motion::Scale { from, to })
+                }
+            })
+            .collect()
+    }
+
+    // The mesh in `file` as a single hittable, loading it only the first time it's named.
+    fn mesh(&mut self, span: &Range<usize>, file: &str) -> Result<Arc<dyn Hittable>, SceneError> {
+        let file = self.resolve(file);
+        if let Some(mesh) = self.meshes.get(&file) {
+            return Ok(mesh.clone());
+        }
+        let mesh = load_obj(&file).map_err(|source| SceneError::Mesh {
+            path: self.path.to_path_buf(),
+            line: self.location(span.start).0,
+            source,
+        })?;
+        let mesh: Arc<dyn Hittable> = Arc::new(BvhNode::from_list(mesh.to_hittable_list()));
+        self.meshes.insert(file, mesh.clone());
+        Ok(mesh)
+    }
+
+    fn object(
+        &mut self,
+        desc: &Spanned<ObjectDesc>,
+        world: &mut HittableList,
+        lights: &mut HittableList,
+    ) -> Result<(), SceneError> {
+        let span = desc.span();
+        let desc = desc.get_ref();
+        let shape: Arc<dyn Hittable> = match &desc.shape {
+            ShapeDesc::Sphere {
+                center,
+                center_end,
+                radius,
+                material,
+            } => {
+                self.check(&span, *radius > 0.0, "radius", "must be positive")?;
+                let mat = self.material_ref(&span, material)?;
+                match center_end {
+                    Some(end) => Arc::new(Sphere::moving(vec3(*center), vec3(*end), *radius, mat)),
+                    None => Arc::new(Sphere::new(vec3(*center), *radius, mat)),
+                }
+            }
+            ShapeDesc::Quad { q, u, v, material } => {
+                let ok = !vec3(*u).cross(&vec3(*v)).near_zero();
+                self.check(&span, ok, "u", "and `v` must span a parallelogram")?;
+                let mat = self.material_ref(&span, material)?;
+                Arc::new(Quad::new(vec3(*q), vec3(*u), vec3(*v), mat))
+            }
+            ShapeDesc::Disk {
+                center,
+                normal,
+                radius,
+                material,
+            } => {
+                self.check(&span, *radius > 0.0, "radius", "must be positive")?;
+                self.check(
+                    &span,
+                    !vec3(*normal).near_zero(),
+                    "normal",
+                    "must not be zero",
+                )?;
+                let mat = self.material_ref(&span, material)?;
+                Arc::new(Disk::new(vec3(*center), vec3(*normal), *radius, mat))
+            }
+            ShapeDesc::Triangle { vertices, material } => {
+                let [a, b, c] = vertices.map(vec3);
+                let ok = !(b - a).cross(&(c - a)).near_zero();
+                self.check(&span, ok, "vertices", "must not be collinear")?;
+                let mat = self.material_ref(&span, material)?;
+                Arc::new(Triangle::from_points(a, b, c, mat))
+            }
+            ShapeDesc::BoxShape { min, max, material } => {
+                let ok = (0..3).all(|i| min[i] < max[i]);
+                self.check(&span, ok, "min", "must be below `max` on every axis")?;
+                let mat = self.material_ref(&span, material)?;
+                Arc::new(BoxShape::new(vec3(*min), vec3(*max), mat))
+            }
+            ShapeDesc::Mesh { file } => self.mesh(&span, file)?,
+            ShapeDesc::Volume {
+                file,
+                resolution,
+                encoding,
+                noise,
+                min,
+                max,
+                extinction,
+                albedo,
+                albedo_texture,
+                emission,
+                emission_texture,
+                g,
+            } => {
+                let ok = extinction.is_finite() && *extinction >= 0.0;
+                self.check(&span, ok, "extinction", "must not be negative")?;
+                self.check(
+                    &span,
+                    *g > -1.0 && *g < 1.0,
+                    "g",
+                    "must be between -1 and 1",
+                )?;
+                let albedo = self.channel(
+                    &span,
+                    "albedo",
+                    *albedo,
+                    albedo_texture.as_ref(),
+                    Color::ones(),
+                )?;
+                let emission = self.channel(
+                    &span,
+                    "emission",
+                    *emission,
+                    emission_texture.as_ref(),
+                    Color::zero(),
+                )?;
+                let mat = Arc::new(VolumeMaterial::new(albedo, emission, *g));
+
+                match (file, noise) {
+                    (Some(file), None) => {
+                        let grid =
+                            self.grid(&span, file, *resolution, encoding.as_ref(), (*min, *max))?;
+                        Arc::new(HeterogeneousVolume::from_grid(
+                            Arc::new(grid),
+                            *extinction,
+                            mat,
+                        ))
+                    }
+                    (None, Some(noise)) => {
+                        let (Some(min), Some(max)) = (min, max) else {
+                            return Err(self.error(span, "noise volumes need `min` and `max`"));
+                        };
+                        let ok = (0..3).all(|i| min[i] < max[i]);
+                        self.check(&span, ok, "min", "must be below `max` on every axis")?;
+                        let ok = noise.scale.is_finite() && noise.scale > 0.0;
+                        self.check(&span, ok, "noise.scale", "must be positive")?;
+                        let mut field = NoiseDensity::new(noise.seed, noise.scale);
+                        if let Some(octaves) = noise.octaves {
+                            let ok = (1..=16).contains(&octaves);
+                            self.check(&span, ok, "noise.octaves", "must be between 1 and 16")?;
+                            field.octaves = octaves;
+                        }
+                        if let Some(threshold) = noise.threshold {
+                            let ok = (0.0..1.0).contains(&threshold);
+                            self.check(&span, ok, "noise.threshold", "must be in [0, 1)")?;
+                            field.threshold = threshold;
+                        }
+                        let bounds = Aabb::from_points(vec3(*min), vec3(*max));
+                        Arc::new(HeterogeneousVolume::new(
+                            bounds,
+                            Arc::new(field),
+                            *extinction,
+                            mat,
+                        ))
+                    }
+                    _ => {
+                        return Err(
+                            self.error(span, "exactly one of `file` and `noise` must be given")
+                        )
+                    }
+                }
+            }
+        };
+
+        let placed: Arc<dyn Hittable> = if !desc.motion.is_empty() {
+            let base = self.transform(&span, &desc.transform)?;
+            let motions = self.motion(&span, &desc.motion)?;
+            let animated = AnimatedTransform::new(base, motions);
+            Arc::new(Instance::animated(shape, animated))
+        } else if desc.transform.is_empty() {
+            shape
+        } else {
+            let transform = self.transform(&span, &desc.transform)?;
+            Arc::new(Instance::new(shape, transform))
+        };
+
+        match desc.density {
+            // The medium wraps the placed boundary, so density is per unit of world distance.
+            Some(density) => {
+                self.check(&span, density > 0.0, "density", "must be positive")?;
+                let Some(material) = desc.shape.material() else {
+                    let message = "`density` needs a shape with a `material`";
+                    return Err(self.error(self.field_span(&span, "density"), message));
+                };
+                let phase_function = self.material_ref(&span, material)?;
+                world.add(Arc::new(ConstantMedium::new(
+                    placed,
+                    density,
+                    phase_function,
+                )));
+            }
+            None => {
+                let emitter = desc.shape.material();
+                if emitter.is_some_and(|name| self.emitters.contains(name)) {
+                    lights.add(placed.clone());
+                }
+                world.add(placed);
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn load_scene<P: AsRef<Path>>(path: P) -> Result<Scene, SceneError> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path).map_err(|source| SceneError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    parse_scene(&source, path)
+}
+
+// The key of `table` that stops it from deserializing as `T`: the one without which the error
+// changes. None when the error isn't about a value, such as a missing key.
+fn culprit<T: DeserializeOwned>(table: &toml::Table) -> Option<String> {
+    let error = |table: toml::Table| toml::Value::Table(table).try_into::<T>().err();
+    let original = error(table.clone())?.message().to_string();
+    if original.starts_with("missing field") {
+        return None;
+    }
+    table
+        .keys()
+        .find(|key| {
+            let mut without = table.clone();
+            without.remove(*key);
+            match error(without) {
+                Some(e) => e.message() != original,
+                None => true,
+            }
+        })
+        .cloned()
+}
+
+// Parses a scene; `path` is used in error messages and to resolve referenced files.
+pub fn parse_scene(source: &str, path: &Path) -> Result<Scene, SceneError> {
+    let mut loader = Loader {
+        source,
+        path,
+        textures: BTreeMap::new(),
+        materials: BTreeMap::new(),
+        emitters: BTreeSet::new(),
+        meshes: HashMap::new(),
+    };
+
+    let desc: SceneDesc = toml::from_str(source).map_err(|e| loader.parse_error(&e))?;
+
+    let camera = match &desc.camera {
+        Some(camera) => loader.camera(camera)?,
+        None => Camera::default(),
+    };
+
+    for (name, texture) in &desc.textures {
+        let texture = loader.texture(texture)?;
+        loader.textures.insert(name.clone(), texture);
+    }
+
+    for (name, material) in &desc.materials {
+        if let MaterialDesc::DiffuseLight { .. } = material.get_ref() {
+            loader.emitters.insert(name.clone());
+        }
+        let material = loader.material(material)?;
+        loader.materials.insert(name.clone(), material);
+    }
+
+    let mut world = HittableList::new();
+    let mut lights = HittableList::new();
+    for object in &desc.objects {
+        loader.object(object, &mut world, &mut lights)?;
+    }
+
+    Ok(Scene {
+        camera,
+        world: Arc::new(BvhNode::from_list(world)),
+        lights,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use super::{parse_scene, SceneError};
+    use crate::ray::{HitRecord, Interval, Ray};
+    use crate::sampler::SamplerKind;
+    use crate::vec3::{Color, Point3, Vec3};
+
+    const SCENE: &str = r#"
+[camera]
+aspect_ratio = 2.0
+image_width = 64
+samples_per_pixel = 4
+sampler = "stratified"
+adaptive_threshold = 0.02
+min_samples = 2
+lookfrom = [0, 0, 5]
+lookat = [0, 0, 0]
+background = [0.1, 0.1, 0.1]
+tone_map = "aces"
+integrator = "path"
+roulette_depth = 5
+seed = 7
+
+[textures.checks]
+type = "checker"
+scale = 0.5
+even = [0.2, 0.3, 0.1]
+odd = [0.9, 0.9, 0.9]
+
+[textures.stone]
+type = "marble"
+scale = 4
+seed = 7
+vein = [0.2, 0.2, 0.25]
+
+[materials.red]
+type = "lambertian"
+albedo = [0.8, 0.1, 0.1]
+
+[materials.stone]
+type = "lambertian"
+texture = "stone"
+
+[materials.floor]
+type = "lambertian"
+texture = "checks"
+
+[materials.lamp]
+type = "diffuse_light"
+emit = [4, 4, 4]
+
+[[objects]]
+type = "sphere"
+center = [0, 0, 0]
+radius = 1
+material = "red"
+
+[[objects]]
+type = "sphere"
+center = [0, 3, 0]
+radius = 0.5
+material = "lamp"
+
+[[objects]]
+type = "quad"
+q = [-5, -1, -5]
+u = [10, 0, 0]
+v = [0, 0, 10]
+material = "stone"
+
+[[objects]]
+type = "disk"
+center = [3, 0, 0]
+normal = [0, 0, 1]
+radius = 0.5
+material = "red"
+
+[[objects]]
+type = "triangle"
+vertices = [[-3, 0, 0], [-2, 0, 0], [-3, 1, 0]]
+material = "red"
+
+[[objects]]
+type = "box"
+min = [-1, -1, -1]
+max = [1, 1, 1]
+material = "red"
+transform = [{ scale = [1, 1, 0.5] }, { rotate = { axis = [0, 1, 0], angle = 90 } }, { translate = [0, 0, -3.5] }]
+"#;
+
+    fn parse_error(source: &str) -> (usize, String) {
+        match parse_scene(source, Path::new("bad.toml")) {
+            Err(SceneError::Parse { line, message, .. }) => (line, message),
+            Err(other) => panic!("unexpected error {}", other),
+            Ok(_) => panic!("expected a parse error"),
+        }
+    }
+
+    #[test]
+    fn test_parse_scene() {
+        let scene = parse_scene(SCENE, Path::new("scene.toml")).unwrap();
+        assert_eq!(scene.camera.img_width, 64);
+        assert_eq!(scene.camera.samples_per_pixel, 4);
+        assert_eq!(scene.camera.roulette_depth, 5);
+        assert_eq!(scene.camera.sampler, SamplerKind::Stratified);
+        assert_eq!(scene.camera.seed, 7);
+        assert_eq!(scene.camera.adaptive_threshold, 0.02);
+        assert_eq!(scene.camera.min_samples, 2);
+        assert_eq!(scene.camera.lookfrom, Point3::new(0.0, 0.0, 5.0));
+
+        let r = Ray::new(Point3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut rec = HitRecord::default();
+        assert!(scene
+            .world
+            .hit(&r, Interval::new(0.001, f32::INFINITY), &mut rec));
+        assert_eq!(rec.t, 4.0);
+
+        // Straight down onto the floor quad, between the sphere and the box.
+        let r = Ray::new(Point3::new(0.0, 5.0, 3.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(scene
+            .world
+            .hit(&r, Interval::new(0.001, f32::INFINITY), &mut rec));
+        assert_eq!(rec.point, Point3::new(0.0, -1.0, 3.0));
+
+        // The box was squashed along z, then turned so that it's thin along x.
+        let r = Ray::new(Point3::new(3.0, 0.0, -3.5), Vec3::new(-1.0, 0.0, 0.0));
+        assert!(scene
+            .world
+            .hit(&r, Interval::new(0.001, f32::INFINITY), &mut rec));
+        assert!((rec.t - 2.5).abs() < 1e-4, "{}", rec.t);
+        assert!((rec.normal - Vec3::new(1.0, 0.0, 0.0)).length() < 1e-4);
+    }
+
+    #[test]
+    fn test_display_keys() {
+        let render = |display: &str| {
+            let source = format!(
+                r#"
+[camera]
+image_width = 8
+samples_per_pixel = 2
+background = [0.6, 0.4, 0.2]
+{}
+
+[materials.grey]
+type = "lambertian"
+albedo = [0.5, 0.5, 0.5]
+
+[[objects]]
+type = "sphere"
+center = [0, 0, -1]
+radius = 0.5
+material = "grey"
+"#,
+                display
+            );
+            let mut scene = parse_scene(&source, Path::new("scene.toml")).unwrap();
+            scene
+                .camera
+                .render_film(scene.world.as_ref(), &scene.lights)
+                .to_rgb8()
+        };
+        let plain = render("");
+        assert_ne!(plain, render("exposure = 2"));
+        assert_ne!(plain, render("tone_map = \"reinhard\""));
+        assert_ne!(plain, render("transfer = \"srgb\""));
+        assert_eq!(plain, render("exposure = 0"));
+    }
+
+    #[test]
+    fn test_moving_objects() {
+        let source = r#"
+[camera]
+shutter_open = 0
+shutter_close = 1
+
+[materials.m]
+type = "lambertian"
+albedo = [0.5, 0.5, 0.5]
+
+[[objects]]
+type = "sphere"
+center = [0, 0, 0]
+center_end = [0, 2, 0]
+radius = 0.5
+material = "m"
+
+[[objects]]
+type = "box"
+min = [-0.5, -0.5, -0.5]
+max = [0.5, 0.5, 0.5]
+material = "m"
+transform = [{ translate = [4, 0, 0] }]
+motion = [{ translate = { from = [0, 0, 0], to = [0, 0, -4] } }]
+"#;
+        let scene = parse_scene(source, Path::new("scene.toml")).unwrap();
+        assert_eq!(scene.camera.shutter_close, 1.0);
+
+        let hit = |origin: Point3, time: f32| {
+            let r = Ray::with_time(origin, Vec3::new(0.0, 0.0, -1.0), time);
+            let mut rec = HitRecord::default();
+            scene
+                .world
+                .hit(&r, Interval::new(0.001, f32::INFINITY), &mut rec)
+                .then_some(rec.t)
+        };
+        let sphere_top = Point3::new(0.0, 2.0, 5.0);
+        assert_eq!(hit(sphere_top, 0.0), None);
+        assert_eq!(hit(sphere_top, 1.0), Some(4.5));
+        let box_ray = Point3::new(4.0, 0.0, 5.0);
+        assert_eq!(hit(box_ray, 0.0), Some(4.5));
+        assert!((hit(box_ray, 0.5).unwrap() - 6.5).abs() < 1e-4);
+
+        let (_, message) = parse_error("[camera]\nshutter_open = 0.6\nshutter_close = 0.4\n");
+        assert!(message.contains("`shutter_close`"), "{}", message);
+    }
+
+    #[test]
+    fn test_participating_media() {
+        let source = r#"
+[materials.smoke]
+type = "henyey_greenstein"
+albedo = [0.9, 0.9, 0.9]
+g = 0.3
+
+[materials.mist]
+type = "isotropic"
+albedo = [1, 1, 1]
+
+[[objects]]
+type = "box"
+min = [-1, -1, -1]
+max = [1, 1, 1]
+material = "smoke"
+density = 1e6
+
+[[objects]]
+type = "sphere"
+center = [0, 0, 0]
+radius = 1
+material = "mist"
+density = 1e6
+transform = [{ translate = [4, 0, 0] }]
+"#;
+        let scene = parse_scene(source, Path::new("scene.toml")).unwrap();
+        // Dense enough that rays scatter right where they enter.
+        for (x, t) in [(0.0, 4.0), (4.0, 4.0)] {
+            let r = Ray::new(Point3::new(x, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+            let mut rec = HitRecord::default();
+            assert!(scene
+                .world
+                .hit(&r, Interval::new(0.001, f32::INFINITY), &mut rec));
+            assert!((rec.t - t).abs() < 1e-3, "{}", rec.t);
+        }
+
+        let (_, message) =
+            parse_error("[materials.m]\ntype = \"henyey_greenstein\"\nalbedo = [1, 1, 1]\ng = 1\n");
+        assert!(message.contains("`g`"), "{}", message);
+        let (_, message) = parse_error(
+            "[materials.m]\ntype = \"isotropic\"\nalbedo = [1, 1, 1]\n\n[[objects]]\ntype = \"sphere\"\ncenter = [0, 0, 0]\nradius = 1\nmaterial = \"m\"\ndensity = 0\n",
+        );
+        assert!(message.contains("`density`"), "{}", message);
+    }
+
+    #[test]
+    fn test_lights_are_collected() {
+        let source = r#"
+[materials.lamp]
+type = "diffuse_light"
+emit = [4, 4, 4]
+
+[materials.matte]
+type = "lambertian"
+albedo = [0.5, 0.5, 0.5]
+
+[[objects]]
+type = "quad"
+q = [-1, 2, -1]
+u = [2, 0, 0]
+v = [0, 0, 2]
+material = "lamp"
+
+[[objects]]
+type = "sphere"
+center = [0, 0, 0]
+radius = 1
+material = "lamp"
+transform = [{ translate = [5, 0, 0] }]
+
+[[objects]]
+type = "sphere"
+center = [0, -100, 0]
+radius = 99
+material = "matte"
+"#;
+        let scene = parse_scene(source, Path::new("scene.toml")).unwrap();
+        assert_eq!(scene.lights.len(), 2);
+        // The moved sphere is sampled where it was placed.
+        let toward = scene.lights.objects[1].random(&Point3::zero(), 0.0, (0.5, 0.5));
+        assert!(toward.unit().x > 0.97, "{:?}", toward);
+    }
+
+    #[test]
+    fn test_volumes() {
+        let dir = std::env::temp_dir().join(format!("ray1-scene-volume-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("solid.raw"), [255u8; 8]).unwrap();
+        let source = r#"
+[textures.heat]
+type = "grid"
+file = "solid.raw"
+resolution = [2, 2, 2]
+min = [-1, -1, -1]
+max = [1, 1, 1]
+tint = [4, 2, 1]
+
+[[objects]]
+type = "volume"
+file = "solid.raw"
+resolution = [2, 2, 2]
+min = [-1, -1, -1]
+max = [1, 1, 1]
+extinction = 1e5
+albedo = [0, 0, 0]
+emission_texture = "heat"
+
+[[objects]]
+type = "volume"
+noise = { scale = 2, seed = 4, threshold = 0 }
+min = [3, -1, -1]
+max = [5, 1, 1]
+extinction = 0
+"#;
+        let scene = parse_scene(source, &dir.join("scene.toml")).unwrap();
+        let hit = |x: f32| {
+            let r = Ray::new(Point3::new(x, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+            let mut rec = HitRecord::default();
+            scene
+                .world
+                .hit(&r, Interval::new(0.001, f32::INFINITY), &mut rec)
+                .then_some(rec)
+        };
+        // The dense grid stops rays right where they enter, and glows with the tinted grid.
+        let rec = hit(0.0).unwrap();
+        assert!((rec.t - 4.0).abs() < 1e-3, "{}", rec.t);
+        let mat = rec.mat.clone().unwrap();
+        assert!((mat.emitted(&rec) - Color::new(4.0, 2.0, 1.0)).length() < 1e-4);
+        // An empty medium lets everything through.
+        assert!(hit(4.0).is_none());
+
+        let (_, message) = parse_error(
+            "[[objects]]\ntype = \"volume\"\nfile = \"a.vol\"\nnoise = { scale = 1 }\nextinction = 1\n",
+        );
+        assert!(message.contains("`file` and `noise`"), "{}", message);
+        let (line, message) = parse_error(
+            "[[objects]]\ntype = \"volume\"\nmin = [0, 0, 0]\nmax = [1, 1, 1]\nextinction = 1\nnoise = { scale = 1, octaves = 40 }\n",
+        );
+        assert_eq!(line, 6);
+        assert!(message.contains("`noise.octaves`"), "{}", message);
+        let (_, message) =
+            parse_error("[[objects]]\ntype = \"volume\"\nfile = \"a.raw\"\nextinction = 1\n");
+        assert!(message.contains("resolution"), "{}", message);
+        match parse_scene(
+            "[[objects]]\ntype = \"volume\"\nfile = \"missing.vol\"\nextinction = 1\n",
+            &dir.join("scene.toml"),
+        ) {
+            Err(SceneError::Grid { line, .. }) => assert_eq!(line, 1),
+            _ => panic!("expected a grid error"),
+        }
+    }
+
+    #[test]
+    fn test_unknown_material_reference() {
+        let source =
+            "[[objects]]\ntype = \"sphere\"\ncenter = [0, 0, 0]\nradius = 1\nmaterial = \"gold\"\n";
+        let (line, message) = parse_error(source);
+        assert_eq!(line, 5);
+        assert!(message.contains("unknown material `gold`"), "{}", message);
+    }
+
+    #[test]
+    fn test_unknown_type() {
+        let source = "\n[materials.x]\ntype = \"plastic\"\nalbedo = [1, 1, 1]\n";
+        let (line, message) = parse_error(source);
+        assert_eq!(line, 3);
+        assert!(message.contains("plastic"), "{}", message);
+    }
+
+    #[test]
+    fn test_invalid_values() {
+        let (line, message) = parse_error(
+            "[materials.m]\ntype = \"lambertian\"\nalbedo = [1, 1, 1]\n\n[[objects]]\ntype = \"sphere\"\ncenter = [0, 0, 0]\nradius = -1\nmaterial = \"m\"\n",
+        );
+        assert_eq!(line, 8);
+        assert!(message.contains("`radius`"), "{}", message);
+
+        let (line, message) = parse_error("[camera]\nintegrator = \"photon\"\n");
+        assert_eq!(line, 2);
+        assert!(message.contains("`integrator`"), "{}", message);
+
+        let (line, message) = parse_error("[camera]\nimage_width = \"wide\"\n");
+        assert_eq!(line, 2);
+        assert!(message.contains("invalid type"), "{}", message);
+
+        let (_, message) = parse_error(
+            "[[objects]]\ntype = \"box\"\nmin = [0, 0, 0]\nmax = [1, 0, 1]\nmaterial = \"m\"\n",
+        );
+        assert!(message.contains("`min`"), "{}", message);
+
+        let (_, message) = parse_error(
+            "[materials.m]\ntype = \"lambertian\"\nalbedo = [1, 1, 1]\n\n[[objects]]\ntype = \"sphere\"\ncenter = [0, 0, 0]\nradius = 1\nmaterial = \"m\"\ntransform = [{ scale = 0 }]\n",
+        );
+        assert!(message.contains("`scale`"), "{}", message);
+
+        let (_, message) = parse_error(
+            "[[objects]]\ntype = \"sphere\"\ncenter = [0, 0, 0]\nradius = 1\nmaterial = \"m\"\ntransform = [{ shear = 2 }]\n",
+        );
+        assert!(message.contains("shear"), "{}", message);
+
+        let (_, message) = parse_error("[camera]\nlookfrom = [0, 5, 0]\nlookat = [0, 0, 0]\n");
+        assert!(message.contains("`vup`"), "{}", message);
+
+        let (_, message) = parse_error("[camera]\nzoom = 2\n");
+        assert!(message.contains("zoom"), "{}", message);
+
+        let (_, message) = parse_error("[camera]\ntone_map = \"magic\"\n");
+        assert!(message.contains("`tone_map`"), "{}", message);
+
+        let (_, message) = parse_error("[camera]\nroulette_depth = -1\n");
+        assert!(message.contains("`roulette_depth`"), "{}", message);
+
+        let (_, message) = parse_error("[camera]\nadaptive_threshold = -1\n");
+        assert!(message.contains("`adaptive_threshold`"), "{}", message);
+
+        let (_, message) = parse_error("[camera]\nsampler = \"random\"\n");
+        assert!(message.contains("`sampler`"), "{}", message);
+    }
+
+    #[test]
+    fn test_error_locations() {
+        // Checked values are reported at their key, not at the start of their table.
+        let source =
+            "[camera]\nimage_width = 8\n\nlookfrom = [0, 0, 1]\n# Too wide.\n  vfov = 400\n";
+        match parse_scene(source, Path::new("bad.toml")) {
+            Err(SceneError::Parse {
+                line,
+                column,
+                message,
+                ..
+            }) => {
+                assert_eq!((line, column), (6, 3));
+                assert!(message.contains("`vfov`"), "{}", message);
+            }
+            Err(other) => panic!("unexpected error {}", other),
+            Ok(_) => panic!("expected a parse error"),
+        }
+
+        let (line, message) =
+            parse_error("[camera]\nbackground.bottom = [1, 1, 1]\nbackground.top = [0, -1, 0]\n");
+        assert_eq!(line, 3);
+        assert!(message.contains("`background.top`"), "{}", message);
+
+        // Values that fail to deserialize name their key too, within tables tagged by type.
+        let (line, message) =
+            parse_error("[materials.m]\ntype = \"metal\"\nfuzz = 0.1\nalbedo = [0.5, 0.5]\n");
+        assert_eq!(line, 4);
+        assert!(message.contains("`albedo`"), "{}", message);
+        assert!(message.contains("invalid length 2"), "{}", message);
+
+        let (line, message) = parse_error(
+            "[[objects]]\ntype = \"sphere\"\ncenter = [0, 0, 0]\nradius = \"big\"\nmaterial = \"m\"\n",
+        );
+        assert_eq!(line, 4);
+        assert!(message.contains("`radius`"), "{}", message);
+
+        let (line, message) = parse_error("[camera]\nsamples_per_pixel = 4\nlookat = [0, 1]\n");
+        assert_eq!(line, 3);
+        assert!(message.contains("`lookat`"), "{}", message);
+
+        let (line, message) = parse_error("[materials.m]\ntype = \"metal\"\ncolour = [1, 1, 1]\n");
+        assert_eq!(line, 3);
+        assert!(message.contains("unknown field `colour`"), "{}", message);
+
+        // Missing keys can only be reported at their table.
+        let (line, message) = parse_error("\n[materials.m]\ntype = \"metal\"\n");
+        assert_eq!(line, 2);
+        assert!(message.contains("missing field `albedo`"), "{}", message);
+    }
+
+    #[test]
+    fn test_texture_references() {
+        let (_, message) =
+            parse_error("[materials.m]\ntype = \"lambertian\"\ntexture = \"wood\"\n");
+        assert!(message.contains("unknown texture `wood`"), "{}", message);
+
+        let (_, message) = parse_error("[materials.m]\ntype = \"lambertian\"\n");
+        assert!(message.contains("exactly one of `albedo`"), "{}", message);
+
+        let (_, message) = parse_error(
+            "[textures.t]\ntype = \"checker\"\nscale = 0\neven = [0, 0, 0]\nodd = [1, 1, 1]\n",
+        );
+        assert!(message.contains("`scale`"), "{}", message);
+
+        let (_, message) =
+            parse_error("[textures.t]\ntype = \"worley\"\nscale = 2\nmetric = \"f3\"\n");
+        assert!(message.contains("`metric`"), "{}", message);
+    }
+
+    #[test]
+    fn test_missing_texture_image() {
+        let source = "\n[textures.t]\ntype = \"image\"\nfile = \"missing.png\"\n";
+        match parse_scene(source, Path::new("scenes/bad.toml")) {
+            Err(SceneError::Texture { line, file, .. }) => {
+                assert_eq!(line, 2);
+                assert_eq!(file, Path::new("scenes/missing.png"));
+            }
+            Err(other) => panic!("unexpected error {}", other),
+            Ok(_) => panic!("expected a texture error"),
+        }
+    }
+}
@@ -0,0 +1,415 @@
+// Scenes compiled into the binary, selectable by name from the command line.
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    bvh::BvhNode,
+    camera::{Background, Camera},
+    material::{
+        Dielectric, DiffuseLight, HenyeyGreenstein, Isotropic, Lambertian, Material, Metal,
+        VolumeMaterial,
+    },
+    medium::ConstantMedium,
+    quad::{BoxShape, Quad},
+    ray::{Hittable, HittableList, Sphere},
+    rtweekend::mix_seed,
+    scene::Scene,
+    texture::{
+        CheckerTexture, MarbleTexture, SolidColor, WoodTexture, WorleyMetric, WorleyTexture,
+    },
+    transform::{Instance, Transform},
+    vec3::{Color, Point3, Vec3},
+    volume::{HeterogeneousVolume, NoiseDensity},
+};
+
+pub const BUILTIN_SCENES: &[&str] = &[
+    "three-spheres",
+    "procedural",
+    "cornell-box",
+    "cornell-smoke",
+    "bouncing-spheres",
+    "clouds",
+];
+
+// The built-in scene called `name`, rendering with `seed`, from which scenes with procedural
+// textures or media also derive their noise.
+pub fn builtin(name: &str, seed: u64) -> Option<Scene> {
+    let mut scene = match name {
+        "three-spheres" => three_spheres(),
+        "procedural" => procedural(seed),
+        "cornell-box" => cornell_box(),
+        "cornell-smoke" => cornell_smoke(),
+        "bouncing-spheres" => bouncing_spheres(),
+        "clouds" => clouds(seed),
+        _ => return None,
+    };
+    scene.camera.seed = seed;
+    Some(scene)
+}
+
+pub fn three_spheres() -> Scene {
+    // world
+    let mut world = HittableList::new();
+    let material_ground = Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.0)));
+    let material_center = Arc::new(Lambertian::new(Color::new(0.1, 0.2, 0.5)));
+    let material_left = Arc::new(Dielectric::new(1.50));
+    let material_bubble = Arc::new(Dielectric::new(1.00 / 1.50));
+    let material_right = Arc::new(Metal::new(Color::new(0.8, 0.6, 0.2), 1.0));
+
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, -100.5, -1.0),
+        100.0,
+        material_ground,
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, 0.0, -1.2),
+        0.5,
+        material_center,
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(-1.0, 0.0, -1.0),
+        0.5,
+        material_left,
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(-1.0, 0.0, -1.0),
+        0.4,
+        material_bubble,
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(1.0, 0.0, -1.0),
+        0.5,
+        material_right,
+    )));
+
+    let mut camera = Camera::default();
+    camera.aspect_ratio = 16.0 / 9.0;
+    camera.img_width = 400;
+    camera.samples_per_pixel = 100;
+    camera.max_depth = 50;
+
+    camera.vfov = 20.0;
+    camera.lookfrom = Point3::new(-2.0, 2.0, 1.0);
+    camera.lookat = Point3::new(0.0, 0.0, -1.0);
+    camera.vup = Vec3::new(0.0, 1.0, 0.0);
+
+    camera.defocus_angle = 10.0;
+    camera.focus_dist = 3.4;
+
+    Scene {
+        camera,
+        world: Arc::new(BvhNode::from_list(world)),
+        lights: HittableList::new(),
+    }
+}
+
+// Marble, wood and cellular spheres on a checkered floor, with noise derived from `seed`.
+pub fn procedural(seed: u64) -> Scene {
+    let mut world = HittableList::new();
+
+    let checker =
+        CheckerTexture::from_colors(1.0, Color::new(0.2, 0.3, 0.1), Color::new(0.9, 0.9, 0.9));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(Lambertian::from_texture(Arc::new(checker))),
+    )));
+
+    let marble = MarbleTexture::new(mix_seed(seed, 1), 4.0);
+    world.add(Arc::new(Sphere::new(
+        Point3::new(-2.2, 1.0, 0.0),
+        1.0,
+        Arc::new(Lambertian::from_texture(Arc::new(marble))),
+    )));
+
+    let wood = WoodTexture::new(mix_seed(seed, 2), 6.0);
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, 1.0, 0.0),
+        1.0,
+        Arc::new(Lambertian::from_texture(Arc::new(wood))),
+    )));
+
+    let mut cells = WorleyTexture::new(mix_seed(seed, 3), 4.0);
+    cells.metric = WorleyMetric::F2MinusF1;
+    cells.cell = Color::new(0.8, 0.3, 0.1);
+    cells.edge = Color::new(0.1, 0.05, 0.02);
+    world.add(Arc::new(Sphere::new(
+        Point3::new(2.2, 1.0, 0.0),
+        1.0,
+        Arc::new(Lambertian::from_texture(Arc::new(cells))),
+    )));
+
+    let mut camera = Camera::default();
+    camera.aspect_ratio = 16.0 / 9.0;
+    camera.img_width = 400;
+    camera.samples_per_pixel = 100;
+    camera.max_depth = 50;
+
+    camera.vfov = 30.0;
+    camera.lookfrom = Point3::new(0.0, 3.0, 9.0);
+    camera.lookat = Point3::new(0.0, 1.0, 0.0);
+    camera.vup = Vec3::new(0.0, 1.0, 0.0);
+
+    Scene {
+        camera,
+        world: Arc::new(BvhNode::from_list(world)),
+        lights: HittableList::new(),
+    }
+}
+
+// The empty Cornell room, 555 units on a side: red and green side walls, white floor, ceiling
+// and back wall, and a ceiling light, which is returned for light sampling.
+fn cornell_room(world: &mut HittableList) -> HittableList {
+    let red = Arc::new(Lambertian::new(Color::new(0.65, 0.05, 0.05)));
+    let white = Arc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73)));
+    let green = Arc::new(Lambertian::new(Color::new(0.12, 0.45, 0.15)));
+    let light = Arc::new(DiffuseLight::new(Color::new(15.0, 15.0, 15.0)));
+
+    world.add(Arc::new(Quad::new(
+        Point3::new(555.0, 0.0, 0.0),
+        Vec3::new(0.0, 555.0, 0.0),
+        Vec3::new(0.0, 0.0, 555.0),
+        green,
+    )));
+    world.add(Arc::new(Quad::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 555.0, 0.0),
+        Vec3::new(0.0, 0.0, 555.0),
+        red,
+    )));
+    // The light faces down into the box.
+    let light: Arc<dyn Hittable> = Arc::new(Quad::new(
+        Point3::new(343.0, 554.0, 332.0),
+        Vec3::new(-130.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, -105.0),
+        light,
+    ));
+    world.add(light.clone());
+    world.add(Arc::new(Quad::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(555.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, 555.0),
+        white.clone(),
+    )));
+    world.add(Arc::new(Quad::new(
+        Point3::new(555.0, 555.0, 555.0),
+        Vec3::new(-555.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, -555.0),
+        white.clone(),
+    )));
+    world.add(Arc::new(Quad::new(
+        Point3::new(0.0, 0.0, 555.0),
+        Vec3::new(555.0, 0.0, 0.0),
+        Vec3::new(0.0, 555.0, 0.0),
+        white,
+    )));
+    HittableList::from_object(light)
+}
+
+fn cornell_camera() -> Camera {
+    let mut camera = Camera::default();
+    camera.aspect_ratio = 1.0;
+    camera.img_width = 600;
+    camera.samples_per_pixel = 200;
+    camera.max_depth = 50;
+    camera.background = Background::Solid(Color::zero());
+
+    camera.vfov = 40.0;
+    camera.lookfrom = Point3::new(278.0, 278.0, -800.0);
+    camera.lookat = Point3::new(278.0, 278.0, 0.0);
+    camera.vup = Vec3::new(0.0, 1.0, 0.0);
+    camera
+}
+
+// The two blocks of the Cornell box in their usual places, made of `mat`.
+fn cornell_blocks(mat: Arc<dyn Material>) -> (Arc<dyn Hittable>, Arc<dyn Hittable>) {
+    let up = Vec3::new(0.0, 1.0, 0.0);
+    let tall = Arc::new(BoxShape::new(
+        Point3::zero(),
+        Point3::new(165.0, 330.0, 165.0),
+        mat.clone(),
+    ));
+    let placement =
+        Transform::rotate(up, 15.0).then(&Transform::translate(Vec3::new(265.0, 0.0, 295.0)));
+    let tall = Arc::new(Instance::new(tall, placement));
+
+    let short = Arc::new(BoxShape::new(
+        Point3::zero(),
+        Point3::new(165.0, 165.0, 165.0),
+        mat,
+    ));
+    let placement =
+        Transform::rotate(up, -18.0).then(&Transform::translate(Vec3::new(130.0, 0.0, 65.0)));
+    let short = Arc::new(Instance::new(short, placement));
+    (tall, short)
+}
+
+// The Cornell box: the Cornell room with two white blocks.
+pub fn cornell_box() -> Scene {
+    let mut world = HittableList::new();
+    let lights = cornell_room(&mut world);
+    let (tall, short) = cornell_blocks(Arc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73))));
+    world.add(tall);
+    world.add(short);
+
+    Scene {
+        camera: cornell_camera(),
+        world: Arc::new(BvhNode::from_list(world)),
+        lights,
+    }
+}
+
+// The Cornell box with its blocks replaced by dark smoke and a forward-scattering white fog.
+pub fn cornell_smoke() -> Scene {
+    let mut world = HittableList::new();
+    let lights = cornell_room(&mut world);
+    // The blocks only serve as boundaries, so their material is never seen.
+    let (tall, short) = cornell_blocks(Arc::new(Lambertian::new(Color::zero())));
+    world.add(Arc::new(ConstantMedium::new(
+        tall,
+        0.01,
+        Arc::new(Isotropic::new(Color::zero())),
+    )));
+    world.add(Arc::new(ConstantMedium::new(
+        short,
+        0.01,
+        Arc::new(HenyeyGreenstein::new(Color::ones(), 0.6)),
+    )));
+
+    Scene {
+        camera: cornell_camera(),
+        world: Arc::new(BvhNode::from_list(world)),
+        lights,
+    }
+}
+
+// A grid of small diffuse spheres hopping upward while the shutter is open, to show motion blur.
+pub fn bouncing_spheres() -> Scene {
+    let mut world = HittableList::new();
+    let checker = Arc::new(CheckerTexture::from_colors(
+        0.32,
+        Color::new(0.2, 0.3, 0.1),
+        Color::new(0.9, 0.9, 0.9),
+    ));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(Lambertian::from_texture(checker)),
+    )));
+
+    for a in -5i32..5 {
+        for b in -5..5 {
+            let center = Point3::new(a as f32 * 2.0 + 1.0, 0.2, b as f32 * 2.0 + 1.0);
+            // A fixed pattern of colors and hop heights keeps the scene the same on every run.
+            let k = ((a * 7 + b * 13).rem_euclid(10)) as f32 / 10.0;
+            let albedo = Color::new(0.2 + 0.7 * k, 0.8 - 0.6 * k, 0.3 + 0.4 * (1.0 - k));
+            let hop = Vec3::new(0.0, 0.1 + 0.4 * k, 0.0);
+            world.add(Arc::new(Sphere::moving(
+                center,
+                center + hop,
+                0.2,
+                Arc::new(Lambertian::new(albedo)),
+            )));
+        }
+    }
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, 1.0, 0.0),
+        1.0,
+        Arc::new(Dielectric::new(1.5)),
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(4.0, 1.0, 0.0),
+        1.0,
+        Arc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0)),
+    )));
+
+    let mut camera = Camera::default();
+    camera.aspect_ratio = 16.0 / 9.0;
+    camera.img_width = 400;
+    camera.samples_per_pixel = 100;
+    camera.max_depth = 50;
+
+    camera.vfov = 20.0;
+    camera.lookfrom = Point3::new(13.0, 2.0, 3.0);
+    camera.lookat = Point3::zero();
+    camera.vup = Vec3::new(0.0, 1.0, 0.0);
+
+    camera.defocus_angle = 0.6;
+    camera.focus_dist = 10.0;
+
+    camera.shutter_open = 0.0;
+    camera.shutter_close = 1.0;
+
+    Scene {
+        camera,
+        world: Arc::new(BvhNode::from_list(world)),
+        lights: HittableList::new(),
+    }
+}
+
+// A bank of noise clouds under a blue sky, with a glowing ember cloud and a glass sphere on a
+// checkered floor. The clouds' shapes derive from `seed`.
+pub fn clouds(seed: u64) -> Scene {
+    let mut world = HittableList::new();
+    let checker = Arc::new(CheckerTexture::from_colors(
+        1.0,
+        Color::new(0.3, 0.3, 0.3),
+        Color::new(0.8, 0.8, 0.8),
+    ));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(Lambertian::from_texture(checker)),
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(1.5, 1.0, 1.0),
+        1.0,
+        Arc::new(Dielectric::new(1.5)),
+    )));
+
+    let white = Arc::new(SolidColor::new(Color::new(0.95, 0.95, 0.95)));
+    let cloud = Arc::new(VolumeMaterial::new(
+        white,
+        Arc::new(SolidColor::new(Color::zero())),
+        0.5,
+    ));
+    let mut density = NoiseDensity::new(mix_seed(seed, 11), 0.8);
+    density.threshold = 0.45;
+    world.add(Arc::new(HeterogeneousVolume::new(
+        Aabb::from_points(Point3::new(-8.0, 3.0, -8.0), Point3::new(8.0, 5.0, 4.0)),
+        Arc::new(density),
+        4.0,
+        cloud,
+    )));
+
+    let ember = Arc::new(VolumeMaterial::new(
+        Arc::new(SolidColor::new(Color::new(0.2, 0.2, 0.2))),
+        Arc::new(SolidColor::new(Color::new(4.0, 1.2, 0.3))),
+        0.0,
+    ));
+    let mut density = NoiseDensity::new(mix_seed(seed, 5), 1.5);
+    density.threshold = 0.5;
+    world.add(Arc::new(HeterogeneousVolume::new(
+        Aabb::from_points(Point3::new(-2.5, 0.0, 0.0), Point3::new(-0.5, 2.0, 2.0)),
+        Arc::new(density),
+        6.0,
+        ember,
+    )));
+
+    let mut camera = Camera::default();
+    camera.aspect_ratio = 16.0 / 9.0;
+    camera.img_width = 400;
+    camera.samples_per_pixel = 100;
+    camera.max_depth = 50;
+
+    camera.vfov = 40.0;
+    camera.lookfrom = Point3::new(0.0, 2.0, 9.0);
+    camera.lookat = Point3::new(0.0, 2.0, 0.0);
+    camera.vup = Vec3::new(0.0, 1.0, 0.0);
+
+    Scene {
+        camera,
+        world: Arc::new(BvhNode::from_list(world)),
+        lights: HittableList::new(),
+    }
+}
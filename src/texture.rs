@@ -0,0 +1,554 @@
+// Textures: spatially varying colors looked up by surface (u, v) coordinates and hit point.
+use std::{fs::File, io::BufReader, path::Path, str::FromStr, sync::Arc};
+
+use crate::{
+    noise::{Perlin, Worley},
+    tonemap::Transfer,
+    vec3::{Color, Point3, Vec3},
+    volume::VoxelGrid,
+};
+
+pub trait Texture: Send + Sync {
+    fn value(&self, u: f32, v: f32, p: &Point3) -> Color;
+}
+
+pub struct SolidColor {
+    albedo: Color,
+}
+
+impl SolidColor {
+    pub fn new(albedo: Color) -> Self {
+        Self { albedo }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f32, _v: f32, _p: &Point3) -> Color {
+        self.albedo
+    }
+}
+
+// Solid 3D checker pattern of cubes with edge length `scale`, independent of the surface UVs.
+pub struct CheckerTexture {
+    inv_scale: f32,
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+}
+
+impl CheckerTexture {
+    pub fn new(scale: f32, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> Self {
+        Self {
+            inv_scale: 1.0 / scale,
+            even,
+            odd,
+        }
+    }
+
+    pub fn from_colors(scale: f32, even: Color, odd: Color) -> Self {
+        Self::new(
+            scale,
+            Arc::new(SolidColor::new(even)),
+            Arc::new(SolidColor::new(odd)),
+        )
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f32, v: f32, p: &Point3) -> Color {
+        let x = (self.inv_scale * p.x).floor() as i64;
+        let y = (self.inv_scale * p.y).floor() as i64;
+        let z = (self.inv_scale * p.z).floor() as i64;
+
+        if (x + y + z).rem_euclid(2) == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+// How texel coordinates outside the image are mapped back onto it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WrapMode {
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+impl WrapMode {
+    fn apply(&self, i: i64, n: usize) -> usize {
+        let n = n as i64;
+        let i = match self {
+            WrapMode::Repeat => i.rem_euclid(n),
+            WrapMode::Clamp => i.clamp(0, n - 1),
+            WrapMode::Mirror => {
+                let m = i.rem_euclid(2 * n);
+                if m < n {
+                    m
+                } else {
+                    2 * n - 1 - m
+                }
+            }
+        };
+        i as usize
+    }
+}
+
+impl FromStr for WrapMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "repeat" => Ok(WrapMode::Repeat),
+            "clamp" => Ok(WrapMode::Clamp),
+            "mirror" => Ok(WrapMode::Mirror),
+            _ => Err(format!("unknown wrap mode `{}`", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "nearest" => Ok(Filter::Nearest),
+            "bilinear" => Ok(Filter::Bilinear),
+            _ => Err(format!("unknown texture filter `{}`", s)),
+        }
+    }
+}
+
+// Image mapped onto the surface UVs, with v = 0 at the bottom row. Texels are stored as linear
+// radiance; 8-bit images are decoded from sRGB when loaded.
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    texels: Vec<Color>,
+    pub wrap: WrapMode,
+    pub filter: Filter,
+}
+
+impl ImageTexture {
+    // Texels are row-major from the top, as in image files.
+    pub fn new(width: usize, height: usize, texels: Vec<Color>) -> Self {
+        assert_eq!(
+            texels.len(),
+            width * height,
+            "texel count doesn't match size"
+        );
+        Self {
+            width,
+            height,
+            texels,
+            wrap: WrapMode::Repeat,
+            filter: Filter::Bilinear,
+        }
+    }
+
+    // Loads any format the `image` crate can decode; Radiance .hdr files keep their float values.
+    pub fn load<P: AsRef<Path>>(path: P) -> image::ImageResult<Self> {
+        let path = path.as_ref();
+        let is_hdr = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("hdr"));
+
+        if is_hdr {
+            let decoder = image::hdr::HDRDecoder::new(BufReader::new(File::open(path)?))?;
+            let meta = decoder.metadata();
+            let texels = decoder
+                .read_image_hdr()?
+                .iter()
+                .map(|p| Color::new(p.data[0], p.data[1], p.data[2]))
+                .collect();
+            return Ok(Self::new(meta.width as usize, meta.height as usize, texels));
+        }
+
+        let img = image::open(path)?.to_rgb();
+        let (width, height) = img.dimensions();
+        let decode = |c: u8| Transfer::Srgb.decode(c as f32 / 255.0);
+        let texels = img
+            .pixels()
+            .map(|p| Color::new(decode(p.data[0]), decode(p.data[1]), decode(p.data[2])))
+            .collect();
+        Ok(Self::new(width as usize, height as usize, texels))
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Color {
+        let x = self.wrap.apply(x, self.width);
+        let y = self.wrap.apply(y, self.height);
+        self.texels[y * self.width + x]
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f32, v: f32, _p: &Point3) -> Color {
+        // Solid cyan as a debugging aid when there is no texture data.
+        if self.texels.is_empty() {
+            return Color::new(0.0, 1.0, 1.0);
+        }
+
+        // Continuous texel coordinates, flipping v so that it runs up the image.
+        let x = u * self.width as f32;
+        let y = (1.0 - v) * self.height as f32;
+
+        match self.filter {
+            Filter::Nearest => self.texel(x.floor() as i64, y.floor() as i64),
+            Filter::Bilinear => {
+                // Texel centers sit at half-integer coordinates.
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+
+                let top = self.texel(x0, y0) * (1.0 - fx) + self.texel(x0 + 1, y0) * fx;
+                let bottom = self.texel(x0, y0 + 1) * (1.0 - fx) + self.texel(x0 + 1, y0 + 1) * fx;
+                top * (1.0 - fy) + bottom * fy
+            }
+        }
+    }
+}
+
+fn lerp(a: Color, b: Color, t: f32) -> Color {
+    a * (1.0 - t) + b * t
+}
+
+// Fractal Perlin noise (fBm) shading `color` from black, for terrain-like detail.
+pub struct NoiseTexture {
+    noise: Perlin,
+    scale: f32,
+    pub octaves: u32,
+    pub color: Color,
+}
+
+impl NoiseTexture {
+    // `scale` is the noise frequency: features are roughly 1 / scale units across.
+    pub fn new(seed: u64, scale: f32) -> Self {
+        Self {
+            noise: Perlin::new(seed),
+            scale,
+            octaves: 7,
+            color: Color::ones(),
+        }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f32, _v: f32, p: &Point3) -> Color {
+        let n = self.noise.fbm(&(*p * self.scale), self.octaves, 2.0, 0.5);
+        self.color * (0.5 * (1.0 + n))
+    }
+}
+
+// Marble: sine bands along Z, phase-shifted by turbulence into veins.
+pub struct MarbleTexture {
+    noise: Perlin,
+    scale: f32,
+    pub turbulence: f32,
+    pub base: Color,
+    pub vein: Color,
+}
+
+impl MarbleTexture {
+    pub fn new(seed: u64, scale: f32) -> Self {
+        Self {
+            noise: Perlin::new(seed),
+            scale,
+            turbulence: 10.0,
+            base: Color::ones(),
+            vein: Color::zero(),
+        }
+    }
+}
+
+impl Texture for MarbleTexture {
+    fn value(&self, _u: f32, _v: f32, p: &Point3) -> Color {
+        let phase = self.scale * p.z + self.turbulence * self.noise.turb(p, 7);
+        lerp(self.vein, self.base, 0.5 * (1.0 + phase.sin()))
+    }
+}
+
+// Wood: concentric growth rings around the Y axis, `scale` rings per unit, wobbled by noise.
+pub struct WoodTexture {
+    noise: Perlin,
+    scale: f32,
+    pub turbulence: f32,
+    pub light: Color,
+    pub dark: Color,
+}
+
+impl WoodTexture {
+    pub fn new(seed: u64, scale: f32) -> Self {
+        Self {
+            noise: Perlin::new(seed),
+            scale,
+            turbulence: 0.5,
+            light: Color::new(0.79, 0.6, 0.4),
+            dark: Color::new(0.45, 0.27, 0.12),
+        }
+    }
+}
+
+impl Texture for WoodTexture {
+    fn value(&self, _u: f32, _v: f32, p: &Point3) -> Color {
+        let radius = (p.x * p.x + p.z * p.z).sqrt();
+        let rings = self.scale * radius + self.turbulence * self.noise.turb(p, 4);
+        // Each ring fades from early (light) to late (dark) wood.
+        let t = rings - rings.floor();
+        lerp(self.light, self.dark, t * t)
+    }
+}
+
+// Which Worley distance drives a `WorleyTexture`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WorleyMetric {
+    // Distance to the nearest feature point: round cells.
+    F1,
+    // Gap between the two nearest feature points: dark cell borders.
+    F2MinusF1,
+}
+
+impl FromStr for WorleyMetric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "f1" => Ok(WorleyMetric::F1),
+            "f2-f1" => Ok(WorleyMetric::F2MinusF1),
+            _ => Err(format!("unknown Worley metric `{}`", s)),
+        }
+    }
+}
+
+// Cellular texture blending from `cell` to `edge` with the Worley distance.
+pub struct WorleyTexture {
+    noise: Worley,
+    scale: f32,
+    pub metric: WorleyMetric,
+    pub cell: Color,
+    pub edge: Color,
+}
+
+impl WorleyTexture {
+    pub fn new(seed: u64, scale: f32) -> Self {
+        Self {
+            noise: Worley::new(seed),
+            scale,
+            metric: WorleyMetric::F1,
+            cell: Color::zero(),
+            edge: Color::ones(),
+        }
+    }
+}
+
+impl Texture for WorleyTexture {
+    fn value(&self, _u: f32, _v: f32, p: &Point3) -> Color {
+        let (f1, f2) = self.noise.distances(&(*p * self.scale));
+        let d = match self.metric {
+            WorleyMetric::F1 => f1,
+            WorleyMetric::F2MinusF1 => f2 - f1,
+        };
+        lerp(self.cell, self.edge, d.clamp(0.0, 1.0))
+    }
+}
+
+// Colors from a voxel grid at the hit point, scaled by `tint`. Grids with three or more
+// channels give RGB; single-channel grids give gray.
+pub struct GridTexture {
+    grid: Arc<VoxelGrid>,
+    pub tint: Color,
+}
+
+impl GridTexture {
+    pub fn new(grid: Arc<VoxelGrid>) -> Self {
+        Self {
+            grid,
+            tint: Color::ones(),
+        }
+    }
+}
+
+impl Texture for GridTexture {
+    fn value(&self, _u: f32, _v: f32, p: &Point3) -> Color {
+        let color = if self.grid.channels() >= 3 {
+            Color::new(
+                self.grid.sample(p, 0),
+                self.grid.sample(p, 1),
+                self.grid.sample(p, 2),
+            )
+        } else {
+            Color::ones() * self.grid.sample(p, 0)
+        };
+        Vec3::elemul(color, self.tint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, sync::Arc};
+
+    use super::{
+        CheckerTexture, Filter, GridTexture, ImageTexture, MarbleTexture, NoiseTexture, SolidColor,
+        Texture, WoodTexture, WorleyMetric, WorleyTexture, WrapMode,
+    };
+    use crate::aabb::Aabb;
+    use crate::vec3::{Color, Point3};
+    use crate::volume::VoxelGrid;
+
+    fn close(a: Color, b: Color) -> bool {
+        (a - b).length() < 1e-4
+    }
+
+    // 2x2 image: red and green on the top row, blue and white on the bottom.
+    fn quad_image() -> ImageTexture {
+        ImageTexture::new(
+            2,
+            2,
+            vec![
+                Color::new(1.0, 0.0, 0.0),
+                Color::new(0.0, 1.0, 0.0),
+                Color::new(0.0, 0.0, 1.0),
+                Color::ones(),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_solid_color() {
+        let tex = SolidColor::new(Color::new(0.1, 0.2, 0.3));
+        assert_eq!(
+            tex.value(0.7, 0.1, &Point3::new(5.0, 1.0, 2.0)),
+            Color::new(0.1, 0.2, 0.3)
+        );
+    }
+
+    #[test]
+    fn test_checker_alternates_in_space() {
+        let tex = CheckerTexture::from_colors(0.5, Color::zero(), Color::ones());
+        let at = |x, y, z| tex.value(0.0, 0.0, &Point3::new(x, y, z));
+        assert_eq!(at(0.1, 0.1, 0.1), Color::zero());
+        assert_eq!(at(0.6, 0.1, 0.1), Color::ones());
+        assert_eq!(at(0.6, 0.6, 0.1), Color::zero());
+        assert_eq!(at(-0.1, 0.1, 0.1), Color::ones());
+    }
+
+    #[test]
+    fn test_image_nearest_orientation() {
+        let mut tex = quad_image();
+        tex.filter = Filter::Nearest;
+        let p = Point3::zero();
+        // v = 0 is the bottom of the image.
+        assert_eq!(tex.value(0.25, 0.25, &p), Color::new(0.0, 0.0, 1.0));
+        assert_eq!(tex.value(0.75, 0.75, &p), Color::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_image_bilinear() {
+        let tex = quad_image();
+        let p = Point3::zero();
+        // Texel centers return the texel itself, the image center averages all four.
+        assert!(close(tex.value(0.25, 0.75, &p), Color::new(1.0, 0.0, 0.0)));
+        assert!(close(tex.value(0.5, 0.5, &p), Color::new(0.5, 0.5, 0.5)));
+    }
+
+    #[test]
+    fn test_wrap_modes() {
+        let mut tex = quad_image();
+        tex.filter = Filter::Nearest;
+        let p = Point3::zero();
+        // Just right of the right edge, on the top row.
+        tex.wrap = WrapMode::Repeat;
+        assert_eq!(tex.value(1.25, 0.75, &p), Color::new(1.0, 0.0, 0.0));
+        tex.wrap = WrapMode::Clamp;
+        assert_eq!(tex.value(1.25, 0.75, &p), Color::new(0.0, 1.0, 0.0));
+        tex.wrap = WrapMode::Mirror;
+        assert_eq!(tex.value(1.25, 0.75, &p), Color::new(0.0, 1.0, 0.0));
+        assert_eq!(tex.value(1.75, 0.75, &p), Color::new(1.0, 0.0, 0.0));
+
+        assert_eq!("mirror".parse::<WrapMode>(), Ok(WrapMode::Mirror));
+        assert!("tile".parse::<WrapMode>().is_err());
+    }
+
+    #[test]
+    fn test_load_png() {
+        let dir = std::env::temp_dir().join(format!("ray1-texture-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("tex.png");
+        image::save_buffer(
+            &path,
+            &[255, 0, 0, 0, 0, 188],
+            2,
+            1,
+            image::ColorType::RGB(8),
+        )
+        .unwrap();
+
+        let mut tex = ImageTexture::load(&path).unwrap();
+        tex.filter = Filter::Nearest;
+        assert_eq!((tex.width, tex.height), (2, 1));
+        let p = Point3::zero();
+        assert_eq!(tex.value(0.25, 0.5, &p), Color::new(1.0, 0.0, 0.0));
+        // sRGB 188 is roughly 0.5 in linear light.
+        assert!((tex.value(0.75, 0.5, &p).z - 0.5).abs() < 0.01);
+
+        assert!(ImageTexture::load(dir.join("missing.png")).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_procedural_textures_stay_between_colors() {
+        let noise = NoiseTexture::new(1, 4.0);
+        let marble = MarbleTexture::new(1, 4.0);
+        let wood = WoodTexture::new(1, 8.0);
+        let mut worley = WorleyTexture::new(1, 3.0);
+        worley.metric = WorleyMetric::F2MinusF1;
+        let textures: [&dyn Texture; 4] = [&noise, &marble, &wood, &worley];
+
+        for i in 0..100 {
+            let t = i as f32 * 0.173;
+            let p = Point3::new(t.cos() * 2.0, t - 8.0, t.sin() * 3.0);
+            for tex in textures {
+                let c = tex.value(0.0, 0.0, &p);
+                for channel in [c.x, c.y, c.z] {
+                    assert!((0.0..=1.0).contains(&channel), "{:?}", c);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_procedural_textures_are_seeded() {
+        let p = Point3::new(0.3, 1.7, -2.2);
+        let value = |seed| MarbleTexture::new(seed, 4.0).value(0.0, 0.0, &p);
+        assert_eq!(value(9), value(9));
+        assert_ne!(value(9), value(10));
+        assert_eq!("f2-f1".parse::<WorleyMetric>(), Ok(WorleyMetric::F2MinusF1));
+    }
+
+    #[test]
+    fn test_grid_texture() {
+        let bounds = Aabb::from_points(Point3::zero(), Point3::ones());
+        let rgb = VoxelGrid::new([1, 1, 1], 3, vec![0.1, 0.2, 0.3], bounds);
+        let mut tex = GridTexture::new(Arc::new(rgb));
+        tex.tint = Color::new(2.0, 2.0, 2.0);
+        let inside = Point3::new(0.5, 0.5, 0.5);
+        assert!(close(
+            tex.value(0.0, 0.0, &inside),
+            Color::new(0.2, 0.4, 0.6)
+        ));
+        assert_eq!(
+            tex.value(0.0, 0.0, &Point3::new(2.0, 0.5, 0.5)),
+            Color::zero()
+        );
+
+        let gray = GridTexture::new(Arc::new(VoxelGrid::new([1, 1, 1], 1, vec![0.5], bounds)));
+        assert_eq!(gray.value(0.0, 0.0, &inside), Color::new(0.5, 0.5, 0.5));
+    }
+}
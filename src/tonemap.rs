@@ -0,0 +1,303 @@
+// Display transforms mapping linear scene radiance to encoded 8-bit output.
+use std::str::FromStr;
+
+use crate::{ray::Interval, vec3::Color};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToneMap {
+    // No curve; values above 1 are clipped.
+    Clamp,
+    Reinhard,
+    // Reinhard with `white` as the smallest luminance mapped to pure white.
+    ReinhardExtended { white: f32 },
+    // John Hable's Uncharted 2 filmic curve.
+    Hable,
+    // Stephen Hill's fit of the ACES RRT + sRGB ODT.
+    Aces,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Transfer {
+    Linear,
+    // Plain square root, the historical default.
+    Gamma2,
+    // Piecewise sRGB encoding.
+    Srgb,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DisplayTransform {
+    pub exposure: f32, // In stops; radiance is scaled by 2^exposure
+    pub tone_map: ToneMap,
+    pub transfer: Transfer,
+}
+
+impl Default for DisplayTransform {
+    // Matches the original clamp + square root encoding.
+    fn default() -> Self {
+        Self {
+            exposure: 0.0,
+            tone_map: ToneMap::Clamp,
+            transfer: Transfer::Gamma2,
+        }
+    }
+}
+
+impl DisplayTransform {
+    // Maps linear radiance to encoded display values in [0, 1].
+    pub fn apply(&self, radiance: Color) -> Color {
+        let exposed = radiance * self.exposure.exp2();
+        let mapped = self.tone_map.apply(exposed);
+        let unit = Interval::new(0.0, 1.0);
+        Color::new(
+            self.transfer.encode(unit.clamp(mapped.x)),
+            self.transfer.encode(unit.clamp(mapped.y)),
+            self.transfer.encode(unit.clamp(mapped.z)),
+        )
+    }
+
+    pub fn rgba(&self, radiance: Color) -> image::Rgba<u8> {
+        let intensity = Interval::new(0.0, 0.999);
+        let c = self.apply(radiance);
+        image::Rgba([
+            (intensity.clamp(c.x) * 255.99) as u8,
+            (intensity.clamp(c.y) * 255.99) as u8,
+            (intensity.clamp(c.z) * 255.99) as u8,
+            255,
+        ])
+    }
+}
+
+pub fn luminance(c: Color) -> f32 {
+    0.2126 * c.x + 0.7152 * c.y + 0.0722 * c.z
+}
+
+fn hable_partial(x: f32) -> f32 {
+    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+    ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+}
+
+fn mat_mul(m: &[[f32; 3]; 3], v: Color) -> Color {
+    Color::new(
+        m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+        m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+        m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+    )
+}
+
+// sRGB => XYZ => D65_2_D60 => AP1 => RRT_SAT
+const ACES_INPUT: [[f32; 3]; 3] = [
+    [0.59719, 0.35458, 0.04823],
+    [0.07600, 0.90834, 0.01566],
+    [0.02840, 0.13383, 0.83777],
+];
+
+// ODT_SAT => XYZ => D60_2_D65 => sRGB
+const ACES_OUTPUT: [[f32; 3]; 3] = [
+    [1.60475, -0.53108, -0.07367],
+    [-0.10208, 1.10813, -0.00605],
+    [-0.00327, -0.07276, 1.07602],
+];
+
+fn rrt_and_odt_fit(v: f32) -> f32 {
+    let a = v * (v + 0.0245786) - 0.000090537;
+    let b = v * (0.983729 * v + 0.432951) + 0.238081;
+    a / b
+}
+
+impl ToneMap {
+    pub fn apply(&self, c: Color) -> Color {
+        match *self {
+            ToneMap::Clamp => c,
+            ToneMap::Reinhard => Self::reinhard(c, f32::INFINITY),
+            ToneMap::ReinhardExtended { white } => Self::reinhard(c, white),
+            ToneMap::Hable => {
+                let exposure_bias = 2.0;
+                let white_scale = 1.0 / hable_partial(11.2);
+                Color::new(
+                    hable_partial(c.x * exposure_bias),
+                    hable_partial(c.y * exposure_bias),
+                    hable_partial(c.z * exposure_bias),
+                ) * white_scale
+            }
+            ToneMap::Aces => {
+                let v = mat_mul(&ACES_INPUT, c);
+                let v = Color::new(
+                    rrt_and_odt_fit(v.x),
+                    rrt_and_odt_fit(v.y),
+                    rrt_and_odt_fit(v.z),
+                );
+                let v = mat_mul(&ACES_OUTPUT, v);
+                let unit = Interval::new(0.0, 1.0);
+                Color::new(unit.clamp(v.x), unit.clamp(v.y), unit.clamp(v.z))
+            }
+        }
+    }
+
+    // Scales the color by the tone mapped luminance, preserving hue.
+    fn reinhard(c: Color, white: f32) -> Color {
+        let l_in = luminance(c);
+        if l_in <= 0.0 {
+            return Color::zero();
+        }
+        let l_out = l_in * (1.0 + l_in / (white * white)) / (1.0 + l_in);
+        c * (l_out / l_in)
+    }
+}
+
+impl Transfer {
+    pub fn encode(&self, x: f32) -> f32 {
+        match self {
+            Transfer::Linear => x,
+            Transfer::Gamma2 => {
+                if x > 0.0 {
+                    x.sqrt()
+                } else {
+                    0.0
+                }
+            }
+            Transfer::Srgb => {
+                if x <= 0.0031308 {
+                    12.92 * x
+                } else {
+                    1.055 * x.powf(1.0 / 2.4) - 0.055
+                }
+            }
+        }
+    }
+
+    // Inverse of `encode`, turning encoded values such as 8-bit texels back into linear ones.
+    pub fn decode(&self, x: f32) -> f32 {
+        match self {
+            Transfer::Linear => x,
+            Transfer::Gamma2 => x * x,
+            Transfer::Srgb => {
+                if x <= 0.04045 {
+                    x / 12.92
+                } else {
+                    ((x + 0.055) / 1.055).powf(2.4)
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for ToneMap {
+    type Err = String;
+
+    // Accepts `clamp`, `reinhard`, `reinhard-extended[:white]`, `hable` (or `filmic`) and `aces`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, arg) = match s.split_once(':') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (s, None),
+        };
+        match (name.to_ascii_lowercase().as_str(), arg) {
+            ("clamp", None) => Ok(ToneMap::Clamp),
+            ("reinhard", None) => Ok(ToneMap::Reinhard),
+            ("reinhard-extended", arg) => {
+                let white = match arg {
+                    Some(arg) => arg
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|w| *w > 0.0)
+                        .ok_or_else(|| format!("invalid white point `{}`", arg))?,
+                    None => 4.0,
+                };
+                Ok(ToneMap::ReinhardExtended { white })
+            }
+            ("hable" | "filmic", None) => Ok(ToneMap::Hable),
+            ("aces", None) => Ok(ToneMap::Aces),
+            _ => Err(format!("unknown tone map `{}`", s)),
+        }
+    }
+}
+
+impl FromStr for Transfer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "linear" => Ok(Transfer::Linear),
+            "gamma2" => Ok(Transfer::Gamma2),
+            "srgb" => Ok(Transfer::Srgb),
+            _ => Err(format!("unknown transfer function `{}`", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DisplayTransform, ToneMap, Transfer};
+    use crate::vec3::Color;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    #[test]
+    fn test_default_matches_vec3_rgba() {
+        let display = DisplayTransform::default();
+        for c in [
+            Color::new(0.0, 1.0, 0.5),
+            Color::new(2.0, 0.01, -1.0),
+            Color::new(0.25, 0.75, 0.9),
+        ] {
+            assert_eq!(display.rgba(c), c.rgba());
+        }
+    }
+
+    #[test]
+    fn test_srgb_transfer() {
+        assert_eq!(Transfer::Srgb.encode(0.0), 0.0);
+        assert!(close(Transfer::Srgb.encode(0.002), 0.02584));
+        assert!(close(Transfer::Srgb.encode(0.18), 0.46135));
+        assert!(close(Transfer::Srgb.encode(1.0), 1.0));
+        for x in [0.001, 0.18, 0.5, 1.0] {
+            assert!(close(Transfer::Srgb.decode(Transfer::Srgb.encode(x)), x));
+        }
+    }
+
+    #[test]
+    fn test_exposure() {
+        let display = DisplayTransform {
+            exposure: 1.0,
+            tone_map: ToneMap::Clamp,
+            transfer: Transfer::Linear,
+        };
+        assert_eq!(
+            display.apply(Color::new(0.25, 0.5, 1.0)),
+            Color::new(0.5, 1.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn test_curves_compress_highlights() {
+        let bright = Color::new(5.0, 5.0, 5.0);
+        for tone_map in [ToneMap::Reinhard, ToneMap::Hable, ToneMap::Aces] {
+            let mid = tone_map.apply(Color::new(0.18, 0.18, 0.18)).x;
+            let high = tone_map.apply(bright).x;
+            assert!(mid > 0.0 && mid < high, "{:?}", tone_map);
+            assert!(high <= 1.0, "{:?}", tone_map);
+        }
+
+        let extended = ToneMap::ReinhardExtended { white: 4.0 };
+        assert!(close(extended.apply(Color::new(4.0, 4.0, 4.0)).x, 1.0));
+        assert!(close(
+            ToneMap::Hable.apply(Color::new(5.6, 5.6, 5.6)).x,
+            1.0
+        ));
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("aces".parse::<ToneMap>(), Ok(ToneMap::Aces));
+        assert_eq!("Filmic".parse::<ToneMap>(), Ok(ToneMap::Hable));
+        assert_eq!(
+            "reinhard-extended:8".parse::<ToneMap>(),
+            Ok(ToneMap::ReinhardExtended { white: 8.0 })
+        );
+        assert!("reinhard-extended:-1".parse::<ToneMap>().is_err());
+        assert!("magic".parse::<ToneMap>().is_err());
+        assert_eq!("srgb".parse::<Transfer>(), Ok(Transfer::Srgb));
+    }
+}
@@ -0,0 +1,664 @@
+// Affine transforms and instances placing a shared hittable anywhere in the scene.
+use std::{ops::Mul, sync::Arc};
+
+use crate::{
+    aabb::Aabb,
+    ray::{HitRecord, Hittable, Interval, Ray},
+    rtweekend::degrees_to_radians,
+    vec3::{Point3, Vec3},
+};
+
+// Row-major 4x4 matrix acting on column vectors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mat4 {
+    pub m: [[f32; 4]; 4],
+}
+
+impl Mat4 {
+    pub fn new(m: [[f32; 4]; 4]) -> Self {
+        Self { m }
+    }
+
+    pub fn identity() -> Self {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            row[i] = 1.0;
+        }
+        Self { m }
+    }
+
+    pub fn translation(offset: Vec3) -> Self {
+        let mut t = Self::identity();
+        t.m[0][3] = offset.x;
+        t.m[1][3] = offset.y;
+        t.m[2][3] = offset.z;
+        t
+    }
+
+    pub fn scaling(factors: Vec3) -> Self {
+        let mut s = Self::identity();
+        s.m[0][0] = factors.x;
+        s.m[1][1] = factors.y;
+        s.m[2][2] = factors.z;
+        s
+    }
+
+    // Counterclockwise rotation by `degrees` about `axis`, looking down the axis at the origin
+    // (Rodrigues' formula).
+    pub fn rotation(axis: Vec3, degrees: f32) -> Self {
+        let a = axis.unit();
+        let theta = degrees_to_radians(degrees);
+        let (sin, cos) = theta.sin_cos();
+        let t = 1.0 - cos;
+        Self::new([
+            [
+                t * a.x * a.x + cos,
+                t * a.x * a.y - sin * a.z,
+                t * a.x * a.z + sin * a.y,
+                0.0,
+            ],
+            [
+                t * a.x * a.y + sin * a.z,
+                t * a.y * a.y + cos,
+                t * a.y * a.z - sin * a.x,
+                0.0,
+            ],
+            [
+                t * a.x * a.z - sin * a.y,
+                t * a.y * a.z + sin * a.x,
+                t * a.z * a.z + cos,
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut t = [[0.0; 4]; 4];
+        for (i, row) in t.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+        Self::new(t)
+    }
+
+    // Gauss-Jordan elimination with partial pivoting; None if the matrix is singular.
+    pub fn inverse(&self) -> Option<Self> {
+        let mut a = self.m;
+        let mut inv = Self::identity().m;
+
+        for col in 0..4 {
+            let pivot = (col..4)
+                .max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))
+                .unwrap();
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let scale = 1.0 / a[col][col];
+            for j in 0..4 {
+                a[col][j] *= scale;
+                inv[col][j] *= scale;
+            }
+            for row in 0..4 {
+                if row != col {
+                    let factor = a[row][col];
+                    for j in 0..4 {
+                        a[row][j] -= factor * a[col][j];
+                        inv[row][j] -= factor * inv[col][j];
+                    }
+                }
+            }
+        }
+        Some(Self::new(inv))
+    }
+
+    // Determinant of the upper-left 3x3 block: how much the linear part scales volumes.
+    pub fn linear_determinant(&self) -> f32 {
+        let m = &self.m;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    pub fn transform_point(&self, p: &Point3) -> Point3 {
+        let m = &self.m;
+        Point3::new(
+            m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3],
+            m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3],
+            m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3],
+        )
+    }
+
+    // Applies only the linear part, as for directions.
+    pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        )
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, rhs: Mat4) -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+            }
+        }
+        Mat4::new(m)
+    }
+}
+
+// An invertible affine transform, keeping its inverse and the matrix used for normals.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    matrix: Mat4,
+    inverse: Mat4,
+    normal_matrix: Mat4,
+}
+
+impl Transform {
+    // None if `matrix` can't be inverted, such as a scale by zero.
+    pub fn new(matrix: Mat4) -> Option<Self> {
+        let inverse = matrix.inverse()?;
+        Some(Self {
+            matrix,
+            inverse,
+            normal_matrix: inverse.transpose(),
+        })
+    }
+
+    pub fn identity() -> Self {
+        Self::new(Mat4::identity()).unwrap()
+    }
+
+    pub fn translate(offset: Vec3) -> Self {
+        Self::new(Mat4::translation(offset)).unwrap()
+    }
+
+    pub fn rotate(axis: Vec3, degrees: f32) -> Self {
+        Self::new(Mat4::rotation(axis, degrees)).unwrap()
+    }
+
+    // None if any factor is zero.
+    pub fn scale(factors: Vec3) -> Option<Self> {
+        Self::new(Mat4::scaling(factors))
+    }
+
+    // This transform followed by `next`.
+    pub fn then(&self, next: &Transform) -> Self {
+        let matrix = next.matrix * self.matrix;
+        let inverse = self.inverse * next.inverse;
+        Self {
+            matrix,
+            inverse,
+            normal_matrix: inverse.transpose(),
+        }
+    }
+
+    pub fn matrix(&self) -> &Mat4 {
+        &self.matrix
+    }
+
+    pub fn inverse(&self) -> &Mat4 {
+        &self.inverse
+    }
+
+    pub fn point(&self, p: &Point3) -> Point3 {
+        self.matrix.transform_point(p)
+    }
+
+    pub fn vector(&self, v: &Vec3) -> Vec3 {
+        self.matrix.transform_vector(v)
+    }
+
+    // Normals transform with the inverse transpose to stay perpendicular to scaled surfaces.
+    pub fn normal(&self, n: &Vec3) -> Vec3 {
+        self.normal_matrix.transform_vector(n).unit()
+    }
+
+    // The box around the transformed corners of `bbox`.
+    pub fn bounding_box(&self, bbox: &Aabb) -> Aabb {
+        if bbox.is_empty() {
+            return *bbox;
+        }
+        let mut min = Point3::vec_max();
+        let mut max = Point3::vec_min();
+        for i in 0..8 {
+            let corner = Point3::new(
+                if i & 1 == 0 { bbox.x.min } else { bbox.x.max },
+                if i & 2 == 0 { bbox.y.min } else { bbox.y.max },
+                if i & 4 == 0 { bbox.z.min } else { bbox.z.max },
+            );
+            let p = self.point(&corner);
+            for axis in 0..3 {
+                min[axis] = min[axis].min(p[axis]);
+                max[axis] = max[axis].max(p[axis]);
+            }
+        }
+        Aabb::from_points(min, max)
+    }
+}
+
+// One elementary transform whose parameter moves linearly from `from` at time 0 to `to` at
+// time 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Motion {
+    Translate { from: Vec3, to: Vec3 },
+    Rotate { axis: Vec3, from: f32, to: f32 },
+    Scale { from: Vec3, to: Vec3 },
+}
+
+impl Motion {
+    fn at(&self, time: f32) -> Transform {
+        match *self {
+            Motion::Translate { from, to } => Transform::translate(from + (to - from) * time),
+            Motion::Rotate { axis, from, to } => Transform::rotate(axis, from + (to - from) * time),
+            // A scale passing through zero has no inverse; fall back to the identity there.
+            Motion::Scale { from, to } => {
+                Transform::scale(from + (to - from) * time).unwrap_or_else(Transform::identity)
+            }
+        }
+    }
+}
+
+// A fixed `base` transform followed by motions applied in order, evaluated per ray time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimatedTransform {
+    pub base: Transform,
+    pub motions: Vec<Motion>,
+}
+
+// Times at which an animated bounding box is sampled over [0, 1].
+const MOTION_SAMPLES: usize = 64;
+
+impl AnimatedTransform {
+    pub fn new(base: Transform, motions: Vec<Motion>) -> Self {
+        Self { base, motions }
+    }
+
+    pub fn at(&self, time: f32) -> Transform {
+        self.motions.iter().fold(self.base, |transform, motion| {
+            transform.then(&motion.at(time))
+        })
+    }
+
+    // A box around `bbox` over the whole motion from time 0 to 1. The union of the sampled
+    // boxes is padded by the largest step any corner takes between samples, since the corners
+    // may follow curved paths in between.
+    pub fn bounding_box(&self, bbox: &Aabb) -> Aabb {
+        if bbox.is_empty() {
+            return *bbox;
+        }
+        let corners: Vec<Point3> = (0..8)
+            .map(|i| {
+                Point3::new(
+                    if i & 1 == 0 { bbox.x.min } else { bbox.x.max },
+                    if i & 2 == 0 { bbox.y.min } else { bbox.y.max },
+                    if i & 4 == 0 { bbox.z.min } else { bbox.z.max },
+                )
+            })
+            .collect();
+
+        let mut union = Aabb::default();
+        let mut previous: Option<Vec<Point3>> = None;
+        let mut max_step: f32 = 0.0;
+        for sample in 0..=MOTION_SAMPLES {
+            let transform = self.at(sample as f32 / MOTION_SAMPLES as f32);
+            let moved: Vec<Point3> = corners.iter().map(|c| transform.point(c)).collect();
+            if let Some(previous) = &previous {
+                for (a, b) in previous.iter().zip(&moved) {
+                    max_step = max_step.max((*b - *a).length());
+                }
+            }
+            union = Aabb::surrounding(&union, &transform.bounding_box(bbox));
+            previous = Some(moved);
+        }
+        Aabb::new(
+            union.x.expand(2.0 * max_step),
+            union.y.expand(2.0 * max_step),
+            union.z.expand(2.0 * max_step),
+        )
+    }
+}
+
+enum Placement {
+    Static(Transform),
+    Animated(AnimatedTransform),
+}
+
+// A hittable placed in the world by a transform; the object itself stays in its own space, so
+// many instances can share one copy of the geometry.
+pub struct Instance {
+    object: Arc<dyn Hittable>,
+    placement: Placement,
+    bbox: Aabb,
+}
+
+impl Instance {
+    pub fn new(object: Arc<dyn Hittable>, transform: Transform) -> Self {
+        let bbox = transform.bounding_box(&object.bounding_box());
+        Self {
+            object,
+            placement: Placement::Static(transform),
+            bbox,
+        }
+    }
+
+    // An instance whose transform changes over the shutter interval, for motion blur.
+    pub fn animated(object: Arc<dyn Hittable>, transform: AnimatedTransform) -> Self {
+        let bbox = transform.bounding_box(&object.bounding_box());
+        Self {
+            object,
+            placement: Placement::Animated(transform),
+            bbox,
+        }
+    }
+
+    // The transform placing the object at `time`.
+    fn transform_at(&self, time: f32) -> Transform {
+        match &self.placement {
+            Placement::Static(transform) => *transform,
+            Placement::Animated(animated) => animated.at(time),
+        }
+    }
+
+    // Moves the ray into object space. The direction isn't renormalized, so the ray parameter
+    // t means the same in both spaces.
+    fn object_ray(transform: &Transform, r: &Ray) -> Ray {
+        let inverse = transform.inverse();
+        Ray::with_time(
+            inverse.transform_point(&r.origin),
+            inverse.transform_vector(&r.direction),
+            r.time,
+        )
+    }
+
+    fn hit_with(
+        &self,
+        transform: &Transform,
+        r: &Ray,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+    ) -> bool {
+        if !self.object.hit(&Self::object_ray(transform, r), ray_t, rec) {
+            return false;
+        }
+
+        // Move the hit back to world space. The normal already faces against the ray, and an
+        // affine map preserves that.
+        rec.point = transform.point(&rec.point);
+        rec.normal = transform.normal(&rec.normal);
+
+        true
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        self.hit_with(&self.transform_at(r.time), r, ray_t, rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, time: f32) -> f32 {
+        let transform = self.transform_at(time);
+        let inverse = transform.inverse();
+        let object_direction = inverse.transform_vector(direction);
+        let object_pdf =
+            self.object
+                .pdf_value(&inverse.transform_point(origin), &object_direction, time);
+        if object_pdf == 0.0 {
+            return 0.0;
+        }
+
+        // Convert the density between solid angles: the linear map stretches a unit direction
+        // by |M u| and scales a cone of directions by |det M| / |M u|^3.
+        let stretch = direction.length() / object_direction.length();
+        let det = transform.matrix().linear_determinant().abs();
+        object_pdf * stretch * stretch * stretch / det
+    }
+
+    fn random(&self, origin: &Point3, time: f32, u: (f32, f32)) -> Vec3 {
+        let transform = self.transform_at(time);
+        let object_origin = transform.inverse().transform_point(origin);
+        transform.vector(&self.object.random(&object_origin, time, u))
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: Interval) -> f32 {
+        let object_ray = Self::object_ray(&self.transform_at(r.time), r);
+        self.object.transmittance(&object_ray, ray_t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{AnimatedTransform, Instance, Mat4, Motion, Transform};
+    use crate::material::Lambertian;
+    use crate::quad::{BoxShape, Quad};
+    use crate::ray::{HitRecord, Hittable, Interval, Ray, Sphere};
+    use crate::rtweekend::{degrees_to_radians, PI};
+    use crate::sampler::{IndependentSampler, Sampler};
+    use crate::vec3::{Color, Point3, Vec3};
+
+    fn close(a: Vec3, b: Vec3) -> bool {
+        (a - b).length() < 1e-4
+    }
+
+    fn gray() -> Arc<Lambertian> {
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    #[test]
+    fn test_rotation() {
+        let r = Transform::rotate(Vec3::new(0.0, 0.0, 2.0), 90.0);
+        assert!(close(
+            r.point(&Vec3::new(1.0, 0.0, 0.0)),
+            Vec3::new(0.0, 1.0, 0.0)
+        ));
+        let r = Transform::rotate(Vec3::new(0.0, 1.0, 0.0), 90.0);
+        assert!(close(
+            r.point(&Vec3::new(0.0, 0.0, 1.0)),
+            Vec3::new(1.0, 0.0, 0.0)
+        ));
+    }
+
+    #[test]
+    fn test_inverse_and_composition() {
+        let t = Transform::scale(Vec3::new(2.0, 1.0, 0.5))
+            .unwrap()
+            .then(&Transform::rotate(Vec3::new(1.0, 1.0, 0.0), 33.0))
+            .then(&Transform::translate(Vec3::new(3.0, -1.0, 2.0)));
+        let product = *t.matrix() * *t.inverse();
+        for (i, row) in product.m.iter().enumerate() {
+            for (j, value) in row.iter().enumerate() {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!((value - expected).abs() < 1e-5, "{:?}", product);
+            }
+        }
+
+        // Steps apply in order: scale, then rotate, then translate.
+        let p = Point3::new(1.0, 0.0, 0.0);
+        let s = Transform::scale(Vec3::new(2.0, 2.0, 2.0)).unwrap();
+        let moved = s.then(&Transform::translate(Vec3::new(1.0, 0.0, 0.0)));
+        assert_eq!(moved.point(&p), Point3::new(3.0, 0.0, 0.0));
+
+        assert!(Transform::scale(Vec3::new(1.0, 0.0, 1.0)).is_none());
+        assert!(Mat4::new([[0.0; 4]; 4]).inverse().is_none());
+    }
+
+    #[test]
+    fn test_normals_use_inverse_transpose() {
+        // Squash a 45 degree plane normal: it must stay perpendicular to the surface.
+        let t = Transform::scale(Vec3::new(1.0, 4.0, 1.0)).unwrap();
+        let n = t.normal(&Vec3::new(1.0, 1.0, 0.0).unit());
+        let tangent = t.vector(&Vec3::new(1.0, -1.0, 0.0));
+        assert!(n.dot(&tangent).abs() < 1e-5);
+        assert!((n.length() - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_instance_hit() {
+        let sphere: Arc<dyn Hittable> = Arc::new(Sphere::new(Point3::zero(), 1.0, gray()));
+        let t = Transform::scale(Vec3::new(2.0, 1.0, 1.0))
+            .unwrap()
+            .then(&Transform::translate(Vec3::new(0.0, 0.0, -10.0)));
+        let instance = Instance::new(sphere, t);
+
+        let bbox = instance.bounding_box();
+        assert!((bbox.x.min + 2.0).abs() < 1e-4 && (bbox.z.max + 9.0).abs() < 1e-4);
+
+        // Hit the stretched side of the ellipsoid head on.
+        let r = Ray::new(Point3::new(5.0, 0.0, -10.0), Vec3::new(-1.0, 0.0, 0.0));
+        let mut rec = HitRecord::default();
+        assert!(instance.hit(&r, Interval::new(0.001, f32::INFINITY), &mut rec));
+        assert!((rec.t - 3.0).abs() < 1e-5);
+        assert!(close(rec.point, Point3::new(2.0, 0.0, -10.0)));
+        assert!(close(rec.normal, Vec3::new(1.0, 0.0, 0.0)));
+        assert!(rec.front_face);
+
+        // An off-axis hit gets a normal that isn't just the object-space one.
+        let r = Ray::new(Point3::new(1.0, 5.0, -10.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(instance.hit(&r, Interval::new(0.001, f32::INFINITY), &mut rec));
+        let p = rec.point - Point3::new(0.0, 0.0, -10.0);
+        let expected = Vec3::new(p.x / 4.0, p.y, p.z).unit();
+        assert!(close(rec.normal, expected));
+    }
+
+    #[test]
+    fn test_rotated_box_bbox() {
+        let cube: Arc<dyn Hittable> = Arc::new(BoxShape::new(
+            Point3::zero(),
+            Point3::new(1.0, 1.0, 1.0),
+            gray(),
+        ));
+        let instance = Instance::new(cube, Transform::rotate(Vec3::new(0.0, 1.0, 0.0), 45.0));
+        let bbox = instance.bounding_box();
+        let diagonal = 2f32.sqrt();
+        assert!((bbox.x.size() - diagonal).abs() < 1e-4);
+        assert!((bbox.z.size() - diagonal).abs() < 1e-4);
+        assert!((bbox.y.size() - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_animated_instance() {
+        let sphere: Arc<dyn Hittable> = Arc::new(Sphere::new(Point3::zero(), 1.0, gray()));
+        let motion = AnimatedTransform::new(
+            Transform::translate(Vec3::new(0.0, 0.0, -10.0)),
+            vec![Motion::Translate {
+                from: Vec3::zero(),
+                to: Vec3::new(4.0, 0.0, 0.0),
+            }],
+        );
+        let instance = Instance::animated(sphere, motion);
+
+        let bbox = instance.bounding_box();
+        assert!(bbox.x.min <= -1.0 && bbox.x.max >= 5.0);
+
+        // The sphere has moved to x = 2 halfway through the shutter.
+        let hit_at = |x: f32, time: f32| {
+            let r = Ray::with_time(Point3::new(x, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0), time);
+            let mut rec = HitRecord::default();
+            instance.hit(&r, Interval::new(0.001, f32::INFINITY), &mut rec)
+        };
+        assert!(hit_at(0.0, 0.0) && !hit_at(0.0, 0.5) && !hit_at(0.0, 1.0));
+        assert!(!hit_at(2.0, 0.0) && hit_at(2.0, 0.5));
+        assert!(hit_at(4.0, 1.0));
+
+        // Light sampling finds the sphere where it is at the time of the sample.
+        let origin = Point3::new(4.0, 0.0, 0.0);
+        for _ in 0..100 {
+            let direction = instance.random(&origin, 1.0, IndependentSampler.get_2d());
+            let r = Ray::with_time(origin, direction, 1.0);
+            let mut rec = HitRecord::default();
+            assert!(instance.hit(&r, Interval::new(0.001, f32::INFINITY), &mut rec));
+            assert!(instance.pdf_value(&origin, &direction, 1.0) > 0.0);
+        }
+        let ahead = Vec3::new(0.0, 0.0, -1.0);
+        assert_eq!(instance.pdf_value(&origin, &ahead, 0.0), 0.0);
+    }
+
+    #[test]
+    fn test_animated_bbox_covers_rotation() {
+        let cube: Arc<dyn Hittable> = Arc::new(BoxShape::new(
+            Point3::new(2.0, 0.0, -0.5),
+            Point3::new(3.0, 1.0, 0.5),
+            gray(),
+        ));
+        let spin = AnimatedTransform::new(
+            Transform::identity(),
+            vec![Motion::Rotate {
+                axis: Vec3::new(0.0, 1.0, 0.0),
+                from: 0.0,
+                to: 180.0,
+            }],
+        );
+        let bbox = spin.bounding_box(&cube.bounding_box());
+        // Check every corner at many times in between the sampled ones.
+        for step in 0..=1000 {
+            let transform = spin.at(step as f32 / 1000.0);
+            for corner in [
+                Point3::new(3.0, 0.0, 0.5),
+                Point3::new(3.0, 1.0, -0.5),
+                Point3::new(2.0, 0.0, 0.5),
+            ] {
+                let p = transform.point(&corner);
+                assert!(bbox.x.contains(p.x) && bbox.y.contains(p.y) && bbox.z.contains(p.z));
+            }
+        }
+    }
+
+    #[test]
+    fn test_instance_light_sampling() {
+        // A quad stretched, rotated and moved keeps a normalized density matching its samples.
+        let quad: Arc<dyn Hittable> = Arc::new(Quad::new(
+            Point3::new(-0.5, -0.5, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            gray(),
+        ));
+        let transform = Transform::scale(Vec3::new(3.0, 1.5, 1.0))
+            .unwrap()
+            .then(&Transform::rotate(Vec3::new(1.0, 0.0, 0.0), 30.0))
+            .then(&Transform::translate(Vec3::new(0.0, 0.0, -2.0)));
+        let instance = Instance::new(quad, transform);
+        let origin = Point3::new(0.2, 0.1, 0.0);
+
+        let n = 200000;
+        let total: f32 = (0..n)
+            .map(|_| instance.pdf_value(&origin, &Vec3::random_unit_vec(), 0.0))
+            .sum::<f32>()
+            / n as f32
+            * 4.0
+            * PI;
+        assert!((total - 1.0).abs() < 0.05, "{}", total);
+
+        // The instance's quad is 4.5 units^2, so toward its center, 2 units away, the density
+        // is the area density times distance^2 / cos.
+        let ahead = Vec3::new(0.0, 0.0, -1.0);
+        let cos = degrees_to_radians(30.0).cos();
+        let expected = 4.0 / (cos * 4.5);
+        assert!((instance.pdf_value(&Point3::zero(), &ahead, 0.0) - expected).abs() < 1e-3);
+
+        for _ in 0..100 {
+            let direction = instance.random(&origin, 0.0, IndependentSampler.get_2d());
+            let mut rec = HitRecord::default();
+            let r = Ray::new(origin, direction);
+            assert!(instance.hit(&r, Interval::new(0.001, f32::INFINITY), &mut rec));
+            assert!((rec.t - 1.0).abs() < 1e-3);
+        }
+    }
+}
@@ -1,5 +1,4 @@
 #![allow(dead_code)]
-use std::f32::{MAX as f32_MAX, MIN as f32_MIN};
 use std::ops::{Add, AddAssign, Div, Index, IndexMut, Mul, Neg, Sub};
 
 use crate::ray::Interval;
@@ -13,6 +12,7 @@ pub struct Vec3 {
 }
 
 pub type Point3 = Vec3;
+pub type Color = Vec3;
 
 impl Add for Vec3 {
     type Output = Self;
@@ -123,17 +123,17 @@ impl Vec3 {
 
     pub fn vec_max() -> Vec3 {
         Vec3 {
-            x: f32_MAX,
-            y: f32_MAX,
-            z: f32_MAX,
+            x: f32::MAX,
+            y: f32::MAX,
+            z: f32::MAX,
         }
     }
 
     pub fn vec_min() -> Vec3 {
         Vec3 {
-            x: f32_MIN,
-            y: f32_MIN,
-            z: f32_MIN,
+            x: f32::MIN,
+            y: f32::MIN,
+            z: f32::MIN,
         }
     }
 
@@ -195,6 +195,10 @@ impl Vec3 {
         ])
     }
 
+    pub fn max_component(&self) -> f32 {
+        self.x.max(self.y).max(self.z)
+    }
+
     pub fn elemul(a: Vec3, b: Vec3) -> Vec3 {
         Vec3::new(a.x * b.x, a.y * b.y, a.z * b.z)
     }
@@ -225,6 +229,19 @@ impl Vec3 {
         }
     }
 
+    pub fn random_in_unit_disk() -> Vec3 {
+        loop {
+            let p = Vec3::new(
+                random_double_in_range(-1.0, 1.0),
+                random_double_in_range(-1.0, 1.0),
+                0.0,
+            );
+            if p.squared_length() < 1.0 {
+                return p;
+            }
+        }
+    }
+
     pub fn random_on_hemisphere(normal: &Vec3) -> Vec3 {
         let on_unit_sphere = Self::random_unit_vec();
         if on_unit_sphere.dot(normal) > 0.0 {
@@ -233,6 +250,35 @@ impl Vec3 {
             -on_unit_sphere
         }
     }
+
+    // Two unit vectors completing this unit vector to a right-handed orthonormal frame.
+    pub fn tangent_frame(&self) -> (Vec3, Vec3) {
+        // Any vector not parallel to this one seeds the frame.
+        let seed = if self.x.abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let tangent = self.cross(&seed).unit();
+        (tangent, self.cross(&tangent))
+    }
+
+    pub fn near_zero(&self) -> bool {
+        // Return true if the vector is close to zero in all dimensions.
+        let s = 1e-8;
+        self.x.abs() < s && self.y.abs() < s && self.z.abs() < s
+    }
+
+    pub fn reflect(v: &Vec3, n: &Vec3) -> Vec3 {
+        *v - *n * (2.0 * v.dot(n))
+    }
+
+    pub fn refract(uv: &Vec3, n: &Vec3, etai_over_etat: f32) -> Vec3 {
+        let cos_theta = (-*uv).dot(n).min(1.0);
+        let r_out_perp = (*uv + *n * cos_theta) * etai_over_etat;
+        let r_out_parallel = *n * -(1.0 - r_out_perp.squared_length()).abs().sqrt();
+        r_out_perp + r_out_parallel
+    }
 }
 
 #[cfg(test)]
@@ -313,7 +359,7 @@ mod tests {
     fn test_rgba() {
         assert_eq!(
             Vec3::new(0.0, 1.0, 0.5).rgba(),
-            image::Rgba([0 as u8, 255 as u8, 127 as u8, 255 as u8])
+            image::Rgba([0_u8, 255, 181, 255])
         );
     }
 
@@ -325,6 +371,22 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_reflect() {
+        assert_eq!(
+            Vec3::reflect(&Vec3::new(1.0, -1.0, 0.0), &Vec3::new(0.0, 1.0, 0.0)),
+            Vec3::new(1.0, 1.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_refract() {
+        // Matching indices leave the direction unchanged.
+        let uv = Vec3::new(1.0, -1.0, 0.0).unit();
+        let refracted = Vec3::refract(&uv, &Vec3::new(0.0, 1.0, 0.0), 1.0);
+        assert!((refracted - uv).length() < 1e-6);
+    }
+
     #[test]
     fn test_index() {
         let vec = Vec3::new(1.0, 2.0, 3.0);
@@ -348,4 +410,18 @@ mod tests {
         let result = std::panic::catch_unwind(|| vec[5]);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_tangent_frame() {
+        for n in [
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            Vec3::new(1.0, 2.0, -3.0).unit(),
+        ] {
+            let (t, b) = n.tangent_frame();
+            assert!((t.length() - 1.0).abs() < 1e-6 && (b.length() - 1.0).abs() < 1e-6);
+            assert!(t.dot(&n).abs() < 1e-6 && b.dot(&n).abs() < 1e-6 && t.dot(&b).abs() < 1e-6);
+            assert!((t.cross(&b) - n).length() < 1e-5);
+        }
+    }
 }
@@ -0,0 +1,614 @@
+// Heterogeneous participating media: density from voxel grids or noise, rendered with delta
+// tracking and ratio tracking against a majorant.
+use std::{
+    error::Error,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+
+use crate::{
+    aabb::Aabb,
+    material::Material,
+    noise::Perlin,
+    ray::{HitRecord, Hittable, Interval, Ray},
+    rtweekend::random_double,
+    vec3::{Point3, Vec3},
+};
+
+#[derive(Debug)]
+pub enum GridError {
+    Io { path: PathBuf, source: io::Error },
+    Format { path: PathBuf, message: String },
+}
+
+impl fmt::Display for GridError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GridError::Io { path, source } => {
+                write!(f, "failed to read {}: {}", path.display(), source)
+            }
+            GridError::Format { path, message } => write!(f, "{}: {}", path.display(), message),
+        }
+    }
+}
+
+impl Error for GridError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            GridError::Io { source, .. } => Some(source),
+            GridError::Format { .. } => None,
+        }
+    }
+}
+
+// How voxel values are stored in a file. Bytes map to [0, 1].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GridEncoding {
+    U8,
+    F32,
+}
+
+impl GridEncoding {
+    fn value_size(&self) -> usize {
+        match self {
+            GridEncoding::U8 => 1,
+            GridEncoding::F32 => 4,
+        }
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Vec<f32> {
+        match self {
+            GridEncoding::U8 => bytes.iter().map(|&b| b as f32 / 255.0).collect(),
+            GridEncoding::F32 => bytes
+                .chunks_exact(4)
+                .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+                .collect(),
+        }
+    }
+}
+
+impl FromStr for GridEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "u8" => Ok(GridEncoding::U8),
+            "f32" => Ok(GridEncoding::F32),
+            _ => Err(format!("unknown grid encoding `{}`", s)),
+        }
+    }
+}
+
+// Voxel values with one or more channels stretched over `bounds`. Voxels are sampled at
+// their centers and interpolated trilinearly; x varies fastest in memory, then y, then z.
+pub struct VoxelGrid {
+    resolution: [usize; 3],
+    channels: usize,
+    data: Vec<f32>,
+    bounds: Aabb,
+    max: f32,
+}
+
+impl VoxelGrid {
+    pub fn new(resolution: [usize; 3], channels: usize, data: Vec<f32>, bounds: Aabb) -> Self {
+        assert_eq!(
+            data.len(),
+            resolution.iter().product::<usize>() * channels,
+            "voxel data doesn't match the grid resolution"
+        );
+        let max = data
+            .iter()
+            .step_by(channels.max(1))
+            .fold(0.0f32, |m, &v| m.max(v));
+        Self {
+            resolution,
+            channels,
+            data,
+            bounds,
+            max,
+        }
+    }
+
+    // Loads a grid in the binary `.vol` format: the bytes "VOL" and version 3, then as
+    // little-endian values the encoding (1 for f32, 3 for u8), the x, y and z resolution and
+    // the channel count as i32, the bounds as six f32 (min x, y, z, then max x, y, z), and
+    // finally the voxel values.
+    pub fn load_vol<P: AsRef<Path>>(path: P) -> Result<Self, GridError> {
+        let path = path.as_ref();
+        let bytes = read_file(path)?;
+        let error = |message: &str| GridError::Format {
+            path: path.to_path_buf(),
+            message: message.to_string(),
+        };
+
+        const HEADER: usize = 48;
+        if bytes.len() < HEADER || &bytes[..3] != b"VOL" {
+            return Err(error("not a .vol file"));
+        }
+        if bytes[3] != 3 {
+            return Err(error("only version 3 .vol files are supported"));
+        }
+        let word = |i: usize| {
+            let at = 4 + 4 * i;
+            [bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]]
+        };
+        let int = |i: usize| i32::from_le_bytes(word(i));
+        let float = |i: usize| f32::from_le_bytes(word(i));
+
+        let encoding = match int(0) {
+            1 => GridEncoding::F32,
+            3 => GridEncoding::U8,
+            _ => return Err(error("unsupported encoding; expected 1 (f32) or 3 (u8)")),
+        };
+        let (nx, ny, nz, channels) = (int(1), int(2), int(3), int(4));
+        if nx <= 0 || ny <= 0 || nz <= 0 || channels <= 0 {
+            return Err(error("resolution and channel count must be positive"));
+        }
+        let resolution = [nx as usize, ny as usize, nz as usize];
+        let (min, max) = (
+            Point3::new(float(5), float(6), float(7)),
+            Point3::new(float(8), float(9), float(10)),
+        );
+        if !(0..3).all(|i| min[i].is_finite() && max[i].is_finite() && min[i] < max[i]) {
+            return Err(error(
+                "bounds must be finite, with min below max on every axis",
+            ));
+        }
+        let bounds = Aabb::from_points(min, max);
+
+        let count = resolution.iter().product::<usize>() * channels as usize;
+        let body = &bytes[HEADER..];
+        if body.len() != count * encoding.value_size() {
+            return Err(error(&format!(
+                "expected {} voxel values, found {} bytes",
+                count,
+                body.len()
+            )));
+        }
+        let data = encoding.decode(body);
+        check_values(&data).map_err(|message| error(&message))?;
+        Ok(Self::new(resolution, channels as usize, data, bounds))
+    }
+
+    // Loads a headerless single-channel grid of `resolution` values.
+    pub fn load_raw<P: AsRef<Path>>(
+        path: P,
+        resolution: [usize; 3],
+        encoding: GridEncoding,
+        bounds: Aabb,
+    ) -> Result<Self, GridError> {
+        let path = path.as_ref();
+        let bytes = read_file(path)?;
+        let count = resolution.iter().product::<usize>();
+        if bytes.len() != count * encoding.value_size() {
+            return Err(GridError::Format {
+                path: path.to_path_buf(),
+                message: format!(
+                    "expected {} bytes for a {}x{}x{} grid, found {}",
+                    count * encoding.value_size(),
+                    resolution[0],
+                    resolution[1],
+                    resolution[2],
+                    bytes.len()
+                ),
+            });
+        }
+        let data = encoding.decode(&bytes);
+        check_values(&data).map_err(|message| GridError::Format {
+            path: path.to_path_buf(),
+            message,
+        })?;
+        Ok(Self::new(resolution, 1, data, bounds))
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        self.bounds
+    }
+
+    // Stretches the grid over other bounds, e.g. to place a raw grid in the scene.
+    pub fn set_bounds(&mut self, bounds: Aabb) {
+        self.bounds = bounds;
+    }
+
+    fn voxel(&self, x: usize, y: usize, z: usize, channel: usize) -> f32 {
+        let [nx, ny, _] = self.resolution;
+        self.data[((z * ny + y) * nx + x) * self.channels + channel]
+    }
+
+    // The interpolated value of `channel` at `p`, or zero outside the bounds.
+    pub fn sample(&self, p: &Point3, channel: usize) -> f32 {
+        let b = &self.bounds;
+        if !(b.x.contains(p.x) && b.y.contains(p.y) && b.z.contains(p.z)) {
+            return 0.0;
+        }
+        let channel = channel.min(self.channels - 1);
+
+        // Per axis, the two neighboring voxel indices and the weight of the second.
+        let axis = |a: usize| {
+            let interval = b.axis_interval(a);
+            let n = self.resolution[a];
+            let x = (p[a] - interval.min) / interval.size() * n as f32 - 0.5;
+            let i = x.floor();
+            let w = x - i;
+            let clamp = |i: f32| (i.max(0.0) as usize).min(n - 1);
+            (clamp(i), clamp(i + 1.0), w)
+        };
+        let (x0, x1, wx) = axis(0);
+        let (y0, y1, wy) = axis(1);
+        let (z0, z1, wz) = axis(2);
+
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        let plane = |z: usize| {
+            lerp(
+                lerp(
+                    self.voxel(x0, y0, z, channel),
+                    self.voxel(x1, y0, z, channel),
+                    wx,
+                ),
+                lerp(
+                    self.voxel(x0, y1, z, channel),
+                    self.voxel(x1, y1, z, channel),
+                    wx,
+                ),
+                wy,
+            )
+        };
+        lerp(plane(z0), plane(z1), wz)
+    }
+}
+
+// Voxels must be finite and not negative: a negative density would let tracking accept more
+// collisions than the majorant allows and push transmittance above 1.
+fn check_values(data: &[f32]) -> Result<(), String> {
+    match data.iter().position(|v| !(v.is_finite() && *v >= 0.0)) {
+        Some(index) => Err(format!(
+            "voxel value {} at index {} must be finite and not negative",
+            data[index], index
+        )),
+        None => Ok(()),
+    }
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>, GridError> {
+    fs::read(path).map_err(|source| GridError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+// Density of a medium in its own space, bounded so tracking can sample against a majorant.
+pub trait DensityField: Send + Sync {
+    fn density(&self, p: &Point3) -> f32;
+
+    // An upper bound on `density` everywhere.
+    fn max_density(&self) -> f32;
+}
+
+impl DensityField for VoxelGrid {
+    fn density(&self, p: &Point3) -> f32 {
+        self.sample(p, 0)
+    }
+
+    fn max_density(&self) -> f32 {
+        self.max
+    }
+}
+
+// Billowing cloud-like density from fractal Perlin noise. Noise values below `threshold` are
+// empty space, and the rest ramps up to a density of 1.
+pub struct NoiseDensity {
+    perlin: Perlin,
+    scale: f32,
+    pub octaves: u32,
+    pub threshold: f32,
+}
+
+impl NoiseDensity {
+    pub fn new(seed: u64, scale: f32) -> Self {
+        Self {
+            perlin: Perlin::new(seed),
+            scale,
+            octaves: 5,
+            threshold: 0.4,
+        }
+    }
+}
+
+impl DensityField for NoiseDensity {
+    fn density(&self, p: &Point3) -> f32 {
+        let n = 0.5 + 0.5 * self.perlin.fbm(&(*p * self.scale), self.octaves, 2.0, 0.5);
+        ((n - self.threshold) / (1.0 - self.threshold)).clamp(0.0, 1.0)
+    }
+
+    fn max_density(&self) -> f32 {
+        1.0
+    }
+}
+
+// A medium whose extinction coefficient is `extinction` times the field's density, filling
+// `bounds`. Collisions scatter or absorb by `material`, normally a `VolumeMaterial`.
+pub struct HeterogeneousVolume {
+    bounds: Aabb,
+    field: Arc<dyn DensityField>,
+    extinction: f32,
+    material: Arc<dyn Material>,
+}
+
+impl HeterogeneousVolume {
+    pub fn new(
+        bounds: Aabb,
+        field: Arc<dyn DensityField>,
+        extinction: f32,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        Self {
+            bounds,
+            field,
+            extinction,
+            material,
+        }
+    }
+
+    // A volume filling the grid's own bounds.
+    pub fn from_grid(grid: Arc<VoxelGrid>, extinction: f32, material: Arc<dyn Material>) -> Self {
+        Self::new(grid.bounds(), grid, extinction, material)
+    }
+
+    fn majorant(&self) -> f32 {
+        self.extinction * self.field.max_density()
+    }
+
+    // Distance along the ray, in units of t, to the next tentative collision against the
+    // majorant. Like the acceptance tests, it comes from the random stream, not a `Sampler`.
+    fn free_flight(&self, r: &Ray) -> f32 {
+        -(1.0 - random_double()).ln() / (self.majorant() * r.direction.length())
+    }
+}
+
+impl Hittable for HeterogeneousVolume {
+    // Delta tracking: step through tentative collisions against the majorant and accept each
+    // as a real collision with probability density over majorant.
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        let Some(inside) = self.bounds.ray_interval(r, ray_t) else {
+            return false;
+        };
+        if self.majorant() <= 0.0 {
+            return false;
+        }
+        let mut t = inside.min;
+        loop {
+            t += self.free_flight(r);
+            if t >= inside.max {
+                return false;
+            }
+            let p = r.at(t);
+            let density = self.extinction * self.field.density(&p);
+            if random_double() * self.majorant() < density {
+                rec.t = t;
+                rec.point = p;
+                // A scattering event has no surface, so the normal and side are arbitrary.
+                rec.normal = Vec3::new(1.0, 0.0, 0.0);
+                rec.front_face = true;
+                rec.u = 0.0;
+                rec.v = 0.0;
+                rec.mat = Some(self.material.clone());
+                return true;
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounds
+    }
+
+    // Ratio tracking: every tentative collision only scales the estimate, so unlike delta
+    // tracking it is never zero by chance and shadow rays through thin media stay smooth.
+    fn transmittance(&self, r: &Ray, ray_t: Interval) -> f32 {
+        let Some(inside) = self.bounds.ray_interval(r, ray_t) else {
+            return 1.0;
+        };
+        if self.majorant() <= 0.0 {
+            return 1.0;
+        }
+        let mut transmittance = 1.0;
+        let mut t = inside.min;
+        loop {
+            t += self.free_flight(r);
+            if t >= inside.max {
+                return transmittance;
+            }
+            let density = self.extinction * self.field.density(&r.at(t));
+            transmittance *= 1.0 - density / self.majorant();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, sync::Arc};
+
+    use super::{DensityField, GridEncoding, HeterogeneousVolume, NoiseDensity, VoxelGrid};
+    use crate::aabb::Aabb;
+    use crate::bvh::BvhNode;
+    use crate::material::Isotropic;
+    use crate::ray::{HitRecord, Hittable, HittableList, Interval, Ray, Sphere};
+    use crate::vec3::{Color, Point3, Vec3};
+
+    fn unit_bounds() -> Aabb {
+        Aabb::from_points(Point3::zero(), Point3::ones())
+    }
+
+    fn vol_file(encoding: i32, resolution: [i32; 3], channels: i32, body: &[u8]) -> Vec<u8> {
+        let mut bytes = b"VOL\x03".to_vec();
+        for v in [
+            encoding,
+            resolution[0],
+            resolution[1],
+            resolution[2],
+            channels,
+        ] {
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+        for v in [0.0f32, 0.0, 0.0, 2.0, 1.0, 1.0] {
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+        bytes.extend_from_slice(body);
+        bytes
+    }
+
+    #[test]
+    fn test_load_grids() {
+        let dir = std::env::temp_dir().join(format!("ray1-volume-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        // Two voxels along x: 0.25 and 0.75.
+        let body: Vec<u8> = [0.25f32, 0.75]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let path = dir.join("two.vol");
+        fs::write(&path, vol_file(1, [2, 1, 1], 1, &body)).unwrap();
+        let grid = VoxelGrid::load_vol(&path).unwrap();
+        assert_eq!((grid.resolution, grid.channels()), ([2, 1, 1], 1));
+        assert_eq!(grid.max, 0.75);
+        // Voxel centers are at x = 0.5 and 1.5 in bounds spanning [0, 2].
+        let at = |x: f32| grid.sample(&Point3::new(x, 0.5, 0.5), 0);
+        assert_eq!((at(0.5), at(1.0), at(1.5)), (0.25, 0.5, 0.75));
+        assert_eq!((at(0.0), at(2.0)), (0.25, 0.75));
+        assert_eq!(at(2.5), 0.0);
+
+        fs::write(&path, vol_file(1, [2, 2, 1], 1, &body)).unwrap();
+        assert!(VoxelGrid::load_vol(&path).is_err());
+        let negative: Vec<u8> = [0.25f32, -0.5]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        fs::write(&path, vol_file(1, [2, 1, 1], 1, &negative)).unwrap();
+        assert!(VoxelGrid::load_vol(&path).is_err());
+        let nan: Vec<u8> = [0.25f32, f32::NAN]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        fs::write(&path, vol_file(1, [2, 1, 1], 1, &nan)).unwrap();
+        assert!(VoxelGrid::load_vol(&path).is_err());
+        // Flatten the bounds along x, then make them infinite.
+        let mut flat = vol_file(1, [2, 1, 1], 1, &body);
+        flat[36..40].copy_from_slice(&0.0f32.to_le_bytes());
+        fs::write(&path, &flat).unwrap();
+        assert!(VoxelGrid::load_vol(&path).is_err());
+        flat[36..40].copy_from_slice(&f32::INFINITY.to_le_bytes());
+        fs::write(&path, &flat).unwrap();
+        assert!(VoxelGrid::load_vol(&path).is_err());
+        fs::write(&path, b"VOX\x03").unwrap();
+        assert!(VoxelGrid::load_vol(&path).is_err());
+
+        let path = dir.join("ramp.raw");
+        fs::write(&path, [0u8, 255, 51, 102]).unwrap();
+        let grid = VoxelGrid::load_raw(&path, [2, 2, 1], GridEncoding::U8, unit_bounds()).unwrap();
+        assert_eq!(grid.sample(&Point3::new(0.75, 0.25, 0.5), 0), 1.0);
+        assert!((grid.sample(&Point3::new(0.25, 0.75, 0.5), 0) - 0.2).abs() < 1e-6);
+        assert!(VoxelGrid::load_raw(&path, [3, 2, 1], GridEncoding::U8, unit_bounds()).is_err());
+        fs::write(&path, (-1.0f32).to_le_bytes()).unwrap();
+        assert!(VoxelGrid::load_raw(&path, [1, 1, 1], GridEncoding::F32, unit_bounds()).is_err());
+    }
+
+    // A volume over the unit cube whose density rises linearly from 0 to 1 along x.
+    fn ramp_volume(extinction: f32) -> HeterogeneousVolume {
+        let n = 64;
+        let data = (0..n * n * n)
+            .map(|i| ((i % n) as f32 + 0.5) / n as f32)
+            .collect();
+        let grid = Arc::new(VoxelGrid::new([n, n, n], 1, data, unit_bounds()));
+        let phase = Arc::new(Isotropic::new(Color::ones()));
+        HeterogeneousVolume::from_grid(grid, extinction, phase)
+    }
+
+    #[test]
+    fn test_delta_and_ratio_tracking_agree() {
+        let volume = ramp_volume(4.0);
+        // Across the ramp, the optical depth is 4 * 0.5 = 2.
+        let r = Ray::new(Point3::new(-1.0, 0.5, 0.5), Vec3::new(1.0, 0.0, 0.0));
+        let ray_t = Interval::new(0.001, f32::INFINITY);
+        let expected = (-2.0f32).exp();
+
+        let n = 20000;
+        let escaped = (0..n)
+            .filter(|_| {
+                let mut rec = HitRecord::default();
+                let hit = volume.hit(&r, ray_t, &mut rec);
+                if hit {
+                    assert!((0.0..=1.0).contains(&rec.point.x));
+                }
+                !hit
+            })
+            .count();
+        assert!(
+            (escaped as f32 / n as f32 - expected).abs() < 0.01,
+            "{}",
+            escaped
+        );
+
+        let ratio: f32 = (0..n).map(|_| volume.transmittance(&r, ray_t)).sum::<f32>() / n as f32;
+        assert!((ratio - expected).abs() < 0.01, "{}", ratio);
+
+        // The thin end alone has an optical depth of 4 * 0.125 = 0.5.
+        let thin = Ray::new(Point3::new(0.25, -1.0, 0.5), Vec3::new(0.0, 1.0, 0.0));
+        let ratio: f32 = (0..n)
+            .map(|_| volume.transmittance(&thin, ray_t))
+            .sum::<f32>()
+            / n as f32;
+        assert!((ratio - (-1.0f32).exp()).abs() < 0.01, "{}", ratio);
+    }
+
+    #[test]
+    fn test_shadow_rays_use_ratio_tracking() {
+        // Behind a list and a BVH, the volume still gives fractional transmittance estimates,
+        // while a solid sphere in the way blocks everything.
+        let volume: Arc<dyn Hittable> = Arc::new(ramp_volume(4.0));
+        let r = Ray::new(Point3::new(-1.0, 0.5, 0.5), Vec3::new(1.0, 0.0, 0.0));
+        let ray_t = Interval::new(0.001, f32::INFINITY);
+        let mut list = HittableList::from_object(volume.clone());
+        let bvh = BvhNode::new(vec![volume]);
+        for world in [&list as &dyn Hittable, &bvh] {
+            let estimates: Vec<f32> = (0..1000).map(|_| world.transmittance(&r, ray_t)).collect();
+            assert!(estimates.iter().all(|t| (0.0..=1.0).contains(t)));
+            assert!(estimates.iter().filter(|&&t| t > 0.0 && t < 1.0).count() > 900);
+        }
+
+        let mat = Arc::new(Isotropic::new(Color::ones()));
+        list.add(Arc::new(Sphere::new(Point3::new(3.0, 0.5, 0.5), 0.5, mat)));
+        assert_eq!(list.transmittance(&r, ray_t), 0.0);
+    }
+
+    #[test]
+    fn test_empty_volume_is_transparent() {
+        let volume = ramp_volume(0.0);
+        let r = Ray::new(Point3::new(-1.0, 0.5, 0.5), Vec3::new(1.0, 0.0, 0.0));
+        let ray_t = Interval::new(0.001, f32::INFINITY);
+        assert!(!volume.hit(&r, ray_t, &mut HitRecord::default()));
+        assert_eq!(volume.transmittance(&r, ray_t), 1.0);
+    }
+
+    #[test]
+    fn test_noise_density_is_bounded() {
+        let noise = NoiseDensity::new(3, 2.0);
+        let mut empty = 0;
+        for i in 0..500 {
+            let t = i as f32 * 0.173;
+            let p = Point3::new(t.sin() * 3.0, t * 0.2, t.cos() * 2.0);
+            let d = noise.density(&p);
+            assert!((0.0..=noise.max_density()).contains(&d));
+            if d == 0.0 {
+                empty += 1;
+            }
+        }
+        // The threshold carves out gaps between the clouds.
+        assert!(empty > 0 && empty < 500);
+    }
+}